@@ -23,6 +23,11 @@
 GlobalData = {}
 
 function on_map_peeked(map)
+    -- Boss arenas are hand-tuned encounters, keep spawners out of them
+    if map:is_boss_arena() then
+        return
+    end
+
     if not GlobalData.SPAWNERS then
         GlobalData.SPAWNERS = {}
     end
@@ -42,6 +47,8 @@ function on_map_peeked(map)
         -- select a random tile from the shuffled list
         local tile = tiles[i]
         local monster = map:add_monster(0, tile)
-        GlobalData.SPAWNERS[monster:get_id()] = monster_kinds[i % #monster_kinds + 1]
+        if monster then
+            GlobalData.SPAWNERS[monster:get_id()] = monster_kinds[i % #monster_kinds + 1]
+        end
     end
 end
\ No newline at end of file
@@ -31,17 +31,27 @@ Position = {}
 function on_spawn(monster) end
 
 ---@param monster Monster
----@param update_iteration integer -- The current update iteration for this turn, starting from 0.
----@return boolean
+---@param update_iteration integer -- How many actions this monster already took since the player last acted, starting from 0.
+---@return boolean|integer -- true when handled as a regular action, or the time the action took (100 is a regular action).
 function on_update(monster, update_iteration) end
 
 ---@param monster Monster
 ---@return boolean
 function on_death(monster) end
 
+---@param monster Monster
+---@param phase integer -- The phase the boss just entered, starting from 2.
+---@return boolean
+function on_phase_change(monster, phase) end
+
 ---@param map Map
 function on_map_peeked(map) end
 
+---@param creature Player|Monster -- Whoever stepped on the trap.
+---@param tile Position -- Where the trap is.
+---@return integer|nil -- Damage dealt to `creature`, nothing for none.
+function on_step(creature, tile) end
+
 ---@param weapon Weapon
 ---@param player Player
 ---@param target Player|Monster
@@ -61,4 +71,9 @@ function find_monster_path(monster) end
 
 ---@param creature_id integer
 ---@param position Position
-function teleport_creature_to(creature_id, position) end
\ No newline at end of file
+function teleport_creature_to(creature_id, position) end
+
+---@param delay integer -- Time units until the event fires, a regular action takes 100.
+---@param callback fun()
+---@return integer -- The event id
+function schedule_event(delay, callback) end
\ No newline at end of file
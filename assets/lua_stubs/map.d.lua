@@ -31,4 +31,19 @@ function Map:get_random_adjacent_position(position, flying) end
 
 ---@param monster_type_id integer
 ---@param pos Position
-function Map:add_monster(monster_type_id, pos) end
\ No newline at end of file
+---@param spawner Monster|nil -- When given, the new monster counts towards this spawner's cap.
+---@return Monster|nil -- nil when the map or spawner population cap is reached.
+function Map:add_monster(monster_type_id, pos, spawner) end
+
+---@return integer
+function Map:get_population() end
+
+---@return integer
+function Map:get_population_cap() end
+
+---@return boolean
+function Map:is_boss_arena() end
+
+---@param pos Position
+---@return boolean -- false when the terrain there can't be destroyed.
+function Map:destroy_terrain(pos) end
\ No newline at end of file
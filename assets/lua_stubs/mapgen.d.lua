@@ -0,0 +1,89 @@
+-- SPDX-License-Identifier: MIT
+--
+-- Copyright (c) 2025 Alexandre Severino
+--
+-- Permission is hereby granted, free of charge, to any person obtaining a copy
+-- of this software and associated documentation files (the "Software"), to deal
+-- in the Software without restriction, including without limitation the rights
+-- to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+-- copies of the Software, and to permit persons to whom the Software is
+-- furnished to do so, subject to the following conditions:
+--
+-- The above copyright notice and this permission notice shall be included in
+-- all copies or substantial portions of the Software.
+--
+-- THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+-- IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+-- FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+-- AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+-- LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+-- OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+-- SOFTWARE.
+
+---@meta
+
+---@class MapGenParams
+---@field width integer
+---@field height integer
+---@field tier integer
+---@field theme string
+---@field radius integer
+---@field num_walks integer
+---@field walk_length integer
+---@field anchors Position[] -- Entrances and fixed start positions, linked to the layout after on_generate.
+
+---@class MapGrid
+MapGrid = {}
+
+---@return integer
+function MapGrid:width() end
+
+---@return integer
+function MapGrid:height() end
+
+---@param x integer
+---@param y integer
+---@return boolean
+function MapGrid:is_floor(x, y) end
+
+--- Edits outside the inner area are ignored, the outer ring belongs to the map borders.
+---@param x integer
+---@param y integer
+function MapGrid:carve(x, y) end
+
+---@param x integer
+---@param y integer
+function MapGrid:fill(x, y) end
+
+---@param x integer
+---@param y integer
+---@param w integer
+---@param h integer
+function MapGrid:carve_rect(x, y, w, h) end
+
+---@param x integer
+---@param y integer
+---@param w integer
+---@param h integer
+function MapGrid:fill_rect(x, y, w, h) end
+
+---@param x1 integer
+---@param y1 integer
+---@param x2 integer
+---@param y2 integer
+---@param radius? integer -- Defaults to the map's path radius, at most 2. Never carves the outer ring.
+function MapGrid:carve_path(x1, y1, x2, y2, radius) end
+
+---@param kind_id integer
+---@param x integer
+---@param y integer
+function MapGrid:place_monster(kind_id, x, y) end
+
+---@param x integer
+---@param y integer
+function MapGrid:place_orb(x, y) end
+
+--- Entry point of a layout script in assets/mapgen.
+---@param params MapGenParams
+---@param grid MapGrid
+function on_generate(params, grid) end
\ No newline at end of file
@@ -28,4 +28,20 @@ Monster = {}
 function Monster:get_position() end
 
 ---@return integer
-function Monster:get_id() end
\ No newline at end of file
+function Monster:get_id() end
+
+---@return "Hostile"|"Neutral"|"Allied"
+function Monster:get_faction() end
+
+---@param faction "Hostile"|"Neutral"|"Allied"
+function Monster:set_faction(faction) end
+
+---@return string
+function Monster:get_rarity() end
+
+---@return string[]
+function Monster:get_affixes() end
+
+---Per-instance table that scripts can use to keep state between calls.
+---@return table
+function Monster:get_state() end
\ No newline at end of file
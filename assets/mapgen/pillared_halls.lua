@@ -0,0 +1,69 @@
+-- SPDX-License-Identifier: MIT
+--
+-- Copyright (c) 2025 Alexandre Severino
+--
+-- Permission is hereby granted, free of charge, to any person obtaining a copy
+-- of this software and associated documentation files (the "Software"), to deal
+-- in the Software without restriction, including without limitation the rights
+-- to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+-- copies of the Software, and to permit persons to whom the Software is
+-- furnished to do so, subject to the following conditions:
+--
+-- The above copyright notice and this permission notice shall be included in
+-- all copies or substantial portions of the Software.
+--
+-- THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+-- IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+-- FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+-- AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+-- LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+-- OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+-- SOFTWARE.
+
+-- A chain of pillared halls linked by corridors, with an orb in the largest one.
+
+local MIN_HALL = 5
+local MAX_HALL = 9
+
+---@param params MapGenParams
+---@param grid MapGrid
+function on_generate(params, grid)
+    local halls = {}
+    local x = 2
+
+    while x + MIN_HALL < params.width - 1 do
+        local w = math.random(MIN_HALL, MAX_HALL)
+        local h = math.random(MIN_HALL, MAX_HALL)
+        if x + w >= params.width - 1 then
+            break
+        end
+        local y = math.random(2, params.height - h - 2)
+
+        grid:carve_rect(x, y, w, h)
+        -- Pillars every other tile, away from the hall walls
+        for px = x + 1, x + w - 2, 2 do
+            for py = y + 1, y + h - 2, 2 do
+                grid:fill(px, py)
+            end
+        end
+
+        halls[#halls + 1] = { x = x, y = y, w = w, h = h }
+        x = x + w + math.random(1, 3)
+    end
+
+    local largest = nil
+    for i, hall in ipairs(halls) do
+        if i > 1 then
+            local prev = halls[i - 1]
+            grid:carve_path(prev.x + prev.w - 1, prev.y + math.floor(prev.h / 2),
+                hall.x, hall.y + math.floor(hall.h / 2), 0)
+        end
+        if largest == nil or hall.w * hall.h > largest.w * largest.h then
+            largest = hall
+        end
+    end
+
+    if largest ~= nil then
+        grid:place_orb(largest.x, largest.y)
+    end
+end
\ No newline at end of file
@@ -0,0 +1,52 @@
+-- SPDX-License-Identifier: MIT
+--
+-- Copyright (c) 2025 Alexandre Severino
+--
+-- Permission is hereby granted, free of charge, to any person obtaining a copy
+-- of this software and associated documentation files (the "Software"), to deal
+-- in the Software without restriction, including without limitation the rights
+-- to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+-- copies of the Software, and to permit persons to whom the Software is
+-- furnished to do so, subject to the following conditions:
+--
+-- The above copyright notice and this permission notice shall be included in
+-- all copies or substantial portions of the Software.
+--
+-- THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+-- IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+-- FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+-- AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+-- LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+-- OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+-- SOFTWARE.
+
+local MINION_KIND = 6 -- Demon
+local LAST_STAND_KIND = 5 -- Slime
+
+---@param monster Monster
+---@param phase integer
+---@return boolean
+function on_phase_change(monster, phase)
+    local map = get_current_map()
+    local pos = monster:get_position()
+    local state = monster:get_state()
+
+    -- A demon for every phase reached so far, not counting the ones already summoned
+    local summoned = state.summoned or 0
+    for i = summoned + 1, phase - 1 do
+        local spawn_pos = map:get_random_adjacent_position(pos, true)
+        map:add_monster(MINION_KIND, spawn_pos)
+    end
+    state.summoned = math.max(summoned, phase - 1)
+
+    -- Cornered, the lord splits its blood into slimes all around it
+    if phase >= 4 and not state.last_stand then
+        state.last_stand = true
+        for i = 1, 4 do
+            local spawn_pos = map:get_random_adjacent_position(pos, true)
+            map:add_monster(LAST_STAND_KIND, spawn_pos)
+        end
+    end
+
+    return true
+end
\ No newline at end of file
@@ -20,20 +20,18 @@
 -- OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 -- SOFTWARE.
 
----@type integer
-local turn_count = 1
----@type integer?
-local spawner_type = nil
-
 ---@param monster Monster
 ---@return boolean
 function on_spawn(monster)
     if not GlobalData.SPAWNERS then
         return false
     end
-    
-    spawner_type = GlobalData.SPAWNERS[monster:get_id()]
-    if not spawner_type then
+
+    -- Every spawner keeps its own counter and type in its state table
+    local state = monster:get_state()
+    state.turn_count = 1
+    state.spawner_type = GlobalData.SPAWNERS[monster:get_id()]
+    if not state.spawner_type then
         return false
     end
 
@@ -44,18 +42,20 @@ end
 ---@param update_iteration integer
 ---@return boolean
 function on_update(monster, update_iteration)
-    if not spawner_type then return false end
+    local state = monster:get_state()
+    if not state.spawner_type then return false end
     
-    if turn_count % 3 == 0 then
+    if state.turn_count % 3 == 0 then
         local pos = monster:get_position()
         local map = get_current_map()
 
-        local monster_kind = get_monster_kind_by_id(spawner_type)
+        local monster_kind = get_monster_kind_by_id(state.spawner_type)
         pos = map:get_random_adjacent_position(pos, monster_kind:can_fly())
 
-        map:add_monster(spawner_type, pos)
+        -- Returns nil once this spawner or the map is at its population cap
+        map:add_monster(state.spawner_type, pos, monster)
     end
 
-    turn_count = turn_count + 1
+    state.turn_count = state.turn_count + 1
     return true
 end
\ No newline at end of file
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+use serde_json::from_str;
+use std::sync::Arc;
+
+use crate::maps::ARENA_MARGIN;
+use crate::maps::map_generator::MIN_MAP_SIZE;
+use crate::position::Position;
+
+/// Smallest arena side, leaving some open floor inside the margins.
+const MIN_ARENA_SIZE: usize = 2 * ARENA_MARGIN + 3;
+
+pub async fn load_bosses() -> BossKindsArc {
+    let file: String = load_string("assets/monsters/bosses.json").await.unwrap();
+    let list: Vec<BossKind> = from_str(&file).unwrap();
+    let min_size = MIN_ARENA_SIZE.max(MIN_MAP_SIZE);
+    for boss in &list {
+        if let Some([width, height]) = boss.map_size
+            && width.min(height) < min_size
+        {
+            panic!(
+                "{} has a {}x{} arena, it must be at least {} wide and high",
+                boss.name, width, height, min_size
+            );
+        }
+    }
+    Arc::new(list.into_iter().map(Arc::new).collect())
+}
+
+pub type BossKindsArc = Arc<Vec<Arc<BossKind>>>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossKind {
+    pub id: u32,
+    pub name: String,
+    pub monster_kind: u32,
+    pub floor: usize,
+    pub slot: [usize; 2],
+    // Width and height of the arena, the regular map size when missing.
+    #[serde(default)]
+    pub map_size: Option<[usize; 2]>,
+    // HP percentages at which the boss enters its next phase, highest first.
+    #[serde(default)]
+    pub phases: Vec<u32>,
+    pub reward_tier: u32,
+    pub reward_count: usize,
+}
+
+impl BossKind {
+    pub fn is_at(&self, floor: usize, x: usize, y: usize) -> bool {
+        self.floor == floor && self.slot == [x, y]
+    }
+}
+
+pub fn boss_for_floor(bosses: &[Arc<BossKind>], floor: usize) -> Option<&Arc<BossKind>> {
+    bosses.iter().find(|boss| boss.floor == floor)
+}
+
+/// Runtime state of the boss fight that belongs to an arena map.
+#[derive(Clone, Debug)]
+pub struct BossEncounter {
+    pub monster_id: u32,
+    pub name: String,
+    pub phases: Vec<u32>,
+    pub phase: usize,
+    pub stairs_pos: Position,
+    pub reward: Vec<u32>,
+    pub defeated: bool,
+}
+
+impl BossEncounter {
+    /// Returns the new phase index if the boss health crossed the next threshold.
+    pub fn check_phase(&mut self, hp: u32, max_hp: u32) -> Option<usize> {
+        if max_hp == 0 {
+            return None;
+        }
+
+        let percentage = hp * 100 / max_hp;
+        let mut new_phase = self.phase;
+        while new_phase < self.phases.len() && percentage <= self.phases[new_phase] {
+            new_phase += 1;
+        }
+
+        if new_phase != self.phase {
+            self.phase = new_phase;
+            Some(new_phase)
+        } else {
+            None
+        }
+    }
+}
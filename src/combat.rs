@@ -26,16 +26,21 @@ use rand::{Rng, thread_rng};
 
 use crate::{
     creature::Creature,
+    items::{base_item::ItemKind, container::Container},
     lua_interface::{LuaInterfaceRc, LuaScripted},
     maps::map::{Map, MapRc},
     monster::Monster,
+    monster_affix::MonsterAffix,
+    monster_kind::Faction,
     player::{Player, PlayerRc},
     position::Position,
-    spell_type::{SpellStrategy, SpellType},
+    spell_type::{SpellEffect, SpellStrategy, SpellType},
     tile::{NO_CREATURE, PLAYER_CREATURE_ID},
 };
 
-fn do_damage(
+const EXPLOSION_DAMAGE: i32 = 5;
+
+pub(crate) fn do_damage(
     player: &mut PlayerRc,
     map_ref: &MapRc,
     target_id: u32,
@@ -43,9 +48,23 @@ fn do_damage(
     lua_interface: &LuaInterfaceRc,
 ) {
     let mut map = map_ref.0.borrow_mut();
+    let damage = match map.monsters.get(&target_id) {
+        Some(monster) => {
+            let mut monster = monster.borrow_mut();
+            // Neutral creatures fight back once provoked
+            if monster.faction == Faction::Neutral {
+                monster.faction = Faction::Hostile;
+            }
+            monster.mitigate_damage(damage)
+        }
+        None => damage,
+    };
+
+    let mut _maybe_player_guard: Option<RefMut<Player>> = None;
     let mut _maybe_monster_guard: Option<RefMut<Monster>> = None;
     let target: &mut dyn Creature = if target_id == PLAYER_CREATURE_ID as u32 {
-        &mut *player.borrow_mut() as &mut dyn Creature
+        _maybe_player_guard = Some(player.borrow_mut());
+        &mut **_maybe_player_guard.as_mut().unwrap() as &mut dyn Creature
     } else {
         _maybe_monster_guard = Some(
             map.monsters
@@ -71,15 +90,23 @@ fn do_damage(
             println!("{} has been defeated!", target_name);
         } else {
             println!("{} has {} HP left.", target.name(), target.get_health().0);
+            let (hp, max_hp) = target.get_health();
+            drop(_maybe_player_guard);
+            drop(_maybe_monster_guard);
+            drop(map);
+            check_boss_phase(map_ref, target_id, hp, max_hp, lua_interface);
             return;
         }
     }
+    drop(_maybe_player_guard);
     drop(_maybe_monster_guard);
     drop(map);
 
     {
         let mut map = map_ref.0.borrow_mut();
-        map.generated_map.tiles[dead_at_pos.unwrap()].creature = NO_CREATURE;
+        map.generated_map
+            .tiles
+            .set_creature(dead_at_pos.unwrap(), NO_CREATURE);
     }
 
     // Now safe to lock again
@@ -99,15 +126,110 @@ fn do_damage(
             // update the monster in the map from Lua code
             *map.monsters
                 .get_mut(&target_id)
-                .expect("Target creature not found") = monster;
+                .expect("Target creature not found") = monster.clone();
             if let Err(e) = r {
                 eprintln!("Error calling Lua on_death: {}", e);
             }
         }
 
+        let (explosive, loot) = {
+            let monster = monster.borrow();
+            (
+                monster.has_affix(MonsterAffix::Explosive),
+                monster.loot.clone(),
+            )
+        };
+
+        let dead_pos = dead_at_pos.unwrap();
+        if !loot.is_empty() {
+            let mut container = Container::new();
+            for item_id in loot {
+                container.add_item(item_id);
+            }
+
+            let mut map = map_ref.0.borrow_mut();
+            map.generated_map
+                .tiles
+                .add_item(dead_pos, ItemKind::Container(container));
+        }
+
         {
             let mut map = map_ref.0.borrow_mut();
             map.monsters.remove(&target_id);
+
+            let is_boss = map
+                .generated_map
+                .boss
+                .as_ref()
+                .is_some_and(|boss| boss.monster_id == target_id);
+            if is_boss {
+                map.on_boss_defeated(dead_pos);
+            }
+        }
+
+        if explosive {
+            println!("The monster explodes!");
+            let in_blast =
+                |pos: Position| pos.x.abs_diff(dead_pos.x) <= 1 && pos.y.abs_diff(dead_pos.y) <= 1;
+            let mut victims: Vec<u32> = map_ref
+                .0
+                .borrow()
+                .monsters
+                .iter()
+                .filter(|(_, monster)| in_blast(monster.borrow().pos()))
+                .map(|(id, _)| *id)
+                .collect();
+            if in_blast(player.borrow().pos()) {
+                victims.push(PLAYER_CREATURE_ID);
+            }
+            for id in victims {
+                // Earlier victims can go off in turn and take others down first
+                let alive = if id == PLAYER_CREATURE_ID {
+                    player.borrow().hp > 0
+                } else {
+                    map_ref.0.borrow().monsters.contains_key(&id)
+                };
+                if alive {
+                    do_damage(player, map_ref, id, EXPLOSION_DAMAGE, lua_interface);
+                }
+            }
+        }
+    }
+}
+
+fn check_boss_phase(
+    map_ref: &MapRc,
+    target_id: u32,
+    hp: u32,
+    max_hp: u32,
+    lua_interface: &LuaInterfaceRc,
+) {
+    let phase = {
+        let mut map = map_ref.0.borrow_mut();
+        match map.generated_map.boss.as_mut() {
+            Some(boss) if boss.monster_id == target_id => boss.check_phase(hp, max_hp),
+            _ => None,
+        }
+    };
+
+    let Some(phase) = phase else {
+        return;
+    };
+
+    let monster = { map_ref.0.borrow().monsters.get(&target_id).cloned() };
+    if let Some(mut monster) = monster {
+        // Phases are 1-based on the Lua side, the fight starts in phase 1
+        let phase = phase as u32 + 1;
+        println!("{} enters phase {}!", monster.borrow().name(), phase);
+
+        let is_scripted = { monster.borrow().kind.is_scripted() };
+        if is_scripted {
+            let r = lua_interface
+                .borrow_mut()
+                .on_phase_change(&mut monster, phase);
+            if let Err(e) = r {
+                eprintln!("Error calling Lua on_phase_change: {}", e);
+            }
         }
     }
 }
@@ -128,7 +250,7 @@ pub(crate) fn do_melee_combat(
 
             let (target_id, mut monster) = {
                 let map = map_ref.0.borrow_mut();
-                let target_id = map.generated_map.tiles[target_pos].creature;
+                let target_id = map.generated_map.tiles.creature(target_pos);
                 (
                     target_id,
                     map.monsters
@@ -176,7 +298,7 @@ pub(crate) fn do_melee_combat(
         }
     };
 
-    let creature_id = map_ref.0.borrow().generated_map.tiles[target_pos].creature;
+    let creature_id = map_ref.0.borrow().generated_map.tiles.creature(target_pos);
     if creature_id > 0 {
         do_damage(
             player,
@@ -195,6 +317,7 @@ pub(crate) fn do_spell_combat(
     target_pos: Position,
     spell_type: &SpellType,
     lua_interface: &LuaInterfaceRc,
+    friendly_fire: bool,
 ) -> Vec<Position> {
     let map = map_ref.0.borrow_mut();
     if spell_type.strategy == SpellStrategy::Aim && map.is_tile_blocking_by_object(target_pos) {
@@ -207,13 +330,13 @@ pub(crate) fn do_spell_combat(
     let mut target_positions: Vec<Position> = Vec::new();
     let mut target_creatures: Vec<u32> = Vec::new();
 
-    map.spell_or_attack_fov_cache.area.iter().for_each(|&pos| {
+    map.spell_or_attack_fov_cache.area.iter().for_each(|pos| {
         if pos == attacker_pos && spell_type.strategy == SpellStrategy::Fixed {
             return;
         }
         target_positions.push(pos);
-        let creature_id = map.generated_map.tiles[pos].creature;
-        if creature_id > 0 {
+        let creature_id = map.generated_map.tiles.creature(pos);
+        if creature_id > 0 && (friendly_fire || !map.is_tile_ally_occupied(pos)) {
             target_creatures.push(creature_id as u32);
         }
     });
@@ -223,5 +346,15 @@ pub(crate) fn do_spell_combat(
         do_damage(player, &map_ref, target_creature, damage, lua_interface);
     }
 
+    if spell_type.has_effect(SpellEffect::Explosion) {
+        let mut map = map_ref.0.borrow_mut();
+        let destroyed = map.blast_terrain(&target_positions);
+        if destroyed > 0 {
+            println!("The blast tears down {} tile(s)!", destroyed);
+            let radius = map.max_fov_radius();
+            map.compute_player_fov(&mut player.borrow_mut(), radius);
+        }
+    }
+
     target_positions
 }
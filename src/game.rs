@@ -20,6 +20,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::boss;
 use crate::creature::Creature;
 use crate::graphics::graphics_manager::GraphicsManager;
 use crate::input::{Input, KeyboardAction};
@@ -27,29 +28,35 @@ use crate::items::base_item::ItemKind;
 use crate::items::collection::{Items, ItemsArc};
 use crate::lua_interface::{self, LuaInterface, LuaInterfaceRc, LuaScripted};
 use crate::maps::map::MapRc;
+use crate::maps::map_generator::MapStatus;
 use crate::maps::navigator::Navigator;
-use crate::maps::overworld::{Overworld, OverworldPos, VisitedState};
+use crate::maps::overworld::{self, Overworld, OverworldPos, VisitedState};
 use crate::maps::overworld_generator::OverworldGenerator;
-use crate::maps::{GRID_HEIGHT, GRID_WIDTH};
 use crate::maps::{TILE_SIZE, map::Map};
 use crate::monster::{Monster, MonsterRc};
-use crate::monster_kind::MonsterKind;
+use crate::monster_affix::MonsterAffix;
+use crate::monster_kind::{Faction, MonsterKind, MonsterKindsDataArc};
 use crate::player::{self, Player, PlayerRc};
 use crate::player_spell::PlayerSpell;
 use crate::position::{Direction, Position};
-use crate::spell_type::{SpellKind, get_spell_types};
-use crate::tile::{NO_CREATURE, PLAYER_CREATURE_ID, TileKind};
+use crate::scheduler::{ACTION_COST_NORMAL, Actor, BASE_SPEED, Scheduler, SchedulerRc};
+use crate::spell_type::{SpellKind, SpellType, get_spell_types};
+use crate::status_effect::{StatusEffect, StatusEffects, StatusKind};
+use crate::tile::{self, DoorState, NO_CREATURE, PLAYER_CREATURE_ID};
+use crate::tile_def::OnStepEffect;
+use crate::trap::{TRAP_PATH_PENALTY, TrapEffect};
 use crate::ui::manager::{Ui, UiEvent};
 use crate::ui::point_f::PointF;
 use crate::ui::size_f::SizeF;
+use crate::ui::widget_minimap::{FloorRoom, FloorView};
+use ::rand::thread_rng;
 use macroquad::prelude::*;
 use mlua::Table;
 
-use crate::{combat, monster_kind, spell_type};
+use crate::{combat, monster_kind, spell_type, tile_def, trap};
 use macroquad::time::get_time;
 
-use std::cell::{RefCell, RefMut};
-use std::cmp::max;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
@@ -72,6 +79,23 @@ pub enum PlayerEvent {
     Death,
     ReachBorder,
     ClimbDown,
+    ClimbUp,
+    Fall,
+}
+
+/// How far an ally looks for hostiles to engage.
+const ALLY_AGGRO_RANGE: usize = 8;
+/// Allies stop following once they are this close to the player.
+const ALLY_FOLLOW_DISTANCE: usize = 2;
+/// How far around the player a search can uncover hidden traps.
+const SEARCH_RADIUS: usize = 2;
+/// Digging through a tile takes this many swings of the digging tool.
+const DIG_SWINGS: u32 = 2;
+
+enum MonsterGoal {
+    Attack(u32, Position),
+    Follow(Position),
+    Idle,
 }
 
 pub struct GameState {
@@ -84,12 +108,19 @@ pub struct GameState {
     pub last_player_event: PlayerEvent,
     pub animate_for: f32,
     pub animating_effects: HashMap<Position, Arc<RwLock<Texture2D>>>,
+    pub monster_kinds: MonsterKindsDataArc,
+    pub friendly_fire: bool,
+    pub scheduler: SchedulerRc,
+    /// Time cost of the player's last action, consumed by `update_turn`.
+    pub player_action_cost: u32,
+    pub status_effects: StatusEffects,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 enum MapTravelKind {
     BorderCross,
     ClimbDown,
+    ClimbUp,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -137,6 +168,17 @@ fn draw(
     ui.set_player_hp(hp, max_hp);
     ui.set_player_mp(mp, max_mp);
 
+    let boss_health = map.generated_map.boss.as_ref().and_then(|boss| {
+        map.monsters
+            .get(&boss.monster_id)
+            .map(|monster| (boss.name.clone(), monster.borrow().get_health()))
+    });
+    ui.set_boss_health(
+        boss_health
+            .as_ref()
+            .map(|(name, (hp, max_hp))| (name.as_str(), *hp, *max_hp)),
+    );
+
     ui.set_player_sp(player.sp);
     ui.set_player_str(player.strength);
     ui.set_player_dex(player.dexterity);
@@ -190,54 +232,112 @@ fn draw(
     ui.draw();
 }
 
-fn get_map_ptr(game: &mut GameState, overworld_pos: OverworldPos) -> MapRc {
-    let current_map_rc = game.overworld.get_map_ptr(overworld_pos);
+/// The map at `overworld_pos`, or `None` while it is still being generated.
+fn try_get_map_ptr(game: &mut GameState, overworld_pos: OverworldPos) -> Option<MapRc> {
+    if let Some(map_rc) = game.overworld.get_map_ptr(overworld_pos) {
+        return Some(map_rc);
+    }
 
-    if current_map_rc.is_none() {
-        let generated_map_arc = if let Some(generated_map_arc) = game
-            .overworld_generator
-            .lock()
-            .unwrap()
-            .get_generated_map_ptr(overworld_pos)
-        {
-            generated_map_arc
-        } else {
-            panic!("Failed to get map pointer from overworld");
-        };
+    let overworld_generator = game.overworld_generator.lock().unwrap();
+    if let Some(generated_map) = overworld_generator.take_map(overworld_pos) {
+        drop(overworld_generator);
+        return Some(game.overworld.add_map(overworld_pos, generated_map));
+    }
+    if overworld_generator.map_status(overworld_pos) == MapStatus::Requested {
+        // Someone is waiting on it now, it goes before anything else
+        overworld_generator.prioritize(overworld_pos);
+    }
+    None
+}
 
-        game.overworld
-            .add_map(overworld_pos, generated_map_arc.clone())
-    } else {
-        current_map_rc.unwrap()
+/// The player can go down during their own action too, caught in the blast of a
+/// monster they just killed.
+fn check_player_death(game: &mut GameState) {
+    if game.player.borrow().hp == 0 {
+        game.last_player_event = PlayerEvent::Death;
     }
 }
 
+fn draw_generating_overlay() {
+    let (width, height) = (screen_width(), screen_height());
+    draw_rectangle(0.0, 0.0, width, height, Color::new(0.0, 0.0, 0.0, 0.6));
+    let text = "Generating...";
+    let size = measure_text(text, None, 40, 1.0);
+    draw_text(text, (width - size.width) / 2.0, height / 2.0, 40.0, WHITE);
+}
+
 fn get_new_opos(
+    overworld: &Overworld,
+    map_size: (usize, usize),
     player_pos: &Position,
     player_opos: &OverworldPos,
     map_update: &MapTravelEvent,
 ) -> OverworldPos {
+    let (width, height) = map_size;
     let mut new_opos = *player_opos;
     if *map_update == MapTravelEvent::Peek(MapTravelKind::BorderCross)
         || *map_update == MapTravelEvent::Visit(MapTravelKind::BorderCross)
     {
         if player_pos.x == 0 {
             new_opos.x -= 1;
-        } else if player_pos.x == GRID_WIDTH - 1 {
+        } else if player_pos.x == width - 1 {
             new_opos.x += 1;
         }
         if player_pos.y == 0 {
             new_opos.y -= 1;
-        } else if player_pos.y == GRID_HEIGHT - 1 {
+        } else if player_pos.y == height - 1 {
             new_opos.y += 1;
         }
+    } else if *map_update == MapTravelEvent::Visit(MapTravelKind::ClimbUp) {
+        if let Some((opos, _)) = overworld.stairs_up.get(&player_opos.floor) {
+            new_opos = *opos;
+        }
     } else {
-        new_opos = OverworldPos::new(player_opos.floor + 1, 2, 2); // Climbing down
+        new_opos = overworld.layout.center(player_opos.floor + 1); // Climbing down
     }
 
     new_opos
 }
 
+/// Where the player lands after crossing the border of a `from_size` map into `map`.
+/// Neighboring maps can differ in size, so the player is lined up with the closest
+/// opening on the side they come through.
+fn border_entry_position(player_pos: Position, from_size: (usize, usize), map: &Map) -> Position {
+    let (from_width, from_height) = from_size;
+    let (width, height) = (map.generated_map.width(), map.generated_map.height());
+
+    // border_positions order: top, right, bottom, left
+    let side = if player_pos.x == 0 {
+        1
+    } else if player_pos.x == from_width - 1 {
+        3
+    } else if player_pos.y == 0 {
+        2
+    } else if player_pos.y == from_height - 1 {
+        0
+    } else {
+        return player_pos;
+    };
+
+    let mut pos = map.generated_map.border_positions[side]
+        .iter()
+        .min_by_key(|border| border.x.abs_diff(player_pos.x) + border.y.abs_diff(player_pos.y))
+        .copied()
+        .unwrap_or(Position::new(
+            player_pos.x.min(width - 1),
+            player_pos.y.min(height - 1),
+        ));
+
+    match side {
+        0 => pos.y = 1,
+        1 => pos.x = width - 2,
+        2 => pos.y = height - 2,
+        _ => pos.x = 1,
+    }
+    pos
+}
+
+/// Returns `true` while the player is held up travelling to a map still being generated.
 fn check_for_map_update(
     game: &mut GameState,
     map_update: &mut MapTravelEvent,
@@ -246,13 +346,58 @@ fn check_for_map_update(
     current_map_rc: &mut MapRc,
     current_downstair_teleport_pos: &mut Option<Position>,
     overworld_pos: &mut OverworldPos,
-) {
+) -> bool {
     if *map_update != MapTravelEvent::None {
         // Determine player's current border position
         let mut player_pos = { game.player.borrow().position };
-        let new_opos = get_new_opos(&player_pos, overworld_pos, &map_update);
+        let map_size = {
+            let map = current_map_rc.0.borrow();
+            (map.generated_map.width(), map.generated_map.height())
+        };
+        let new_opos = get_new_opos(
+            &game.overworld,
+            map_size,
+            &player_pos,
+            overworld_pos,
+            &map_update,
+        );
 
-        let new_map_rc = get_map_ptr(game, new_opos);
+        let Some(new_map_rc) = try_get_map_ptr(game, new_opos) else {
+            let status = game
+                .overworld_generator
+                .lock()
+                .unwrap()
+                .map_status(new_opos);
+            if status == MapStatus::NotRequested {
+                // It was never asked for, or got dropped along the way, so ask again
+                let tier = current_map_rc.0.borrow().generated_map.tier;
+                game.overworld_generator
+                    .lock()
+                    .unwrap()
+                    .setup_adjacent_maps(
+                        tier + 1,
+                        overworld_pos.floor,
+                        overworld_pos.x,
+                        overworld_pos.y,
+                        *current_downstair_teleport_pos,
+                        Some(player_pos),
+                    );
+                let overworld_generator = game.overworld_generator.lock().unwrap();
+                if overworld_generator.map_status(new_opos) == MapStatus::NotRequested {
+                    println!("No map can be generated at {:?}, staying put.", new_opos);
+                    *map_update = MapTravelEvent::None;
+                    return false;
+                }
+                overworld_generator.prioritize(new_opos);
+            }
+            if let MapTravelEvent::Peek(_) = map_update {
+                // Peeking is only a look ahead, the player keeps playing meanwhile
+                println!("Map at {:?} is still being generated.", new_opos);
+                *map_update = MapTravelEvent::None;
+                return false;
+            }
+            return true;
+        };
 
         if let MapTravelEvent::Peek(_) = map_update {
             let mut map = new_map_rc.0.borrow_mut();
@@ -275,7 +420,7 @@ fn check_for_map_update(
                 *map_update = MapTravelEvent::None; // Reset map update to None
 
                 if map.generated_map.visited_state == VisitedState::Unvisited {
-                    update_map_visited_state(game, &mut map, new_opos, VisitedState::Peeked);
+                    map.generated_map.visited_state = VisitedState::Peeked;
                     *current_downstair_teleport_pos = {
                         let current_map = current_map_rc.0.borrow();
                         current_map.generated_map.downstair_teleport.clone()
@@ -291,51 +436,95 @@ fn check_for_map_update(
                     }
                 }
 
-                //print_overworld(game);
-                return;
+                //print_overworld(game, overworld_pos.floor);
+                return false;
             }
         }
 
         if let MapTravelEvent::Visit(_) = map_update {
             let mut current_tier = 0u32;
             {
-                let new_map_rc = get_map_ptr(game, new_opos);
+                let allies;
+
+                // Falling through a pit skips the peek, so the map gets its first look here
+                let unvisited =
+                    new_map_rc.0.borrow().generated_map.visited_state == VisitedState::Unvisited;
+                if unvisited {
+                    *current_downstair_teleport_pos =
+                        current_map_rc.0.borrow().generated_map.downstair_teleport;
+                    new_map_rc.0.borrow_mut().generated_map.visited_state = VisitedState::Peeked;
+                    if let Err(e) = game.lua_interface.borrow_mut().on_map_peeked(&new_map_rc) {
+                        eprintln!("Error calling Lua on_map_peeked: {}", e);
+                    }
+                }
 
                 {
                     let mut map = current_map_rc.0.borrow_mut();
                     current_tier = map.generated_map.tier;
+                    allies = map.take_allies();
                     let mut player_ref = game.player.borrow_mut();
                     map.remove_creature(&mut *player_ref);
-                    map.remove_downstairs_teleport();
+                    // Stairs stay around once they have been used, and arena stairs
+                    // always do, so the floor below remains reachable
+                    let below = game.overworld.layout.center(overworld_pos.floor + 1);
+                    if map.generated_map.boss.is_none()
+                        && *map_update == MapTravelEvent::Visit(MapTravelKind::BorderCross)
+                        && !game.overworld.is_visited(below)
+                    {
+                        map.remove_downstairs_teleport();
+                    }
                 }
 
                 *current_map_rc = new_map_rc;
 
                 let mut map = current_map_rc.0.borrow_mut();
 
+                // Coming back to a map, its own stairs are the ones that lead down
+                if !unvisited && map.generated_map.downstair_teleport.is_some() {
+                    *current_downstair_teleport_pos = map.generated_map.downstair_teleport;
+                }
+
+                if let Some(boss) = &map.generated_map.boss {
+                    *current_downstair_teleport_pos = Some(boss.stairs_pos);
+                }
+
                 if *map_update == MapTravelEvent::Visit(MapTravelKind::BorderCross) {
-                    if player_pos.x == 0 {
-                        player_pos.x = GRID_WIDTH - 2;
-                    } else if player_pos.x == GRID_WIDTH - 1 {
-                        player_pos.x = 1;
-                    }
-                    if player_pos.y == 0 {
-                        player_pos.y = GRID_HEIGHT - 2;
-                    } else if player_pos.y == GRID_HEIGHT - 1 {
-                        player_pos.y = 1;
-                    }
+                    player_pos = border_entry_position(player_pos, map_size, &map);
+                } else if *map_update == MapTravelEvent::Visit(MapTravelKind::ClimbUp)
+                    && let Some((_, stairs_pos)) =
+                        game.overworld.stairs_up.get(&overworld_pos.floor)
+                {
+                    player_pos = *stairs_pos;
                 }
 
-                update_map_visited_state(game, &mut map, new_opos, VisitedState::Visited);
+                map.generated_map.visited_state = VisitedState::Visited;
+
+                // The spot under the stairs or pit is not always open on the new map
+                let landing_pos = map.find_free_tile_near(player_pos).unwrap_or(player_pos);
+                map.add_player(&mut game.player.borrow_mut(), landing_pos);
 
-                map.add_player(&mut game.player.borrow_mut(), player_pos);
+                if *map_update == MapTravelEvent::Visit(MapTravelKind::ClimbDown) {
+                    // The upstairs lead back to wherever the player last came down from
+                    game.overworld
+                        .stairs_up
+                        .insert(new_opos.floor, (*overworld_pos, player_pos));
+                    map.add_upstairs_teleport(landing_pos);
+                }
+                let player_pos = game.player.borrow().position;
+                for ally in allies {
+                    let name = ally.borrow().name().to_string();
+                    if !map.place_monster_near(ally, player_pos) {
+                        println!("{} could not follow the player.", name);
+                    }
+                }
                 peek_map_rc.take();
                 println!("Player moved to new map at position: {:?}", new_opos);
             }
 
             {
                 let mut overworld_generator = game.overworld_generator.lock().unwrap();
-                game.overworld.clear_unvisited(overworld_pos.clone());
+                let cleared = game.overworld.clear_unvisited(overworld_pos.clone());
+                overworld_generator.forget(&cleared);
                 overworld_generator.clear_unvisited(overworld_pos.clone());
                 overworld_generator.setup_adjacent_maps(
                     current_tier + 1,
@@ -343,6 +532,7 @@ fn check_for_map_update(
                     overworld_pos.x,
                     overworld_pos.y,
                     None,
+                    None,
                 );
                 overworld_generator.setup_adjacent_maps(
                     current_tier + 1,
@@ -350,6 +540,7 @@ fn check_for_map_update(
                     new_opos.x,
                     new_opos.y,
                     current_downstair_teleport_pos.clone(),
+                    Some(game.player.borrow().position),
                 );
             }
 
@@ -363,86 +554,88 @@ fn check_for_map_update(
         };
         *map_update = MapTravelEvent::None;
 
-        //print_overworld(game);
+        //print_overworld(game, overworld_pos.floor);
     }
+    false
 }
 
-pub fn update_map_visited_state(
-    game: &mut GameState,
-    map: &mut RefMut<'_, Map>,
-    overworld_pos: OverworldPos,
-    visited_state: VisitedState,
-) {
-    map.generated_map.visited_state = visited_state.clone();
-
-    if let Some(generated_map_arc) = game
-        .overworld_generator
-        .lock()
-        .unwrap()
-        .get_generated_map_ptr(overworld_pos)
-    {
-        let mut generated_map = generated_map_arc.lock().unwrap();
-        generated_map.visited_state = visited_state;
+/// What the player knows of the floor they are on, for the minimap and floor map.
+fn floor_view(game: &GameState, overworld_pos: OverworldPos) -> FloorView {
+    let (width, height) = game.overworld.layout.dimensions(overworld_pos.floor);
+    let rooms = game
+        .overworld
+        .maps
+        .borrow()
+        .iter()
+        .filter(|(pos, _)| pos.floor == overworld_pos.floor)
+        .map(|(pos, map_rc)| {
+            let map = map_rc.0.borrow();
+            let generated_map = &map.generated_map;
+            let known = generated_map.visited_state != VisitedState::Unvisited;
+            FloorRoom {
+                x: pos.x,
+                y: pos.y,
+                state: generated_map.visited_state.clone(),
+                exits: std::array::from_fn(|side| {
+                    known && !generated_map.border_positions[side].is_empty()
+                }),
+                downstairs: known && generated_map.downstair_teleport.is_some(),
+                upstairs: known && generated_map.upstair_teleport.is_some(),
+            }
+        })
+        .collect();
+
+    FloorView {
+        floor: overworld_pos.floor,
+        width,
+        height,
+        rooms,
+        player: Some((overworld_pos.x, overworld_pos.y)),
     }
 }
 
-pub fn print_overworld(game: &mut GameState) {
+pub fn print_overworld(game: &mut GameState, floor: usize) {
     //  Overworld          OverworldGenerator
     // [ 0, 0, 0, 0, 0] | [ 0, 0, 0, 0, 0]
-    // [ 0, 0, n, 0, 0] | [ 0, 0, n, 0, 0]
-    // [ 0, n, v, n, 0] | [ 0, n, v, n, 0]
-    // [ 0, 0, p, 0, 0] | [ 0, 0, p, 0, 0]
+    // [ 0, 0, n, 0, 0] | [ 0, 0, r, 0, 0]
+    // [ 0, n, v, n, 0] | [ 0, r, t, q, 0]
+    // [ 0, 0, p, 0, 0] | [ 0, 0, t, 0, 0]
     // [ 0, 0, 0, 0, 0] | [ 0, 0, 0, 0, 0]
     // n = unvisited, v = visited, p = peeked
+    // q = requested, r = ready, t = taken by the game
 
-    // Overworld: maps[y][x] (row-major)
-    let states = |overworld: &Overworld| {
-        let mut grid = vec![vec!['0'; 5]; 5];
-        for y in 0..5 {
-            for x in 0..5 {
-                let pos = OverworldPos { floor: 0, x, y };
-                if let Some(map_rc) = overworld.get_map_ptr(pos) {
-                    let map = map_rc.0.borrow();
-                    grid[y][x] = match map.generated_map.visited_state {
-                        VisitedState::Unvisited => 'n',
-                        VisitedState::Visited => 'v',
-                        VisitedState::Peeked => 'p',
-                    };
-                }
-            }
-        }
-        grid
+    let (width, height) = game.overworld.layout.dimensions(floor);
+    let state_char = |visited_state: &VisitedState| match visited_state {
+        VisitedState::Unvisited => 'n',
+        VisitedState::Visited => 'v',
+        VisitedState::Peeked => 'p',
     };
 
-    // OverworldGenerator: generated_maps[floor][x][y] (column-major, to match Overworld)
-    let states_generated = |generated_maps: &Vec<
-        [[Option<Arc<Mutex<crate::maps::generated_map::GeneratedMap>>>; 5]; 5],
-    >| {
-        let mut grid = vec![vec!['0'; 5]; 5];
-        if let Some(floor_maps) = generated_maps.get(0) {
-            for y in 0..5 {
-                for x in 0..5 {
-                    if let Some(gmap_arc) = &floor_maps[x][y] {
-                        let gmap = gmap_arc.lock().unwrap();
-                        grid[y][x] = match gmap.visited_state {
-                            VisitedState::Unvisited => 'n',
-                            VisitedState::Visited => 'v',
-                            VisitedState::Peeked => 'p',
-                        };
-                    }
-                }
-            }
+    // Both grids are printed row-major: grid[y][x]
+    let mut left = vec![vec!['0'; width]; height];
+    for (pos, map_rc) in game.overworld.maps.borrow().iter() {
+        if pos.floor == floor && pos.x < width && pos.y < height {
+            left[pos.y][pos.x] = state_char(&map_rc.0.borrow().generated_map.visited_state);
         }
-        grid
-    };
-
-    let left = states(&game.overworld);
+    }
 
-    let overworld_generator = game.overworld_generator.lock().unwrap();
-    let right = states_generated(&*overworld_generator.generated_maps.lock().unwrap());
+    let mut right = vec![vec!['0'; width]; height];
+    {
+        let overworld_generator = game.overworld_generator.lock().unwrap();
+        for (y, row) in right.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = match overworld_generator.map_status(OverworldPos::new(floor, x, y)) {
+                    MapStatus::NotRequested => '0',
+                    MapStatus::Requested => 'q',
+                    MapStatus::Ready(_) => 'r',
+                    MapStatus::Taken(_) => 't',
+                };
+            }
+        }
+    }
 
     println!("//  Overworld          OverworldGenerator");
-    for y in 0..5 {
+    for y in 0..height {
         let left_row: String = left[y].iter().map(|c| format!("{}, ", c)).collect();
         let right_row: String = right[y].iter().map(|c| format!("{}, ", c)).collect();
         println!(
@@ -452,6 +645,7 @@ pub fn print_overworld(game: &mut GameState) {
         );
     }
     println!("// n = unvisited, v = visited, p = peeked");
+    println!("// q = requested, r = ready, t = taken by the game");
 }
 
 pub async fn run() {
@@ -460,9 +654,18 @@ pub async fn run() {
     let spell_types = spell_type::load_spell_types().await;
     spell_type::set_global_spell_types(spell_types);
 
+    let tile_defs = tile_def::load_tile_defs().await;
+    tile_def::set_global_tile_defs(tile_defs);
+    tile::load_tile_sprites().await;
+
+    let trap_kinds = trap::load_trap_kinds(&lua_interface).await;
+    trap::set_global_trap_kinds(trap_kinds);
+
     let monster_kinds = monster_kind::load_monster_kinds(&lua_interface).await;
 
     let items = Arc::new(RwLock::new(Items::new()));
+    let bosses = boss::load_bosses().await;
+    let overworld_layout = overworld::load_overworld_layout().await;
 
     let mut game = GameState {
         player: Rc::new(RefCell::new(Player::new(Position::new(1, 1)).await)),
@@ -470,15 +673,22 @@ pub async fn run() {
             &lua_interface,
             monster_kinds.read().unwrap().vec.clone(),
             &items,
+            bosses,
+            Arc::clone(&overworld_layout),
         )
         .await,
-        overworld: Overworld::new(),
+        overworld: Overworld::new(Arc::clone(&overworld_layout)),
         items: items,
         lua_interface: lua_interface,
         last_player_event: PlayerEvent::None,
         turn: 1,
         animating_effects: HashMap::new(),
         animate_for: 0.0,
+        monster_kinds: monster_kinds.clone(),
+        friendly_fire: false,
+        scheduler: Rc::new(RefCell::new(Scheduler::new())),
+        player_action_cost: ACTION_COST_NORMAL,
+        status_effects: StatusEffects::new(),
     };
 
     game.items
@@ -487,14 +697,17 @@ pub async fn run() {
         .load_holdable_items(&game.lua_interface)
         .await;
 
-    let mut overworld_pos = OverworldPos {
-        floor: 0,
-        x: 2,
-        y: 2,
-    };
+    let mut overworld_pos = overworld_layout.center(0);
     let mut current_downstair_teleport_pos: Option<Position> = None;
 
-    let mut current_map_rc = get_map_ptr(&mut game, overworld_pos);
+    let mut current_map_rc = loop {
+        if let Some(map_rc) = try_get_map_ptr(&mut game, overworld_pos) {
+            break map_rc;
+        }
+        clear_background(BLACK);
+        draw_generating_overlay();
+        next_frame().await;
+    };
     let mut peek_map_rc: Option<MapRc> = None;
 
     let shared_map_ptr: Rc<RefCell<MapRc>> = Rc::new(RefCell::new(current_map_rc.clone()));
@@ -502,7 +715,7 @@ pub async fn run() {
     {
         let mut map = current_map_rc.0.borrow_mut();
         map.add_player_first_map(&mut game.player.borrow_mut());
-        update_map_visited_state(&mut game, &mut map, overworld_pos, VisitedState::Visited);
+        map.generated_map.visited_state = VisitedState::Visited;
     }
 
     let mut last_move_time = 0.0;
@@ -519,23 +732,18 @@ pub async fn run() {
     {
         let mut lua_interface = game.lua_interface.borrow_mut();
         let monster_kinds_clone = monster_kinds.clone();
+        let items_clone = game.items.clone();
         lua_interface.map_add_monster_callback = Some(Rc::new(
-            move |map_rc, kind_id, pos: Position| -> MonsterRc {
-                let binding = monster_kinds_clone.read().unwrap();
-                let binding = binding.vec.read().unwrap();
-                let kind = binding
-                    .iter()
-                    .find(|mt| mt.id == kind_id)
-                    .expect("Monster type not found");
-
-                // Create a new monster and wrap it in Rc
-                let monster = Rc::new(RefCell::new(Monster::new(pos.clone(), kind.clone())));
-
-                let mut map = map_rc.0.borrow_mut();
-                map.generated_map.tiles[pos].creature = monster.borrow().id; // Set the creature ID in the tile
-                // Wrap the monster in Rc and push to creatures
-                map.monsters.insert(monster.borrow().id, monster.clone());
-                monster
+            move |map_rc, kind_id, pos: Position, spawner_id| -> Option<MonsterRc> {
+                add_monster_to_map(
+                    &monster_kinds_clone,
+                    &items_clone,
+                    &map_rc,
+                    kind_id,
+                    pos,
+                    spawner_id,
+                    None,
+                )
             },
         ));
 
@@ -554,11 +762,13 @@ pub async fn run() {
             Some(Rc::new(move |monster: &Monster| -> Vec<Position> {
                 //let monster = monster_rc.borrow();
                 let player_pos = { player_clone.borrow().position };
+                // Scripts move monsters tile by tile and have no way to open doors
                 let path = find_monster_path(
                     &shared_map_ptr_clone.borrow(),
                     monster.position,
                     player_pos,
                     monster.kind.flying,
+                    false,
                 );
 
                 if path.is_none() {
@@ -604,6 +814,11 @@ pub async fn run() {
                     None
                 }
             }));
+        let scheduler_clone = game.scheduler.clone();
+        lua_interface.schedule_event_callback = Some(Rc::new(move |delay| -> u32 {
+            scheduler_clone.borrow_mut().schedule_event(delay as u64)
+        }));
+
         let shared_map_ptr_clone = shared_map_ptr.clone();
         lua_interface.get_current_map_callback = Some(Rc::new(move || -> MapRc {
             let binding = shared_map_ptr_clone.borrow();
@@ -729,7 +944,7 @@ pub async fn run() {
             }
         }
 
-        check_for_map_update(
+        let waiting_for_map = check_for_map_update(
             &mut game,
             &mut map_update,
             &mut last_map_travel_kind,
@@ -739,11 +954,30 @@ pub async fn run() {
             &mut overworld_pos,
         );
 
+        ui.set_floor_view(&floor_view(&game, overworld_pos));
+
         if !Rc::ptr_eq(&current_map_rc.0, &shared_map_ptr.borrow().0) {
             // Update the shared map pointer if it has changed
             *shared_map_ptr.borrow_mut() = current_map_rc.clone();
         }
 
+        if waiting_for_map {
+            clear_background(BLACK);
+            {
+                let mut map = current_map_rc.0.borrow_mut();
+                draw(
+                    &mut graphics_manager,
+                    &mut game,
+                    &mut ui,
+                    &mut map,
+                    game_interface_offset,
+                );
+            }
+            draw_generating_overlay();
+            next_frame().await;
+            continue;
+        }
+
         let now = get_time();
         if now - last_move_time < move_interval {
             {
@@ -776,9 +1010,14 @@ pub async fn run() {
                 map_update = MapTravelEvent::Visit(last_map_travel_kind.clone());
             } else if input.keyboard_action == KeyboardAction::Cancel {
                 peek_map_rc = None; // Reset peek map
+                // Peeking borrowed the player's sight, give it back to the map they are on
+                let mut map = current_map_rc.0.borrow_mut();
+                let radius = map.max_fov_radius();
+                map.compute_player_fov(&mut game.player.borrow_mut(), radius);
             } else {
                 let mut map = peek_map_rc.as_mut().unwrap().0.borrow_mut();
-                map.compute_player_fov(&mut game.player.borrow_mut(), max(GRID_WIDTH, GRID_HEIGHT));
+                let radius = map.max_fov_radius();
+                map.compute_player_fov(&mut game.player.borrow_mut(), radius);
                 draw(
                     &mut graphics_manager,
                     &mut game,
@@ -811,18 +1050,29 @@ pub async fn run() {
 
             if map.hovered_tile_changed {
                 if let Some(pos) = map.hovered_tile {
-                    if pos.x >= GRID_WIDTH || pos.y >= GRID_HEIGHT {
+                    if !map.generated_map.tiles.in_bounds(pos) {
                         ui.update_tile_info(None);
                         continue; // Skip if out of bounds
                     }
+                    if !map.explored.is_explored(pos)
+                        && !map.is_tile_in_view(&game.player.borrow(), pos)
+                    {
+                        ui.update_tile_info(None);
+                        continue; // Nothing to tell about tiles never seen
+                    }
                     let tile = &map.generated_map.tiles[pos];
-                    let tile_description = match tile.kind() {
-                        TileKind::Floor => "Floor",
-                        TileKind::Wall => "Wall",
-                        TileKind::Chasm => "Chasm",
-                        _ => "Unknown Tile",
+                    let name = &tile.def().name;
+                    let mut tile_description = match tile.door {
+                        Some(DoorState::Open) => format!("{} (open)", name),
+                        Some(DoorState::Closed) => format!("{} (closed)", name),
+                        Some(DoorState::Locked) => format!("{} (locked)", name),
+                        None => name.clone(),
                     };
-                    ui.update_tile_info(Some(tile_description.to_string()));
+                    if let Some(trap) = tile.trap.filter(|trap| !trap.hidden) {
+                        tile_description =
+                            format!("{}, {} trap", tile_description, trap.kind().name);
+                    }
+                    ui.update_tile_info(Some(tile_description));
                 } else {
                     ui.update_tile_info(None);
                 }
@@ -840,12 +1090,16 @@ pub async fn run() {
         };
 
         if ui.is_focused {
-            if input.keyboard_action == KeyboardAction::Cancel {
+            if input.keyboard_action == KeyboardAction::Cancel
+                || input.keyboard_action == KeyboardAction::ToggleFloorMap
+            {
                 ui.hide();
             }
         } else {
             if input.keyboard_action == KeyboardAction::OpenCharacterSheet {
                 ui.toggle_character_sheet();
+            } else if input.keyboard_action == KeyboardAction::ToggleFloorMap {
+                ui.toggle_floor_map();
             } else {
                 update(
                     &mut game,
@@ -879,6 +1133,21 @@ pub async fn run() {
                     map_update = MapTravelEvent::Peek(MapTravelKind::BorderCross);
                 } else if player_event == PlayerEvent::ClimbDown {
                     map_update = MapTravelEvent::Peek(MapTravelKind::ClimbDown);
+                } else if player_event == PlayerEvent::ClimbUp {
+                    // Floors above have all been seen already, no need to peek
+                    map_update = MapTravelEvent::Visit(MapTravelKind::ClimbUp);
+                } else if player_event == PlayerEvent::Fall {
+                    // There is no looking before leaping, the floor below is entered right away
+                    let below = overworld_layout.center(overworld_pos.floor + 1);
+                    // A floor still being generated is waited for like any other
+                    let below_ready = game.overworld.get_map_ptr(below).is_some()
+                        || game.overworld_generator.lock().unwrap().map_status(below)
+                            != MapStatus::NotRequested;
+                    if below_ready {
+                        map_update = MapTravelEvent::Visit(MapTravelKind::ClimbDown);
+                    } else {
+                        println!("The player catches the edge of the pit and climbs out.");
+                    }
                 }
             }
         }
@@ -916,6 +1185,7 @@ pub fn update(
     let player_pos = { game.player.borrow().position };
 
     let mut new_player_pos: Option<Position> = None;
+    let mut swapped_ally: Option<u32> = None;
     let mut should_update_turn = false;
 
     if let Some(player_goal) = player_goal_position {
@@ -937,11 +1207,7 @@ pub fn update(
                         && (player_pos.euclidean_distance_squared(&player_goal)
                             <= (range * range) as f64
                             || player_pos.is_neighbor(&player_goal))
-                        && game
-                            .player
-                            .borrow_mut()
-                            .line_of_sight
-                            .contains(&player_goal)
+                        && game.player.borrow_mut().line_of_sight.contains(player_goal)
                 };
 
                 if can_attack {
@@ -952,8 +1218,10 @@ pub fn update(
                         player_goal,
                         &game.lua_interface,
                     );
+                    game.player_action_cost = game.player.borrow().attack_cost();
                     should_update_turn = true;
                     game.last_player_event = PlayerEvent::AttackConfirm;
+                    check_player_death(game);
                 }
             } else {
                 let mut should_cast = false;
@@ -961,7 +1229,7 @@ pub fn update(
                     let mut player = game.player.borrow_mut();
 
                     let (in_line_of_sight, spell_range) = {
-                        let in_line_of_sight = player.line_of_sight.contains(&player_goal);
+                        let in_line_of_sight = player.line_of_sight.contains(player_goal);
                         let spell_range = player
                             .spells
                             .get(index as usize)
@@ -998,14 +1266,19 @@ pub fn update(
                             .spell_type
                             .clone()
                     };
-                    let positions = combat::do_spell_combat(
-                        &mut game.player,
-                        map_ref,
-                        player_pos,
-                        player_goal,
-                        &spell_type,
-                        &game.lua_interface,
-                    );
+                    let positions = if spell_type.kind == SpellKind::Summon {
+                        summon_ally(game, map_ref, player_goal, &spell_type)
+                    } else {
+                        combat::do_spell_combat(
+                            &mut game.player,
+                            map_ref,
+                            player_pos,
+                            player_goal,
+                            &spell_type,
+                            &game.lua_interface,
+                            game.friendly_fire,
+                        )
+                    };
                     game.animate_for = 0.2;
 
                     if let Some(sprite) = &spell_type.sprite {
@@ -1015,6 +1288,9 @@ pub fn update(
                     }
 
                     game.last_player_event = PlayerEvent::SpellCast;
+                    // The turn advances once the spell animation finishes
+                    game.player_action_cost = spell_type.action_cost.unwrap_or(ACTION_COST_NORMAL);
+                    check_player_death(game);
                     let mut player = game.player.borrow_mut();
                     player.selected_spell = None;
                     player.goal_position = None;
@@ -1035,7 +1311,7 @@ pub fn update(
                         (player_pos.euclidean_distance_squared(&player_goal)
                             <= (range * range) as f64
                             || player_pos.is_neighbor(&player_goal))
-                            && player.line_of_sight.contains(&player_goal)
+                            && player.line_of_sight.contains(player_goal)
                     } else {
                         false
                     }
@@ -1053,14 +1329,42 @@ pub fn update(
                     player_goal,
                     &game.lua_interface,
                 );
+                game.player_action_cost = game.player.borrow().attack_cost();
+                check_player_death(game);
             } else {
+                let keys = game.player.borrow().keys;
                 let path: Option<Vec<Position>> =
-                    Navigator::find_path(player_pos, player_goal, |pos| {
-                        map_ref.0.borrow().is_tile_walkable(pos)
+                    Navigator::find_weighted_path(player_pos, player_goal, |pos| {
+                        let map = map_ref.0.borrow();
+                        if !map.generated_map.tiles.in_bounds(pos) {
+                            return None;
+                        }
+                        let tile = &map.generated_map.tiles[pos];
+                        let empty = map.generated_map.tiles.creature(pos) == NO_CREATURE;
+                        // Doors on the way get opened when the player reaches them
+                        let openable = match tile.door {
+                            Some(DoorState::Closed) => empty,
+                            Some(DoorState::Locked) => empty && keys > 0,
+                            _ => false,
+                        };
+                        // Known traps are walked around when there is another way
+                        let trap_cost = match tile.trap {
+                            Some(trap) if !trap.hidden => TRAP_PATH_PENALTY,
+                            _ => 0,
+                        };
+                        (map.is_tile_walkable(pos) || openable)
+                            .then(|| tile.def().path_cost() + trap_cost)
                     });
 
                 if let Some(path) = path {
-                    if path.len() > 1 {
+                    if path.len() > 1
+                        && map_ref.0.borrow().generated_map.tiles[path[1]].is_door_closed()
+                    {
+                        if player_open_door(game, map_ref, path[1]) {
+                            should_update_turn = true;
+                        }
+                        game.last_player_event = PlayerEvent::AutoMove;
+                    } else if path.len() > 1 {
                         new_player_pos = Some(path[1]);
                         game.last_player_event = PlayerEvent::AutoMove;
                     } else {
@@ -1131,8 +1435,30 @@ pub fn update(
                     pos,
                     &game.lua_interface,
                 );
-            } else if map.generated_map.tiles[pos].is_border(&pos) && !map.monsters.is_empty() {
+                game.player_action_cost = game.player.borrow().attack_cost();
+                check_player_death(game);
+            } else if map.generated_map.tiles.is_border(pos) && map.has_hostiles() {
                 game.last_player_event = PlayerEvent::Cancel;
+            } else if map.generated_map.tiles.in_bounds(pos)
+                && map.generated_map.tiles[pos].is_door_closed()
+            {
+                drop(map);
+                if player_open_door(game, map_ref, pos) {
+                    should_update_turn = true;
+                }
+                game.last_player_event = PlayerEvent::Move;
+            } else if map.is_tile_destructible(pos) && game.player.borrow().can_dig() {
+                drop(map);
+                if player_dig(game, map_ref, pos) {
+                    should_update_turn = true;
+                    game.player_action_cost = game.player.borrow().attack_cost() * DIG_SWINGS;
+                }
+                game.last_player_event = PlayerEvent::Move;
+            } else if map.is_tile_ally_occupied(pos) {
+                swapped_ally = Some(map.generated_map.tiles.creature(pos));
+                new_player_pos = Some(pos);
+                should_update_turn = true;
+                game.last_player_event = PlayerEvent::Move;
             } else {
                 if map.is_tile_walkable(pos) {
                     new_player_pos = Some(pos);
@@ -1144,6 +1470,37 @@ pub fn update(
         }
 
         game.player.borrow_mut().goal_position = None;
+    } else if player_action == KeyboardAction::ToggleFriendlyFire {
+        game.friendly_fire = !game.friendly_fire;
+        println!(
+            "Friendly fire {}",
+            if game.friendly_fire { "on" } else { "off" }
+        );
+    } else if player_action == KeyboardAction::CloseDoor {
+        let mut map = map_ref.0.borrow_mut();
+        let closed = player_pos
+            .positions_around()
+            .into_iter()
+            .filter(|&pos| map.close_door(pos))
+            .count();
+        if closed > 0 {
+            let radius = map.max_fov_radius();
+            map.compute_player_fov(&mut game.player.borrow_mut(), radius);
+            should_update_turn = true;
+            game.last_player_event = PlayerEvent::Wait;
+        }
+        game.player.borrow_mut().goal_position = None;
+    } else if player_action == KeyboardAction::Search {
+        let mut map = map_ref.0.borrow_mut();
+        let found = map.search_for_traps(player_pos, SEARCH_RADIUS);
+        if found > 0 {
+            println!("Player found {} trap(s)!", found);
+        } else {
+            println!("Player searches but finds nothing.");
+        }
+        new_player_pos = Some(player_pos);
+        game.player.borrow_mut().goal_position = None;
+        game.last_player_event = PlayerEvent::Wait;
     } else if player_action == KeyboardAction::Wait {
         new_player_pos = Some(player_pos); // Stay in place
         game.player.borrow_mut().goal_position = None; // Clear goal position
@@ -1151,10 +1508,33 @@ pub fn update(
         game.last_player_event = PlayerEvent::Wait;
     }
 
+    let mut step_damage = None;
+    let mut stepped_trap = None;
+    if let Some(pos) = new_player_pos.filter(|&pos| pos != player_pos) {
+        let tile = &map_ref.0.borrow().generated_map.tiles[pos];
+        let def = tile.def();
+        game.player_action_cost = def.movement_cost;
+        if let Some(OnStepEffect::Damage { amount }) = def.on_step {
+            step_damage = Some((def, amount));
+        }
+        stepped_trap = tile.trap.map(|_| pos);
+    }
+
     if let Some(pos) = new_player_pos {
         let mut map = map_ref.0.borrow_mut();
-        map.generated_map.tiles[player_pos].creature = NO_CREATURE;
-        map.generated_map.tiles[pos].creature = PLAYER_CREATURE_ID;
+        map.generated_map
+            .tiles
+            .set_creature(player_pos, NO_CREATURE);
+        map.generated_map
+            .tiles
+            .set_creature(pos, PLAYER_CREATURE_ID);
+
+        // Allies step aside by trading places with the player
+        if let Some(ally) = swapped_ally.and_then(|id| map.monsters.get(&id).cloned()) {
+            let mut ally = ally.borrow_mut();
+            ally.set_pos(player_pos);
+            map.generated_map.tiles.set_creature(player_pos, ally.id);
+        }
 
         let mut player = game.player.borrow_mut();
         player.set_pos(pos);
@@ -1163,22 +1543,33 @@ pub fn update(
             player.goal_position = None; // Clear goal position if reached
         }
 
-        map.compute_player_fov(&mut player, max(GRID_WIDTH, GRID_HEIGHT));
+        let radius = map.max_fov_radius();
+        map.compute_player_fov(&mut player, radius);
         should_update_turn = true;
 
         let mut to_remove: Vec<usize> = Vec::new();
 
-        for (idx, item) in map.generated_map.tiles[pos].items.iter().rev().enumerate() {
+        for (idx, item) in map.generated_map.tiles.items(pos).iter().rev().enumerate() {
             match item {
                 ItemKind::Orb(_) => {
                     println!("Player picked up an orb at index {idx}!");
                     player.sp += 1;
                     to_remove.push(idx); // Collect for removal
                 }
-                ItemKind::Teleport(_) => {
-                    if map.monsters.is_empty() {
-                        println!("Player walked downstairs.");
-                        game.last_player_event = PlayerEvent::ClimbDown;
+                ItemKind::Key(_) => {
+                    player.keys += 1;
+                    println!("Player picked up a key, now holding {}.", player.keys);
+                    to_remove.push(idx);
+                }
+                ItemKind::Teleport(teleport) => {
+                    if !map.has_hostiles() {
+                        if teleport.up {
+                            println!("Player walked upstairs.");
+                            game.last_player_event = PlayerEvent::ClimbUp;
+                        } else {
+                            println!("Player walked downstairs.");
+                            game.last_player_event = PlayerEvent::ClimbDown;
+                        }
                         return;
                     }
                 }
@@ -1193,155 +1584,661 @@ pub fn update(
         }
 
         for idx in to_remove {
-            map.generated_map.tiles[pos].remove_item(idx);
+            map.generated_map.tiles.remove_item(pos, idx);
         }
 
-        if map.generated_map.tiles[pos].is_border(&pos) {
+        if map.generated_map.tiles.is_border(pos) {
             game.last_player_event = PlayerEvent::ReachBorder;
         }
     }
 
+    if let Some((def, amount)) = step_damage {
+        println!("The {} burns the player!", def.name.to_lowercase());
+        combat::do_damage(
+            &mut game.player,
+            map_ref,
+            PLAYER_CREATURE_ID,
+            amount,
+            &game.lua_interface,
+        );
+        if game.player.borrow().hp == 0 {
+            game.last_player_event = PlayerEvent::Death;
+        }
+    }
+
+    if let Some(pos) = stepped_trap {
+        game.player.borrow_mut().goal_position = None;
+        trigger_trap(game, map_ref, PLAYER_CREATURE_ID, pos);
+    }
+
     if should_update_turn {
         update_turn(game, map_ref);
     }
 }
 
 pub fn update_turn(game: &mut GameState, map_ref: &MapRc) {
-    let mut player = game.player.borrow_mut();
-    if player.accumulated_speed >= 200 {
-        player.accumulated_speed -= 100;
-        return;
+    let player_speed = game.player.borrow().get_speed();
+    let action_cost = std::mem::replace(&mut game.player_action_cost, ACTION_COST_NORMAL);
+
+    let mut scheduler = game.scheduler.borrow_mut();
+    scheduler.schedule(
+        Actor::Player,
+        Scheduler::delay_for(action_cost, player_speed),
+    );
+
+    // Anything that showed up since the last turn (new map, spawns, summons) joins the queue
+    for (id, monster) in map_ref.0.borrow().monsters.iter() {
+        if !scheduler.is_scheduled(Actor::Monster(*id)) {
+            let speed = monster.borrow().speed;
+            scheduler.schedule(
+                Actor::Monster(*id),
+                Scheduler::delay_for(ACTION_COST_NORMAL, speed),
+            );
+        }
     }
-    let mut player_accumulated_speed = player.accumulated_speed;
-    let player_speed = player.get_speed();
-    let player_pos = player.position.clone();
-    drop(player);
-
-    while player_accumulated_speed < 100 {
-        let map = map_ref.0.borrow_mut();
-        let mut monsters = map.monsters.clone(); // Clone the monsters to avoid borrowing conflicts
-        drop(map);
-
-        let mut update_monsters_again = true;
-        let mut update_iteration = 0;
-
-        while update_monsters_again {
-            update_monsters_again = false;
-            for (id, monster_ref) in &mut monsters {
-                let monster = monster_ref.borrow_mut();
-                if monster.hp <= 0 {
-                    continue; // Skip dead monsters
+    drop(scheduler);
+
+    // How many times each monster has acted since the player's last action
+    let mut actions_taken: HashMap<u32, u32> = HashMap::new();
+
+    loop {
+        let actor = game.scheduler.borrow_mut().pop();
+        let Some(actor) = actor else {
+            break;
+        };
+
+        match actor {
+            Actor::Player => break,
+            Actor::Event(event_id) => {
+                if let Err(e) = game.lua_interface.borrow().run_timed_event(event_id) {
+                    eprintln!("Error running Lua timed event: {}", e);
+                }
+            }
+            Actor::Status(status_id) => {
+                if tick_status_effect(game, map_ref, status_id) {
+                    game.scheduler.borrow_mut().schedule(
+                        Actor::Status(status_id),
+                        Scheduler::delay_for(ACTION_COST_NORMAL, BASE_SPEED),
+                    );
+                }
+                if game.last_player_event == PlayerEvent::Death {
+                    return;
+                }
+            }
+            Actor::Monster(id) => {
+                let monster_ref = map_ref.0.borrow().monsters.get(&id).cloned();
+                // Stale entries belong to dead monsters or monsters left on another map
+                let Some(monster_ref) = monster_ref else {
+                    continue;
+                };
+                if monster_ref.borrow().hp <= 0 {
+                    continue;
                 }
 
-                if monster.kind.is_scripted() {
-                    drop(monster);
-                    let mut clone = monster_ref.clone();
-                    let r = game
-                        .lua_interface
-                        .borrow_mut()
-                        .on_update(&mut clone, update_iteration);
-                    if let Err(e) = r {
-                        eprintln!("Error calling Lua on_update: {}", e);
-                    } else {
-                        if r.unwrap() {
-                            // If the Lua script returned true, we skip the rest of the update for this monster
-                            // The update has already been handled in Lua
-                            continue;
-                        }
-                    }
-                    drop(clone);
-                } else {
-                    drop(monster); // Just drop the immutable borrow
+                let iteration = actions_taken.entry(id).or_insert(0);
+                let update_iteration = *iteration;
+                *iteration += 1;
+
+                let cost = update_monster(game, map_ref, id, &monster_ref, update_iteration);
+                if game.last_player_event == PlayerEvent::Death {
+                    return;
                 }
 
-                let mut monster = monster_ref.borrow_mut();
-                let mut monster_speed = monster.kind.speed + monster.accumulated_speed;
-
-                if monster_speed >= 100 {
-                    monster_speed -= 100;
-
-                    let monster_pos = monster.pos();
-                    let path =
-                        find_monster_path(map_ref, monster_pos, player_pos, monster.kind.flying);
-
-                    if let Some(path) = path {
-                        if path.len() > 1 {
-                            let next_step = path[1];
-
-                            if next_step == player_pos {
-                                println!(
-                                    "Monster {} hit player for {} damage!",
-                                    monster.name(),
-                                    monster.kind.melee_damage
-                                );
-                                {
-                                    let mut player = game.player.borrow_mut();
-                                    player.add_health(-monster.kind.melee_damage);
-                                    if player.hp <= 0 {
-                                        println!("Player has been defeated!");
-                                        game.last_player_event = PlayerEvent::Death;
-                                        return;
-                                    }
-                                }
-
-                                continue;
-                            }
+                let speed = monster_ref.borrow().speed;
+                game.scheduler
+                    .borrow_mut()
+                    .schedule(Actor::Monster(id), Scheduler::delay_for(cost, speed));
+            }
+        }
+    }
 
-                            //monster_moves.push((monster_pos, next_step, *id as usize));
-                            monster.set_pos(next_step);
+    game.turn = (game.scheduler.borrow().now() / ACTION_COST_NORMAL as u64) as u32 + 1;
 
-                            let mut map = map_ref.0.borrow_mut();
-                            map.generated_map.tiles[monster_pos].creature = NO_CREATURE;
-                            map.generated_map.tiles[next_step].creature = *id;
-                        }
-                    }
-                }
+    // Glowing monsters moved around, so what the player can see has changed too
+    let mut map = map_ref.0.borrow_mut();
+    let radius = map.max_fov_radius();
+    map.compute_player_fov(&mut game.player.borrow_mut(), radius);
+}
+
+/// Puts `effect` on its target and queues its first tick one regular action from now.
+fn add_status_effect(game: &mut GameState, effect: StatusEffect) {
+    let id = game.status_effects.add(effect);
+    game.scheduler.borrow_mut().schedule(
+        Actor::Status(id),
+        Scheduler::delay_for(ACTION_COST_NORMAL, BASE_SPEED),
+    );
+}
+
+/// Applies one tick of the status effect `status_id`. Returns whether it has
+/// ticks left and should be queued again.
+fn tick_status_effect(game: &mut GameState, map_ref: &MapRc, status_id: u32) -> bool {
+    let Some(effect) = game.status_effects.tick(status_id) else {
+        return false;
+    };
+    // The creature died or was left behind on another map
+    let present = effect.target == PLAYER_CREATURE_ID
+        || map_ref.0.borrow().monsters.contains_key(&effect.target);
+    if !present {
+        game.status_effects.remove(status_id);
+        return false;
+    }
+
+    match effect.kind {
+        StatusKind::Poison { damage } => {
+            println!("The {} hurts {}!", effect.kind.name(), effect.target);
+            combat::do_damage(
+                &mut game.player,
+                map_ref,
+                effect.target,
+                damage,
+                &game.lua_interface,
+            );
+        }
+    }
+    if game.player.borrow().hp == 0 {
+        game.last_player_event = PlayerEvent::Death;
+    }
+    effect.ticks_left > 1
+}
+
+/// Runs a single action for a monster and returns how long it took.
+fn update_monster(
+    game: &mut GameState,
+    map_ref: &MapRc,
+    id: u32,
+    monster_ref: &MonsterRc,
+    update_iteration: u32,
+) -> u32 {
+    if monster_ref.borrow().kind.is_scripted() {
+        let mut clone = monster_ref.clone();
+        let r = game
+            .lua_interface
+            .borrow_mut()
+            .on_update(&mut clone, update_iteration);
+        match r {
+            // The update has already been handled in Lua
+            Ok(Some(cost)) => return cost,
+            Ok(None) => {}
+            Err(e) => eprintln!("Error calling Lua on_update: {}", e),
+        }
+    }
 
-                monster.accumulated_speed = monster_speed as u32;
+    let monsters = map_ref.0.borrow().monsters.clone(); // Clone the monsters to avoid borrowing conflicts
+    let player_pos = game.player.borrow().position;
+    let mut monster = monster_ref.borrow_mut();
 
-                if monster_speed >= 100 {
-                    update_monsters_again = true;
+    let monster_pos = monster.pos();
+    let goal = choose_monster_goal(&monsters, id, monster_pos, monster.faction, player_pos);
+    let (goal_pos, target_id) = match goal {
+        MonsterGoal::Attack(target_id, pos) => (pos, Some(target_id)),
+        MonsterGoal::Follow(pos) => (pos, None),
+        MonsterGoal::Idle => (monster_pos, None),
+    };
+    let path = if goal_pos == monster_pos {
+        None
+    } else {
+        find_monster_path(
+            map_ref,
+            monster_pos,
+            goal_pos,
+            monster.kind.flying,
+            monster.kind.opens_doors,
+        )
+    };
+
+    let Some(next_step) = path.and_then(|path| path.get(1).copied()) else {
+        return ACTION_COST_NORMAL;
+    };
+
+    if next_step == player_pos {
+        if target_id != Some(PLAYER_CREATURE_ID) {
+            // Allies wait rather than walking into the player
+            return ACTION_COST_NORMAL;
+        }
+        println!(
+            "Monster {} hit player for {} damage!",
+            monster.name(),
+            monster.melee_damage
+        );
+        if monster.has_affix(MonsterAffix::Vampiric) {
+            let heal = (monster.melee_damage / 2).max(1);
+            monster.add_health(heal);
+        }
+        let mut player = game.player.borrow_mut();
+        player.add_health(-monster.melee_damage);
+        if player.hp <= 0 {
+            println!("Player has been defeated!");
+            game.last_player_event = PlayerEvent::Death;
+        }
+        return monster.kind.attack_cost;
+    }
+
+    if let Some(target_id) = target_id.filter(|_| next_step == goal_pos) {
+        println!(
+            "Monster {} hits {} for {} damage!",
+            monster.name(),
+            target_id,
+            monster.melee_damage
+        );
+        let damage = monster.melee_damage;
+        let attack_cost = monster.kind.attack_cost;
+        drop(monster);
+        combat::do_damage(
+            &mut game.player,
+            map_ref,
+            target_id,
+            damage,
+            &game.lua_interface,
+        );
+        return attack_cost;
+    }
+
+    let mut map = map_ref.0.borrow_mut();
+    if map.generated_map.tiles[next_step].is_door_closed() {
+        // Opening the door takes the whole turn. Monsters carry no keys, so a
+        // locked door blocks the step and they wait instead
+        if map.generated_map.tiles[next_step].door == Some(DoorState::Closed)
+            && map.open_door(next_step, &mut 0)
+        {
+            println!("{} opens a door.", monster.name());
+        }
+        return ACTION_COST_NORMAL;
+    }
+
+    monster.set_pos(next_step);
+
+    map.generated_map
+        .tiles
+        .set_creature(monster_pos, NO_CREATURE);
+    map.generated_map.tiles.set_creature(next_step, id);
+
+    // Flyers pass over the terrain without being slowed or hurt by it
+    if monster.kind.flying {
+        return ACTION_COST_NORMAL;
+    }
+    let def = map.generated_map.tiles[next_step].def();
+    let has_trap = map.generated_map.tiles[next_step].trap.is_some();
+    drop(map);
+    if let Some(OnStepEffect::Damage { amount }) = def.on_step {
+        println!("The {} burns {}!", def.name.to_lowercase(), monster.name());
+        drop(monster);
+        combat::do_damage(&mut game.player, map_ref, id, amount, &game.lua_interface);
+    } else {
+        drop(monster);
+    }
+    if has_trap && map_ref.0.borrow().monsters.contains_key(&id) {
+        trigger_trap(game, map_ref, id, next_step);
+    }
+    def.movement_cost
+}
+
+/// Picks what a monster should move towards this turn based on its faction.
+fn choose_monster_goal(
+    monsters: &HashMap<u32, MonsterRc>,
+    id: u32,
+    monster_pos: Position,
+    faction: Faction,
+    player_pos: Position,
+) -> MonsterGoal {
+    let distance = |pos: &Position| monster_pos.euclidean_distance_squared(pos);
+    let others = monsters.iter().filter(|(other_id, _)| **other_id != id);
+
+    match faction {
+        Faction::Neutral => MonsterGoal::Idle,
+        Faction::Hostile => {
+            let mut goal = MonsterGoal::Attack(PLAYER_CREATURE_ID, player_pos);
+            let mut best = distance(&player_pos);
+            for (other_id, other) in others {
+                let other = other.borrow();
+                if other.faction == Faction::Allied && other.hp > 0 {
+                    let d = distance(&other.position);
+                    if d < best {
+                        best = d;
+                        goal = MonsterGoal::Attack(*other_id, other.position);
+                    }
                 }
             }
-            update_iteration += 1;
+            goal
+        }
+        Faction::Allied => {
+            let range = (ALLY_AGGRO_RANGE * ALLY_AGGRO_RANGE) as f64;
+            let target = others
+                .filter_map(|(other_id, other)| {
+                    let other = other.borrow();
+                    (other.faction == Faction::Hostile && other.hp > 0)
+                        .then(|| (*other_id, other.position))
+                })
+                .filter(|(_, pos)| distance(pos) <= range)
+                .min_by(|a, b| distance(&a.1).total_cmp(&distance(&b.1)));
+
+            if let Some((target_id, pos)) = target {
+                MonsterGoal::Attack(target_id, pos)
+            } else if !monster_pos.in_range(&player_pos, ALLY_FOLLOW_DISTANCE) {
+                MonsterGoal::Follow(player_pos)
+            } else {
+                MonsterGoal::Idle
+            }
         }
+    }
+}
+
+/// Opens the door at `pos` for the player and refreshes what they can see through it.
+fn player_open_door(game: &mut GameState, map_ref: &MapRc, pos: Position) -> bool {
+    let mut map = map_ref.0.borrow_mut();
+    let mut player = game.player.borrow_mut();
+    if !map.open_door(pos, &mut player.keys) {
+        return false;
+    }
+    println!("Player opens the door.");
+    let radius = map.max_fov_radius();
+    map.compute_player_fov(&mut player, radius);
+    true
+}
 
-        game.turn += 1;
-        player_accumulated_speed += player_speed;
+/// Digs through the terrain at `pos` and refreshes what the player can see past it.
+fn player_dig(game: &mut GameState, map_ref: &MapRc, pos: Position) -> bool {
+    let mut map = map_ref.0.borrow_mut();
+    let name = map.generated_map.tiles[pos].def().name.clone();
+    if !map.destroy_terrain(pos) {
+        return false;
     }
-    player_accumulated_speed -= 100;
-    game.player.borrow_mut().accumulated_speed = player_accumulated_speed;
+    println!("Player digs through the {}.", name.to_lowercase());
+    let radius = map.max_fov_radius();
+    map.compute_player_fov(&mut game.player.borrow_mut(), radius);
+    true
 }
 
 fn find_monster_path(
     map_ref: &MapRc,
     monster_pos: Position,
-    player_pos: Position,
+    goal_pos: Position,
     flying: bool,
+    opens_doors: bool,
 ) -> Option<Vec<Position>> {
-    Navigator::find_path(monster_pos, player_pos, |pos| {
-        if pos.x >= GRID_WIDTH || pos.y >= GRID_HEIGHT {
-            return false;
-        }
+    Navigator::find_weighted_path(monster_pos, goal_pos, |pos| {
         // borrow the map _immutably_ each time to see current occupancy:
         let map = map_ref.0.borrow();
+        if !map.generated_map.tiles.in_bounds(pos) {
+            return None;
+        }
+        let tile = &map.generated_map.tiles[pos];
+        // The goal is usually occupied by whoever is being chased
+        if pos == goal_pos {
+            return Some(ACTION_COST_NORMAL);
+        }
 
-        if flying {
-            return !map.generated_map.tiles[pos].is_blocking();
+        if tile.door == Some(DoorState::Closed) && opens_doors {
+            (map.generated_map.tiles.creature(pos) == NO_CREATURE).then_some(ACTION_COST_NORMAL)
+        } else if flying {
+            (!map.generated_map.tiles.is_blocking(pos)).then_some(ACTION_COST_NORMAL)
         } else {
-            return map.generated_map.tiles[pos].is_walkable();
+            let trap_cost = if tile.trap.is_some() {
+                TRAP_PATH_PENALTY
+            } else {
+                0
+            };
+            map.generated_map
+                .tiles
+                .is_walkable(pos)
+                .then(|| tile.def().path_cost() + trap_cost)
         }
     })
 }
+/// Brings in the spell's summoned creature on the targeted tile as an ally.
+fn summon_ally(
+    game: &mut GameState,
+    map_ref: &MapRc,
+    target_pos: Position,
+    spell_type: &SpellType,
+) -> Vec<Position> {
+    let Some(kind_id) = spell_type.summon_kind else {
+        eprintln!("Summon spell {} has no summon_kind", spell_type.name);
+        return Vec::new();
+    };
+
+    match add_monster_to_map(
+        &game.monster_kinds,
+        &game.items,
+        map_ref,
+        kind_id,
+        target_pos,
+        None,
+        Some(Faction::Allied),
+    ) {
+        Some(monster) => {
+            println!("{} answers the summons!", monster.borrow().name());
+            vec![target_pos]
+        }
+        None => {
+            println!("The summoning fails, there is no room.");
+            Vec::new()
+        }
+    }
+}
+
+/// Spawns a monster of `kind_id` at `pos`, honoring tile occupancy and population caps.
+/// `faction` overrides the one of its kind, allies never roll a rarity so their
+/// affixes cannot turn on the player.
+fn add_monster_to_map(
+    monster_kinds: &MonsterKindsDataArc,
+    items: &ItemsArc,
+    map_rc: &MapRc,
+    kind_id: u32,
+    pos: Position,
+    spawner_id: Option<u32>,
+    faction: Option<Faction>,
+) -> Option<MonsterRc> {
+    {
+        let map = map_rc.0.borrow();
+        if !pos.is_valid(map.generated_map.width(), map.generated_map.height())
+            || !map.is_tile_walkable(pos)
+            || map.generated_map.tiles.creature(pos) != NO_CREATURE
+            || !map.can_add_monster(spawner_id)
+        {
+            return None;
+        }
+    }
+
+    let binding = monster_kinds.read().unwrap();
+    let binding = binding.vec.read().unwrap();
+    let Some(kind) = binding.iter().find(|mt| mt.id == kind_id) else {
+        eprintln!("Cannot add monster, unknown monster kind {}", kind_id);
+        return None;
+    };
+
+    // Create a new monster and wrap it in Rc
+    let mut monster = Monster::new(pos.clone(), kind.clone());
+    monster.spawner_id = spawner_id;
+    if let Some(faction) = faction {
+        monster.faction = faction;
+    }
+    if monster.faction != Faction::Allied {
+        let tier = map_rc.0.borrow().generated_map.tier;
+        monster.roll_rarity(tier, &items.read().unwrap(), &mut thread_rng());
+    }
+    let monster = Rc::new(RefCell::new(monster));
+
+    let mut map = map_rc.0.borrow_mut();
+    map.generated_map
+        .tiles
+        .set_creature(pos, monster.borrow().id); // Set the creature ID in the tile
+    // Wrap the monster in Rc and push to creatures
+    map.monsters.insert(monster.borrow().id, monster.clone());
+    Some(monster)
+}
+
 fn teleport_creature_to(player: &PlayerRc, map_rc: &MapRc, creature_id: u32, pos: Position) {
     let mut map = map_rc.0.borrow_mut();
     if creature_id == PLAYER_CREATURE_ID as u32 {
         let mut player_ref = player.borrow_mut();
+        let old_pos = player_ref.position;
+        if map.generated_map.tiles.in_bounds(old_pos)
+            && map.generated_map.tiles.creature(old_pos) == creature_id
+        {
+            map.generated_map.tiles.set_creature(old_pos, NO_CREATURE);
+        }
         player_ref.position = pos;
-    } else if let Some(monster) = map.monsters.get(&creature_id) {
+        map.generated_map.tiles.set_creature(pos, creature_id);
+        let radius = map.max_fov_radius();
+        map.compute_player_fov(&mut player_ref, radius);
+    } else if let Some(monster) = map.monsters.get(&creature_id).cloned() {
         let mut monster_ref = monster.borrow_mut();
+        let old_pos = monster_ref.position;
+        if map.generated_map.tiles.in_bounds(old_pos)
+            && map.generated_map.tiles.creature(old_pos) == creature_id
+        {
+            map.generated_map.tiles.set_creature(old_pos, NO_CREATURE);
+        }
         monster_ref.position = pos;
         drop(monster_ref);
-        map.generated_map.tiles[pos].creature = creature_id;
+        map.generated_map.tiles.set_creature(pos, creature_id);
+    }
+}
+
+/// Sets off the trap at `pos` on the creature `target_id` that just stepped there.
+fn trigger_trap(game: &mut GameState, map_ref: &MapRc, target_id: u32, pos: Position) {
+    let trap = {
+        let mut map = map_ref.0.borrow_mut();
+        let Some(trap) = map.generated_map.tiles[pos].trap.as_mut() else {
+            return;
+        };
+        trap.hidden = false;
+        *trap
+    };
+    let kind = trap.kind();
+    let name = if target_id == PLAYER_CREATURE_ID {
+        "Player".to_string()
+    } else {
+        match map_ref.0.borrow().monsters.get(&target_id) {
+            Some(monster) => monster.borrow().name().to_string(),
+            None => return,
+        }
+    };
+    println!("{} sets off a {} trap!", name, kind.name.to_lowercase());
+
+    match &kind.effect {
+        TrapEffect::Spike { damage } => {
+            combat::do_damage(
+                &mut game.player,
+                map_ref,
+                target_id,
+                *damage,
+                &game.lua_interface,
+            );
+        }
+        TrapEffect::PoisonGas {
+            damage,
+            radius,
+            lingering,
+        } => {
+            let mut targets: Vec<u32> = map_ref
+                .0
+                .borrow()
+                .monsters
+                .iter()
+                .filter(|(_, monster)| monster.borrow().position.in_range(&pos, *radius))
+                .map(|(id, _)| *id)
+                .collect();
+            if game.player.borrow().position.in_range(&pos, *radius) {
+                targets.push(PLAYER_CREATURE_ID);
+            }
+            for id in targets {
+                // Earlier victims can take others down with them on death
+                let alive =
+                    id == PLAYER_CREATURE_ID || map_ref.0.borrow().monsters.contains_key(&id);
+                if alive {
+                    combat::do_damage(&mut game.player, map_ref, id, *damage, &game.lua_interface);
+                }
+                let survived = if id == PLAYER_CREATURE_ID {
+                    game.player.borrow().hp > 0
+                } else {
+                    map_ref.0.borrow().monsters.contains_key(&id)
+                };
+                if survived && *lingering > 0 {
+                    add_status_effect(
+                        game,
+                        StatusEffect {
+                            target: id,
+                            kind: StatusKind::Poison {
+                                damage: (*damage / 2).max(1),
+                            },
+                            ticks_left: *lingering,
+                        },
+                    );
+                }
+            }
+        }
+        TrapEffect::Alarm { radius } => {
+            let map = map_ref.0.borrow();
+            for monster in map.monsters.values() {
+                let mut monster = monster.borrow_mut();
+                if monster.faction == Faction::Neutral && monster.position.in_range(&pos, *radius) {
+                    monster.faction = Faction::Hostile;
+                    println!("{} is alerted by the noise!", monster.name());
+                }
+            }
+        }
+        TrapEffect::Teleport => {
+            let destination = map_ref.0.borrow().get_random_free_position();
+            if let Some(destination) = destination {
+                teleport_creature_to(&game.player, map_ref, target_id, destination);
+                println!("{} vanishes in a flash of light.", name);
+            }
+        }
+        TrapEffect::Pit => {
+            if target_id == PLAYER_CREATURE_ID {
+                let leads_down = map_ref
+                    .0
+                    .borrow()
+                    .generated_map
+                    .downstair_teleport
+                    .is_some();
+                if leads_down {
+                    println!("Player falls through to the floor below!");
+                    game.last_player_event = PlayerEvent::Fall;
+                } else {
+                    println!("The pit is shallow, the player climbs back out.");
+                }
+            } else {
+                let mut map = map_ref.0.borrow_mut();
+                map.generated_map.tiles.set_creature(pos, NO_CREATURE);
+                map.monsters.remove(&target_id);
+                println!("{} falls into the pit and is gone.", name);
+            }
+        }
+        TrapEffect::Scripted => {
+            let result = if target_id == PLAYER_CREATURE_ID {
+                game.lua_interface
+                    .borrow()
+                    .on_step(kind.script_id, game.player.clone(), &pos)
+            } else {
+                let monster = map_ref.0.borrow().monsters.get(&target_id).cloned();
+                match monster {
+                    Some(monster) => {
+                        game.lua_interface
+                            .borrow()
+                            .on_step(kind.script_id, monster, &pos)
+                    }
+                    None => Ok(None),
+                }
+            };
+            match result {
+                Ok(Some(damage)) if damage > 0 => {
+                    combat::do_damage(
+                        &mut game.player,
+                        map_ref,
+                        target_id,
+                        damage,
+                        &game.lua_interface,
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Error calling Lua on_step: {}", e),
+            }
+        }
+    }
+
+    if game.player.borrow().hp == 0 {
+        game.last_player_event = PlayerEvent::Death;
     }
 }
@@ -36,6 +36,10 @@ pub enum KeyboardAction {
     AttackChooseTarget,
     AttackConfirm,
     OpenCharacterSheet,
+    ToggleFriendlyFire,
+    CloseDoor,
+    Search,
+    ToggleFloorMap,
 }
 
 pub struct Input {
@@ -113,6 +117,18 @@ impl Input {
         if is_key_pressed(KeyCode::C) {
             keyboard_action = KeyboardAction::OpenCharacterSheet;
         }
+        if is_key_pressed(KeyCode::F) {
+            keyboard_action = KeyboardAction::ToggleFriendlyFire;
+        }
+        if is_key_pressed(KeyCode::X) {
+            keyboard_action = KeyboardAction::CloseDoor;
+        }
+        if is_key_pressed(KeyCode::S) {
+            keyboard_action = KeyboardAction::Search;
+        }
+        if is_key_pressed(KeyCode::M) {
+            keyboard_action = KeyboardAction::ToggleFloorMap;
+        }
 
         self.keyboard_action = keyboard_action;
         self.direction_intention = direction;
@@ -31,6 +31,7 @@ use serde::Deserialize;
 use crate::items::{
     container::Container,
     holdable::{Armor, BaseHoldableItemData, Boots, Helmet, HoldableGroupKind, Shield, Weapon},
+    key::Key,
     orb::Orb,
     teleport::Teleport,
 };
@@ -58,6 +59,7 @@ pub struct BaseItemData {
 #[derive(PartialEq, Clone, Debug)]
 pub enum ItemKind {
     Orb(Orb),
+    Key(Key),
     Teleport(Teleport),
     Holdable(HoldableGroupKind),
     Container(Container),
@@ -36,6 +36,9 @@ pub struct BaseHoldableItemData {
     pub attribute_modifier: String,
     pub required: Vec<Vec<serde_json::Value>>,
     pub slot: String,
+    // Carrying the item lights up this many tiles around the player
+    #[serde(default)]
+    pub light_radius: u32,
     pub script: Option<String>,
     #[serde(default)]
     pub scripted: bool,
@@ -104,6 +107,10 @@ pub struct Weapon {
     pub two_handed: bool,
     #[serde(default)]
     pub range: Option<u32>,
+    #[serde(default)]
+    pub attack_cost: Option<u32>, // Time an attack takes, heavy weapons are slower
+    #[serde(default)]
+    pub digs: bool, // Walking into destructible terrain digs through it
 }
 
 impl UserData for Weapon {
@@ -26,5 +26,6 @@ pub mod base_item;
 pub mod collection;
 pub mod container;
 pub mod holdable;
+pub mod key;
 pub mod orb;
 pub mod teleport;
@@ -24,5 +24,6 @@ use crate::items::base_item::Item;
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct Teleport {
-    //
+    // Upstairs lead back to the floor above, everything else leads down
+    pub up: bool,
 }
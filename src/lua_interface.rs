@@ -26,17 +26,19 @@
 
 use mlua::{
     AnyUserData, AnyUserDataExt, Error, FromLuaMulti, Function, IntoLuaMulti, Lua, MultiValue,
-    RegistryKey, Result, Table, UserDataMetatable, Value,
+    RegistryKey, Result, Table, UserData, UserDataMetatable, Value,
 };
 
 use std::fs;
 use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
 use std::{cell::RefCell, collections::HashMap};
 
 use crate::maps::map::{Map, MapRc};
 use crate::monster::{Monster, MonsterRc};
 use crate::monster_kind::MonsterKind;
 use crate::player::PlayerRc;
+use crate::scheduler::ACTION_COST_NORMAL;
 use crate::{items::holdable::Weapon, position::Position};
 
 macro_rules! lua_fn_opt {
@@ -67,6 +69,8 @@ macro_rules! lua_fn_opt {
     };
 }
 
+const TIMED_EVENTS_KEY: &str = "timed_events";
+
 pub trait LuaScripted {
     fn set_script_id(&mut self, id: u32);
     fn get_script_id(&self) -> u32;
@@ -82,6 +86,8 @@ struct ScriptedFunctions {
     on_spawn: Option<RegistryKey>,
     on_update: Option<RegistryKey>,
     on_death: Option<RegistryKey>,
+    on_phase_change: Option<RegistryKey>,
+    on_step: Option<RegistryKey>,
 }
 
 /// Manages one Lua VM and a cache of loaded scripts → functions.
@@ -94,11 +100,16 @@ pub struct LuaInterface {
     pub get_monster_by_id_callback: Option<Rc<dyn Fn(u32) -> Option<MonsterRc> + 'static>>,
     pub get_monster_kind_by_id_callback: Option<Rc<dyn Fn(u32) -> Option<MonsterKind>>>,
     pub get_current_map_callback: Option<Rc<dyn Fn() -> MapRc>>,
-    pub map_add_monster_callback: Option<Rc<dyn Fn(MapRc, u32, Position) -> MonsterRc>>,
+    pub map_add_monster_callback:
+        Option<Rc<dyn Fn(MapRc, u32, Position, Option<u32>) -> Option<MonsterRc>>>,
+    pub schedule_event_callback: Option<Rc<dyn Fn(u32) -> u32>>,
     pub script_id_counter: u32,
 }
 
 pub type LuaInterfaceRc = Rc<RefCell<LuaInterface>>;
+/// Registry key of a monster's script state, filled in the first time a script asks
+/// for it and shared by every copy of the monster handed to Lua.
+pub type MonsterStateSlot = Arc<OnceLock<RegistryKey>>;
 
 impl LuaInterface {
     /// Create a fresh Lua VM.
@@ -113,6 +124,7 @@ impl LuaInterface {
             get_monster_kind_by_id_callback: None,
             get_current_map_callback: None,
             map_add_monster_callback: None,
+            schedule_event_callback: None,
             script_id_counter: 1,
         }));
 
@@ -164,6 +176,18 @@ impl LuaInterface {
                 Ok(())
             }
         })?;
+        lua_if.add_lua_fn("schedule_event", {
+            let cb_opt = lua_if.schedule_event_callback.clone();
+            move |lua, (delay, func): (u32, Function)| {
+                let cb = cb_opt
+                    .as_ref()
+                    .ok_or_else(|| Error::external("No schedule_event_callback set!"))?;
+
+                let event_id = cb(delay);
+                LuaInterface::get_registry_table(lua, TIMED_EVENTS_KEY)?.set(event_id, func)?;
+                Ok(event_id)
+            }
+        })?;
         lua_fn_opt!(lua_if, "get_player", get_player_callback, direct, ());
         lua_fn_opt!(lua_if, "get_monster_by_id", get_monster_by_id_callback,( id: u32 ));
         lua_fn_opt!(lua_if, "get_monster_kind_by_id", get_monster_kind_by_id_callback,( id: u32 ));
@@ -178,6 +202,41 @@ impl LuaInterface {
         Ok(())
     }
 
+    fn get_registry_table<'lua>(lua: &'lua Lua, key: &str) -> Result<Table<'lua>> {
+        match lua.named_registry_value::<Option<Table>>(key)? {
+            Some(table) => Ok(table),
+            None => {
+                let table = lua.create_table()?;
+                lua.set_named_registry_value(key, table.clone())?;
+                Ok(table)
+            }
+        }
+    }
+
+    /// Returns the state table owned by a monster instance, creating it on first use.
+    /// The monster holds the only key to it, so the table goes away with the monster
+    /// however it leaves the game.
+    pub fn get_monster_state<'lua>(lua: &'lua Lua, slot: &MonsterStateSlot) -> Result<Table<'lua>> {
+        if let Some(key) = slot.get() {
+            return lua.registry_value(key);
+        }
+
+        let state = lua.create_table()?;
+        let key = lua.create_registry_value(state.clone())?;
+        let _ = slot.set(key);
+        Ok(state)
+    }
+
+    /// Fires a function queued through `schedule_event` once the scheduler reaches it.
+    pub fn run_timed_event(&self, event_id: u32) -> Result<()> {
+        let events = LuaInterface::get_registry_table(&self.lua, TIMED_EVENTS_KEY)?;
+        let Some(func) = events.get::<_, Option<Function>>(event_id)? else {
+            return Ok(());
+        };
+        events.set(event_id, Value::Nil)?;
+        func.call(())
+    }
+
     pub fn add_position<'lua>(lua: &'lua Lua, pos: &Position) -> mlua::Result<Table<'lua>> {
         let lua_pos = lua.create_table()?;
         lua_pos.set("x", pos.x)?;
@@ -217,6 +276,8 @@ impl LuaInterface {
             on_spawn: None,
             on_update: None,
             on_death: None,
+            on_phase_change: None,
+            on_step: None,
         };
 
         self.script_cache.insert(0, holder);
@@ -257,6 +318,8 @@ impl LuaInterface {
             on_spawn: None,
             on_update: None,
             on_death: None,
+            on_phase_change: None,
+            on_step: None,
         };
 
         for name in entity.functions() {
@@ -272,6 +335,8 @@ impl LuaInterface {
                 "on_spawn" => holder.on_spawn = Some(key),
                 "on_update" => holder.on_update = Some(key),
                 "on_death" => holder.on_death = Some(key),
+                "on_phase_change" => holder.on_phase_change = Some(key),
+                "on_step" => holder.on_step = Some(key),
                 _ => {} // ignore anything else
             }
         }
@@ -340,7 +405,13 @@ impl LuaInterface {
         }
     }
 
-    pub fn on_update(&self, monster_ref: &mut MonsterRc, update_iteration: u32) -> Result<bool> {
+    /// Runs the monster's `on_update`. Returns how long the action took when the
+    /// script handled it: `true` stands for a regular action, a number for a custom cost.
+    pub fn on_update(
+        &self,
+        monster_ref: &mut MonsterRc,
+        update_iteration: u32,
+    ) -> Result<Option<u32>> {
         let monster = monster_ref.borrow_mut();
         let binding = &self.script_cache;
         let funcs = binding.get(&monster.kind.get_script_id()).ok_or_else(|| {
@@ -357,12 +428,15 @@ impl LuaInterface {
             let lua_monster_ud = self.lua.create_userdata(monster_ref.clone())?;
 
             drop(monster);
-            // Invoke and return result
-            let result = func.call((lua_monster_ud, update_iteration));
-
-            result
+            let result: Value = func.call((lua_monster_ud, update_iteration))?;
+            match result {
+                Value::Boolean(true) => Ok(Some(ACTION_COST_NORMAL)),
+                Value::Integer(cost) => Ok(Some(cost.max(0) as u32)),
+                Value::Number(cost) => Ok(Some(cost.max(0.0) as u32)),
+                _ => Ok(None),
+            }
         } else {
-            Ok(false)
+            Ok(None)
         }
     }
 
@@ -391,6 +465,51 @@ impl LuaInterface {
         }
     }
 
+    pub fn on_phase_change(&self, monster_ref: &mut MonsterRc, phase: u32) -> Result<bool> {
+        let monster = monster_ref.borrow_mut();
+        let binding = &self.script_cache;
+        let funcs = binding.get(&monster.kind.get_script_id()).ok_or_else(|| {
+            Error::external(format!(
+                "No Lua script loaded for monster type `{}`",
+                monster.kind.get_script_id()
+            ))
+        })?;
+
+        // Retrieve the Function from the registry
+        if let Some(func_key) = &funcs.on_phase_change {
+            let func: Function = self.lua.registry_value(func_key)?;
+
+            let lua_monster_ud = self.lua.create_userdata(monster_ref.clone())?;
+
+            drop(monster);
+            // Invoke and return result
+            func.call((lua_monster_ud, phase))
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Runs a scripted trap. Returns the damage the script wants dealt to `creature`.
+    pub fn on_step<T: UserData + 'static>(
+        &self,
+        script_id: u32,
+        creature: T,
+        tile: &Position,
+    ) -> Result<Option<i32>> {
+        let funcs = self.script_cache.get(&script_id).ok_or_else(|| {
+            Error::external(format!("No Lua script loaded for trap `{}`", script_id))
+        })?;
+
+        if let Some(func_key) = &funcs.on_step {
+            let func: Function = self.lua.registry_value(func_key)?;
+            let lua_creature_ud = self.lua.create_userdata(creature)?;
+            let lua_tile = LuaInterface::add_position(&self.lua, tile)?;
+            func.call((lua_creature_ud, lua_tile))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn setup_lua_map_methods(&self, lua_map_ud: AnyUserData) -> mlua::Result<()> {
         let map_add_monster_callback = self.map_add_monster_callback.clone();
         let mt = lua_map_ud.get_metatable()?; // mt: UserDataMetatable
@@ -399,10 +518,22 @@ impl LuaInterface {
         methods_tbl.set(
             "add_monster",
             self.lua.create_function(
-                move |lua_ctx, (lua_self, kind_id, pos): (AnyUserData, u32, Table)| {
+                move |lua_ctx,
+                      (lua_self, kind_id, pos, spawner): (
+                    AnyUserData,
+                    u32,
+                    Table,
+                    Option<AnyUserData>,
+                )| {
                     // pull the MapRef back out of the userdata:
                     let map_ref: MapRc = lua_self.borrow::<MapRc>()?.clone();
 
+                    // monsters added on behalf of a spawner count towards its cap
+                    let spawner_id = match spawner {
+                        Some(ud) => Some(ud.borrow::<MonsterRc>()?.borrow().id),
+                        None => None,
+                    };
+
                     // build the Position
                     let p = Position {
                         x: pos.get("x")?,
@@ -411,10 +542,11 @@ impl LuaInterface {
 
                     // call your Rust callback
                     if let Some(cb) = &map_add_monster_callback {
-                        let monster_rc = cb(map_ref, kind_id, p);
-                        // return the new monster userdata back into Lua
-                        let ud = lua_ctx.create_userdata(monster_rc)?;
-                        Ok(ud)
+                        // return the new monster userdata back into Lua, or nil when capped
+                        match cb(map_ref, kind_id, p, spawner_id) {
+                            Some(monster_rc) => Ok(Some(lua_ctx.create_userdata(monster_rc)?)),
+                            None => Ok(None),
+                        }
                     } else {
                         Err(mlua::Error::external("No map_add_monster_callback set!"))
                     }
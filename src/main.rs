@@ -20,6 +20,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod boss;
 mod combat;
 mod creature;
 mod game;
@@ -29,14 +30,19 @@ mod items;
 mod lua_interface;
 mod maps;
 mod monster;
+mod monster_affix;
 mod monster_kind;
 mod player;
 mod player_spell;
 mod position;
+mod scheduler;
 mod spell_execution;
 mod spell_type;
+mod status_effect;
 mod tile;
+mod tile_def;
 mod tile_map;
+mod trap;
 mod ui;
 
 use macroquad::prelude::*;
@@ -51,9 +57,33 @@ fn window_conf() -> Conf {
     }
 }
 
+// Asset loading goes through macroquad's context and monster and spell
+// sprites need a GL context, so exporting still needs a window. Miniquad
+// cannot open a hidden one, so keep it as small as possible instead.
+fn export_window_conf() -> Conf {
+    Conf {
+        window_title: "Rust Rogue - exporting maps".to_string(),
+        window_width: 320,
+        window_height: 80,
+        window_resizable: false,
+        ..Default::default()
+    }
+}
+
 //use crate::ui::{Ui};
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    game::run().await;
+// Arguments are parsed before macroquad starts so that bad export options
+// fail without opening a window at all.
+fn main() {
+    match maps::map_export::ExportOptions::from_args(std::env::args()) {
+        Ok(Some(options)) => macroquad::Window::from_config(
+            export_window_conf(),
+            maps::map_export::run_export(options),
+        ),
+        Ok(None) => macroquad::Window::from_config(window_conf(), game::run()),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 }
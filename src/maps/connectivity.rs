@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::maps::map_algorithms::LayoutCanvas;
+use crate::maps::navigator::Navigator;
+use crate::position::Position;
+
+/// Floor regions of a map and the required positions none of them reach.
+///
+/// Regions use the same 8-way movement as the player and monsters, and are
+/// sorted largest first, so `regions[0]` is the main region.
+#[derive(Debug, Clone)]
+pub struct ReachabilityReport {
+    pub regions: Vec<Vec<Position>>,
+    pub unreachable_targets: Vec<Position>,
+}
+
+impl ReachabilityReport {
+    /// Flood fills every floor tile and checks that all `targets` (entrances,
+    /// stairs, chests...) end up in the main region.
+    pub fn analyze(
+        width: usize,
+        height: usize,
+        is_floor: impl Fn(Position) -> bool,
+        targets: &[Position],
+    ) -> Self {
+        let mut region_ids = vec![vec![None; height]; width];
+        let mut regions: Vec<Vec<Position>> = Vec::new();
+
+        for x in 0..width {
+            for y in 0..height {
+                let start = Position::new(x, y);
+                if region_ids[x][y].is_some() || !is_floor(start) {
+                    continue;
+                }
+
+                let id = regions.len();
+                let mut region = Vec::new();
+                let mut queue = VecDeque::from([start]);
+                region_ids[x][y] = Some(id);
+
+                while let Some(pos) = queue.pop_front() {
+                    region.push(pos);
+                    for neighbor in neighbors(pos, width, height) {
+                        if region_ids[neighbor.x][neighbor.y].is_none() && is_floor(neighbor) {
+                            region_ids[neighbor.x][neighbor.y] = Some(id);
+                            queue.push_back(neighbor);
+                        }
+                    }
+                }
+                regions.push(region);
+            }
+        }
+
+        let main_id = (0..regions.len()).max_by_key(|&id| (regions[id].len(), usize::MAX - id));
+        let unreachable_targets = targets
+            .iter()
+            .copied()
+            .filter(|pos| {
+                pos.x >= width
+                    || pos.y >= height
+                    || main_id.is_none()
+                    || region_ids[pos.x][pos.y] != main_id
+            })
+            .collect();
+
+        regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+
+        Self {
+            regions,
+            unreachable_targets,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.regions.len() <= 1 && self.unreachable_targets.is_empty()
+    }
+
+    pub fn main_region(&self) -> &[Position] {
+        self.regions
+            .first()
+            .map(|region| region.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+fn neighbors(pos: Position, width: usize, height: usize) -> impl Iterator<Item = Position> {
+    (-1isize..=1)
+        .flat_map(|dx| (-1isize..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .filter_map(move |(dx, dy)| {
+            let x = pos.x.checked_add_signed(dx)?;
+            let y = pos.y.checked_add_signed(dy)?;
+            (x < width && y < height).then(|| Position::new(x, y))
+        })
+}
+
+/// Carves every target, then joins each stray region to the main one with a
+/// corridor between their closest tiles. Corridors stay off the outer ring so
+/// they never open border entrances the neighbouring maps do not know about.
+/// Returns the report after the repair.
+pub fn repair(canvas: &mut LayoutCanvas, targets: &[Position]) -> ReachabilityReport {
+    for &target in targets {
+        canvas.carve(target);
+    }
+
+    let report = ReachabilityReport::analyze(
+        canvas.width(),
+        canvas.height(),
+        |pos| canvas.is_floor(pos),
+        targets,
+    );
+    let mut connected: HashSet<Position> = report.main_region().iter().copied().collect();
+
+    for region in report.regions.iter().skip(1) {
+        let closest = region
+            .iter()
+            .filter(|&&pos| canvas.is_inner(pos))
+            .flat_map(|&from| {
+                connected
+                    .iter()
+                    .filter(|&&pos| canvas.is_inner(pos))
+                    .map(move |&to| (from, to))
+            })
+            .min_by_key(|(from, to)| from.x.abs_diff(to.x) + from.y.abs_diff(to.y));
+        if let Some((from, to)) = closest {
+            let path = Navigator::find_path(from, to, |pos| canvas.is_inner(pos));
+            for pos in path.unwrap_or_default() {
+                canvas.carve(pos);
+            }
+        }
+        connected.extend(region.iter().copied());
+    }
+
+    ReachabilityReport::analyze(
+        canvas.width(),
+        canvas.height(),
+        |pos| canvas.is_floor(pos),
+        targets,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{Tile, TileKind};
+    use crate::tile_def::{TILE_DEFS, TileDefs};
+
+    // Two rooms split by a wall, the right one larger
+    const SPLIT: &[&str] = &[
+        "#########",
+        "#..#....#",
+        "#..#....#",
+        "#..#....#",
+        "#########",
+    ];
+
+    // The same rooms with a gap in the wall
+    const CONNECTED: &[&str] = &[
+        "#########",
+        "#..#....#",
+        "#.......#",
+        "#..#....#",
+        "#########",
+    ];
+
+    fn floor_grid(rows: &[&str]) -> Vec<Vec<bool>> {
+        let width = rows[0].len();
+        (0..width)
+            .map(|x| rows.iter().map(|row| row.as_bytes()[x] == b'.').collect())
+            .collect()
+    }
+
+    // Two regions whose closest tiles are entrances on the top border
+    const BORDER_SPLIT: &[&str] = &[
+        "##.##.###",
+        "#.####.##",
+        "#.####.##",
+        "#.####.##",
+        "#########",
+    ];
+
+    fn layout_tiles(rows: &[&str]) -> (Vec<Vec<Tile>>, Vec<Position>) {
+        TILE_DEFS.get_or_init(|| {
+            TileDefs::from_json(&std::fs::read_to_string("assets/tiles/tiles.json").unwrap())
+        });
+
+        let mut walkable = Vec::new();
+        let tiles = floor_grid(rows)
+            .iter()
+            .enumerate()
+            .map(|(x, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .map(|(y, &floor)| {
+                        if floor {
+                            walkable.push(Position::new(x, y));
+                            Tile::new(TileKind::FLOOR)
+                        } else {
+                            Tile::new(TileKind::WALL)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        (tiles, walkable)
+    }
+
+    fn analyze(rows: &[&str], targets: &[Position]) -> ReachabilityReport {
+        let grid = floor_grid(rows);
+        ReachabilityReport::analyze(grid.len(), grid[0].len(), |pos| grid[pos.x][pos.y], targets)
+    }
+
+    #[test]
+    fn split_layout_is_not_connected() {
+        let left = Position::new(1, 1);
+        let right = Position::new(5, 2);
+        let report = analyze(SPLIT, &[left, right]);
+
+        assert!(!report.is_connected());
+        assert_eq!(report.regions.len(), 2);
+        assert_eq!(report.main_region().len(), 12);
+        assert!(report.main_region().contains(&right));
+        assert_eq!(report.unreachable_targets, vec![left]);
+    }
+
+    #[test]
+    fn repair_joins_regions() {
+        let (mut tiles, mut walkable) = layout_tiles(SPLIT);
+        let mut canvas = LayoutCanvas::new(&mut tiles, &mut walkable, TileKind::WALL);
+
+        let targets = [Position::new(1, 1), Position::new(7, 3)];
+        let report = repair(&mut canvas, &targets);
+
+        assert!(report.is_connected());
+        assert_eq!(report.regions.len(), 1);
+        assert!(report.main_region().contains(&Position::new(1, 1)));
+        assert!(report.main_region().contains(&Position::new(7, 3)));
+    }
+
+    #[test]
+    fn repair_keeps_off_the_border() {
+        let (mut tiles, mut walkable) = layout_tiles(BORDER_SPLIT);
+        let mut canvas = LayoutCanvas::new(&mut tiles, &mut walkable, TileKind::WALL);
+
+        let targets = [Position::new(2, 0), Position::new(5, 0)];
+        let report = repair(&mut canvas, &targets);
+
+        assert!(report.is_connected());
+        let top_row: Vec<usize> = (0..canvas.width())
+            .filter(|&x| canvas.is_floor(Position::new(x, 0)))
+            .collect();
+        assert_eq!(top_row, vec![2, 5]);
+    }
+
+    #[test]
+    fn connected_layout_reaches_every_target() {
+        let targets = [
+            Position::new(1, 1),
+            Position::new(2, 3),
+            Position::new(4, 1),
+            Position::new(7, 3),
+        ];
+        let report = analyze(CONNECTED, &targets);
+
+        assert!(report.is_connected());
+        assert!(report.unreachable_targets.is_empty());
+        assert!(
+            targets
+                .iter()
+                .all(|target| report.main_region().contains(target))
+        );
+    }
+}
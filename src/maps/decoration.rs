@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashSet;
+
+use rand::Rng;
+use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+
+use crate::maps::map_algorithms::LayoutCanvas;
+use crate::maps::prefab::PrefabFootprint;
+use crate::position::Position;
+use crate::tile::TileKind;
+use crate::tile_def::get_tile_defs;
+
+// Lava only shows up once the player has had a few floors to learn the ropes
+const LAVA_MIN_TIER: u32 = 2;
+const LAVA_CHANCE: f64 = 0.4;
+const PILLAR_CHANCE: f64 = 0.05;
+const RUBBLE_CHANCE: f64 = 0.02;
+
+/// Sprinkles terrain over the plain floor of a carved layout: patches of grass
+/// and water, the odd lava pool, rubble and free standing pillars.
+///
+/// `reserved` tiles (entrances and placed content) and prefab footprints keep
+/// their floor. Pillars only go where all eight neighbours are walkable, so
+/// they never split the layout.
+pub fn decorate(
+    canvas: &mut LayoutCanvas,
+    reserved: &[Position],
+    footprints: &[PrefabFootprint],
+    tier: u32,
+    rng: &mut ThreadRng,
+) {
+    let defs = get_tile_defs();
+    let is_free = |canvas: &LayoutCanvas, pos: Position| {
+        canvas.is_inner(pos)
+            && canvas.kind(pos) == TileKind::FLOOR
+            && !reserved.contains(&pos)
+            && !footprints.iter().any(|footprint| footprint.contains(pos))
+    };
+
+    let mut floor: Vec<Position> = canvas
+        .walkable()
+        .iter()
+        .copied()
+        .filter(|&pos| is_free(canvas, pos))
+        .collect();
+    if floor.is_empty() {
+        return;
+    }
+    floor.shuffle(rng);
+    let area = floor.len();
+
+    let mut patches = Vec::new();
+    if let Some(grass) = defs.by_name("Grass") {
+        patches.extend((0..rng.gen_range(1..=3)).map(|_| (grass, area / 20)));
+    }
+    if let Some(water) = defs.by_name("Shallow Water") {
+        patches.extend((0..rng.gen_range(0..=2)).map(|_| (water, area / 30)));
+    }
+    if let Some(lava) = defs.by_name("Lava")
+        && tier >= LAVA_MIN_TIER
+        && rng.gen_bool(LAVA_CHANCE)
+    {
+        patches.push((lava, area / 60));
+    }
+
+    for (kind, size) in patches {
+        let start = floor[rng.gen_range(0..floor.len())];
+        let mut frontier = vec![start];
+        let mut grown = HashSet::new();
+        while grown.len() < size.max(1) && !frontier.is_empty() {
+            let pos = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            if !is_free(canvas, pos) || !grown.insert(pos) {
+                continue;
+            }
+            canvas.set_kind(pos, kind);
+            frontier.extend([(0, -1), (1, 0), (0, 1), (-1, 0)].iter().map(|&(dx, dy)| {
+                Position::new(
+                    pos.x.saturating_add_signed(dx),
+                    pos.y.saturating_add_signed(dy),
+                )
+            }));
+        }
+    }
+
+    let rubble = defs.by_name("Rubble");
+    let pillar = defs.by_name("Pillar");
+    for &pos in &floor {
+        if !is_free(canvas, pos) {
+            continue;
+        }
+        if let Some(pillar) = pillar {
+            let open_around = (-1isize..=1)
+                .flat_map(|dx| (-1isize..=1).map(move |dy| (dx, dy)))
+                .filter(|&offset| offset != (0, 0))
+                .all(|(dx, dy)| {
+                    canvas.is_floor(Position::new(
+                        pos.x.saturating_add_signed(dx),
+                        pos.y.saturating_add_signed(dy),
+                    ))
+                });
+            if open_around && rng.gen_bool(PILLAR_CHANCE) {
+                canvas.set_kind(pos, pillar);
+                continue;
+            }
+        }
+        if let Some(rubble) = rubble
+            && rng.gen_bool(RUBBLE_CHANCE)
+        {
+            canvas.set_kind(pos, rubble);
+        }
+    }
+}
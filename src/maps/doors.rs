@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+use crate::maps::connectivity::ReachabilityReport;
+use crate::maps::map_algorithms::{LayoutCanvas, LayoutPlacement};
+use crate::maps::prefab::PrefabFootprint;
+use crate::position::Position;
+use crate::tile::{DoorState, TileKind};
+use crate::tile_def::get_tile_defs;
+
+const DOOR_CHANCE: f64 = 0.6;
+const LOCKED_CHANCE: f64 = 0.2;
+// A tile with this many walkable neighbours is part of a room rather than a corridor
+const ROOM_OPENNESS: usize = 5;
+
+/// Hangs doors in the doorways where a corridor meets a room, and locks a few
+/// of them with a key left on the entrances' side.
+///
+/// A door is only locked when every entrance in `anchors` can still reach the
+/// others with it shut, so a key is never stuck behind its own door.
+pub fn place_doors(
+    canvas: &mut LayoutCanvas,
+    anchors: &[Position],
+    reserved: &[Position],
+    footprints: &[PrefabFootprint],
+    rng: &mut ThreadRng,
+) {
+    let Some(door_kind) = get_tile_defs()
+        .iter()
+        .find(|def| def.door)
+        .map(|def| TileKind(def.id))
+    else {
+        return;
+    };
+
+    let mut doors: Vec<Position> = Vec::new();
+    for x in 1..canvas.width() - 1 {
+        for y in 1..canvas.height() - 1 {
+            let pos = Position::new(x, y);
+            if canvas.kind(pos) != TileKind::FLOOR
+                || reserved.contains(&pos)
+                || footprints.iter().any(|footprint| footprint.contains(pos))
+                || doors.iter().any(|door| door.is_neighbor(&pos))
+                || !is_doorway(canvas, pos)
+                || !rng.gen_bool(DOOR_CHANCE)
+            {
+                continue;
+            }
+            canvas.set_kind(pos, door_kind);
+            doors.push(pos);
+        }
+    }
+
+    let mut locked: Vec<Position> = Vec::new();
+    for &door in &doors {
+        if !rng.gen_bool(LOCKED_CHANCE) {
+            continue;
+        }
+        let report = ReachabilityReport::analyze(
+            canvas.width(),
+            canvas.height(),
+            |pos| canvas.is_floor(pos) && pos != door && !locked.contains(&pos),
+            anchors,
+        );
+        if !report.unreachable_targets.is_empty() {
+            continue;
+        }
+        let spots: Vec<Position> = report
+            .main_region()
+            .iter()
+            .copied()
+            .filter(|&pos| {
+                canvas.kind(pos) == TileKind::FLOOR
+                    && !reserved.contains(&pos)
+                    && !footprints.iter().any(|footprint| footprint.contains(pos))
+            })
+            .collect();
+        if spots.is_empty() {
+            continue;
+        }
+
+        canvas.set_door(door, DoorState::Locked);
+        canvas.place(LayoutPlacement::Key(spots[rng.gen_range(0..spots.len())]));
+        locked.push(door);
+    }
+
+    println!(
+        "[MapGenerator] Placed {} doors, {} locked",
+        doors.len(),
+        locked.len()
+    );
+}
+
+/// A floor tile squeezed between two solid tiles, opening into a room on at
+/// least one side.
+fn is_doorway(canvas: &LayoutCanvas, pos: Position) -> bool {
+    let (x, y) = (pos.x, pos.y);
+    let north = Position::new(x, y - 1);
+    let south = Position::new(x, y + 1);
+    let west = Position::new(x - 1, y);
+    let east = Position::new(x + 1, y);
+
+    let sides = if !canvas.is_floor(west) && !canvas.is_floor(east) {
+        [north, south]
+    } else if !canvas.is_floor(north) && !canvas.is_floor(south) {
+        [west, east]
+    } else {
+        return false;
+    };
+    if !sides.iter().all(|&side| canvas.is_floor(side)) {
+        return false;
+    }
+
+    sides
+        .iter()
+        .any(|&side| openness(canvas, side) >= ROOM_OPENNESS)
+}
+
+fn openness(canvas: &LayoutCanvas, pos: Position) -> usize {
+    (-1isize..=1)
+        .flat_map(|dx| (-1isize..=1).map(move |dy| (dx, dy)))
+        .filter(|&offset| offset != (0, 0))
+        .filter(|&(dx, dy)| {
+            canvas.is_floor(Position::new(
+                pos.x.saturating_add_signed(dx),
+                pos.y.saturating_add_signed(dy),
+            ))
+        })
+        .count()
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+
+use crate::items::base_item::ItemKind;
+use crate::position::Position;
+
+/// What the player has seen of a map. It lives on the map, so it is still
+/// there when the player comes back through the overworld.
+#[derive(Clone, Debug)]
+pub struct ExploredMemory {
+    width: usize,
+    explored: Vec<bool>,
+    // Items as they were when each tile was last in view
+    items: HashMap<Position, Vec<ItemKind>>,
+}
+
+impl ExploredMemory {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            explored: vec![false; width * height],
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn is_explored(&self, pos: Position) -> bool {
+        self.explored
+            .get(pos.y * self.width + pos.x)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Marks `pos` as explored and remembers what was lying there.
+    pub fn remember(&mut self, pos: Position, items: &[ItemKind]) {
+        if let Some(explored) = self.explored.get_mut(pos.y * self.width + pos.x) {
+            *explored = true;
+        }
+        if items.is_empty() {
+            self.items.remove(&pos);
+        } else {
+            self.items.insert(pos, items.to_vec());
+        }
+    }
+
+    pub fn items(&self, pos: Position) -> &[ItemKind] {
+        self.items.get(&pos).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
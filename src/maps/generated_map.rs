@@ -20,31 +20,44 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 use rand::{seq::SliceRandom, thread_rng};
 
 use crate::{
+    boss::BossEncounter,
+    items::{base_item::ItemKind, collection::Items, container::Container},
     lua_interface::LuaInterfaceRc,
-    maps::overworld::VisitedState,
-    monster::{Monster, MonsterArc},
+    maps::connectivity::ReachabilityReport,
+    maps::map_algorithms::LayoutPlacement,
+    maps::{DEFAULT_POPULATION_CAP, overworld::VisitedState},
+    monster::Monster,
     monster_kind::MonsterKind,
     position::Position,
-    tile::Tile,
+    tile::{NO_CREATURE, Tile},
     tile_map::TileMap,
+    trap::{Trap, TrapEffect, TrapKind, get_trap_kinds},
 };
 
+const MAX_TRAPS: usize = 5;
+
 #[derive(Clone, Debug)]
 pub struct GeneratedMap {
     pub tier: u32,
     pub tiles: TileMap,
     pub walkable_cache: Vec<Position>,
     pub available_walkable_cache: Vec<Position>,
-    pub monsters: Vec<MonsterArc>,
+    // Moved into the game's `Map` when it takes the map over
+    pub monsters: Vec<Monster>,
     pub border_positions: [Vec<Position>; 4],
     pub downstair_teleport: Option<Position>,
+    pub upstair_teleport: Option<Position>,
     pub visited_state: VisitedState,
     pub monster_kinds: Vec<u32>,
+    pub boss: Option<BossEncounter>,
+    pub population_cap: usize,
+    // Light reaching every tile before any light source, see `lighting`
+    pub ambient_light: f32,
 }
 
 impl GeneratedMap {
@@ -67,20 +80,116 @@ impl GeneratedMap {
                 Vec::new(), // Left border
             ],
             downstair_teleport: None,
+            upstair_teleport: None,
             visited_state: VisitedState::Unvisited,
             monster_kinds: Vec::new(),
+            boss: None,
+            population_cap: DEFAULT_POPULATION_CAP,
+            ambient_light: 1.0,
         }
     }
 
-    pub(crate) fn add_random_monsters(
+    pub fn width(&self) -> usize {
+        self.tiles.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.height()
+    }
+
+    /// Checks that the entrances, stairs and chests all share one floor region.
+    pub fn reachability_report(&self) -> ReachabilityReport {
+        let mut targets: Vec<Position> = self.border_positions.iter().flatten().copied().collect();
+        targets.extend(self.downstair_teleport);
+        targets.extend(self.boss.as_ref().map(|boss| boss.stairs_pos));
+        targets.extend(
+            self.walkable_cache
+                .iter()
+                .copied()
+                .filter(|&pos| self.tiles.has_container(pos)),
+        );
+
+        ReachabilityReport::analyze(
+            self.width(),
+            self.height(),
+            |pos| self.tiles[pos].def().walkable,
+            &targets,
+        )
+    }
+
+    /// Places the content requested by the map layout and its prefabs.
+    pub(crate) fn add_layout_placements(
         &mut self,
+        placements: &[LayoutPlacement],
         monster_kinds: &Vec<Arc<MonsterKind>>,
         monster_kinds_by_tier: &Vec<Vec<u32>>,
+        items: &Items,
         tier: u32,
+        has_downstairs: bool,
     ) {
         let mut rng = thread_rng();
+        let tier_pool = Self::monster_kinds_for_tier(monster_kinds, monster_kinds_by_tier, tier);
+
+        for placement in placements {
+            match placement {
+                LayoutPlacement::Monster(_, pos) | LayoutPlacement::RandomMonster(pos) => {
+                    let kind = match placement {
+                        LayoutPlacement::Monster(kind_id, _) => {
+                            monster_kinds.iter().find(|mt| mt.id == *kind_id)
+                        }
+                        _ => tier_pool.choose(&mut rng).copied(),
+                    };
+                    let Some(kind) = kind else {
+                        eprintln!(
+                            "No monster kind available for layout placement {:?}",
+                            placement
+                        );
+                        continue;
+                    };
+                    if self.tiles.creature(*pos) != NO_CREATURE || !self.claim_position(*pos) {
+                        continue;
+                    }
+
+                    let mut monster = Monster::new(*pos, kind.clone());
+                    monster.roll_rarity(tier, items, &mut rng);
+                    self.tiles.set_creature(*pos, monster.id);
+                    self.monsters.push(monster);
+                }
+                LayoutPlacement::Orb(pos) => {
+                    if self.claim_position(*pos) {
+                        self.tiles.add_orb(*pos);
+                    }
+                }
+                LayoutPlacement::Chest(pos) => {
+                    if self.claim_position(*pos) {
+                        let container = random_tier_chest(items, tier);
+                        self.tiles.add_item(*pos, ItemKind::Container(container));
+                    }
+                }
+                LayoutPlacement::Key(pos) => {
+                    if self.claim_position(*pos) {
+                        self.tiles.add_key(*pos);
+                    }
+                }
+                LayoutPlacement::Teleport(pos) => {
+                    if has_downstairs
+                        && self.downstair_teleport.is_none()
+                        && self.claim_position(*pos)
+                    {
+                        self.tiles.add_teleport(*pos);
+                        self.downstair_teleport = Some(*pos);
+                    }
+                }
+            }
+        }
+    }
 
-        let mut monster_kinds_in_this_tier = if tier as usize >= monster_kinds_by_tier.len() {
+    fn monster_kinds_for_tier<'a>(
+        monster_kinds: &'a Vec<Arc<MonsterKind>>,
+        monster_kinds_by_tier: &Vec<Vec<u32>>,
+        tier: u32,
+    ) -> Vec<&'a Arc<MonsterKind>> {
+        if tier as usize >= monster_kinds_by_tier.len() {
             monster_kinds
                 .iter()
                 .filter(|mt| mt.tier == tier)
@@ -90,7 +199,29 @@ impl GeneratedMap {
                 .iter()
                 .filter_map(|&id| monster_kinds.iter().find(|mt| mt.id == id))
                 .collect::<Vec<_>>()
-        };
+        }
+    }
+
+    /// Takes a walkable position out of the pool used for random content.
+    fn claim_position(&mut self, pos: Position) -> bool {
+        if !self.walkable_cache.contains(&pos) {
+            return false;
+        }
+        self.available_walkable_cache.retain(|&p| p != pos);
+        true
+    }
+
+    pub(crate) fn add_random_monsters(
+        &mut self,
+        monster_kinds: &Vec<Arc<MonsterKind>>,
+        monster_kinds_by_tier: &Vec<Vec<u32>>,
+        items: &Items,
+        tier: u32,
+    ) {
+        let mut rng = thread_rng();
+
+        let mut monster_kinds_in_this_tier =
+            Self::monster_kinds_for_tier(monster_kinds, monster_kinds_by_tier, tier);
 
         monster_kinds_in_this_tier.shuffle(&mut rng);
         monster_kinds_in_this_tier.truncate(2);
@@ -112,13 +243,10 @@ impl GeneratedMap {
                 .expect("No monster types available"))
             .clone();
 
-            let monster = Arc::new(RwLock::new(Monster::new(pos.clone(), kind.clone())));
-            if let Ok(monster_guard) = monster.read() {
-                self.tiles[pos].creature = monster_guard.id;
-            } else {
-                println!("Failed to read monster data due to poisoning.");
-            }
-            // Wrap the monster in Rc and push to creatures
+            let mut monster = Monster::new(pos.clone(), kind.clone());
+            monster.roll_rarity(tier, items, &mut rng);
+
+            self.tiles.set_creature(pos, monster.id);
             self.monsters.push(monster);
         }
 
@@ -129,4 +257,63 @@ impl GeneratedMap {
 
         //setup_spawners_table
     }
+
+    /// Hides a few traps of the tier around the map. Pits need a floor below to drop into.
+    pub(crate) fn add_random_traps(&mut self, tier: u32, has_downstairs: bool) {
+        let mut rng = thread_rng();
+        let kinds: Vec<&TrapKind> = get_trap_kinds()
+            .iter()
+            .filter(|kind| kind.min_tier <= tier)
+            .filter(|kind| has_downstairs || !matches!(kind.effect, TrapEffect::Pit))
+            .collect();
+        if kinds.is_empty() {
+            return;
+        }
+
+        let count = (tier as usize + 1).min(MAX_TRAPS);
+        let len = self.available_walkable_cache.len();
+        let positions: Vec<Position> = self
+            .available_walkable_cache
+            .drain(len.saturating_sub(count)..)
+            .collect();
+
+        for pos in positions {
+            let Ok(kind) = kinds.choose_weighted(&mut rng, |kind| kind.weight) else {
+                return;
+            };
+            self.tiles[pos].trap = Some(Trap {
+                kind: kind.id,
+                hidden: true,
+            });
+        }
+    }
+}
+
+/// A chest holding up to three random items of the given tier.
+pub(crate) fn random_tier_chest(items: &Items, tier: u32) -> Container {
+    let mut container = Container::new();
+
+    // get 3 random items from items of the specified tier
+    let mut rng = thread_rng();
+    let items_of_tier: Vec<u32> = items
+        .items_ids_by_tier
+        .get(tier as usize)
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    let selected_items = items_of_tier
+        .choose_multiple(&mut rng, 3)
+        .cloned()
+        .collect::<Vec<u32>>();
+
+    // Add them to the container, ensuring they are unique (0 to 3, depending on availability)
+    for item_id in selected_items {
+        if items.items_by_id.contains_key(&item_id) {
+            container.add_item(item_id);
+        }
+    }
+
+    container
 }
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::maps::MapTheme;
+use crate::maps::navigator::{FovMode, Navigator};
+use crate::position::Position;
+use crate::tile_map::TileMap;
+
+/// Light level needed to make out a tile or whoever stands on it.
+pub const LIT_THRESHOLD: f32 = 0.3;
+/// How much ambient light is lost with each floor going down.
+const DARKENING_PER_FLOOR: f32 = 0.25;
+
+/// Light that reaches every tile of a map on `floor`, before any light source.
+pub fn ambient_light(floor: usize, theme: &MapTheme) -> f32 {
+    let theme_bonus = match theme {
+        // Arenas are kept lit for whoever watches the fights
+        MapTheme::Arena => 0.3,
+        MapTheme::Wall => -0.1,
+        MapTheme::Chasm | MapTheme::Any => 0.0,
+    };
+    (1.0 - floor as f32 * DARKENING_PER_FLOOR + theme_bonus).clamp(0.0, 1.0)
+}
+
+/// Something that sheds light around itself.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSource {
+    pub pos: Position,
+    pub radius: u32,
+}
+
+/// Light level of every tile of a map, from 0 (pitch black) to 1 (fully lit).
+#[derive(Clone, Debug)]
+pub struct LightMap {
+    width: usize,
+    levels: Vec<f32>,
+}
+
+impl LightMap {
+    /// Starts from the ambient light and adds every source, each fading out towards
+    /// the edge of its radius and stopped by whatever blocks sight.
+    pub fn compute(tiles: &TileMap, ambient: f32, sources: &[LightSource]) -> Self {
+        let (width, height) = (tiles.width(), tiles.height());
+        let mut levels = vec![ambient; width * height];
+
+        for source in sources.iter().filter(|source| source.radius > 0) {
+            let lit = Navigator::compute_fov(
+                tiles,
+                source.pos,
+                source.radius as usize,
+                FovMode::Permissive,
+            );
+            let falloff = (source.radius + 1) as f32;
+            for pos in lit.iter() {
+                let distance = source.pos.euclidean_distance_squared(&pos).sqrt() as f32;
+                let level = &mut levels[pos.y * width + pos.x];
+                *level = (*level + (1.0 - distance / falloff).max(0.0)).min(1.0);
+            }
+        }
+
+        Self { width, levels }
+    }
+
+    pub fn level(&self, pos: Position) -> f32 {
+        self.levels
+            .get(pos.y * self.width + pos.x)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn is_lit(&self, pos: Position) -> bool {
+        self.level(pos) >= LIT_THRESHOLD
+    }
+}
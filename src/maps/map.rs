@@ -23,7 +23,7 @@
 use macroquad::prelude::*;
 extern crate rand as external_rand;
 
-use external_rand::thread_rng;
+use external_rand::{Rng, thread_rng};
 use mlua::{Table, UserData, UserDataMethods};
 
 use crate::creature::Creature;
@@ -32,21 +32,27 @@ use crate::graphics::graphics_manager::GraphicsManager;
 use crate::items::base_item::ItemKind;
 use crate::items::container::Container;
 use crate::lua_interface::LuaInterface;
+use crate::maps::TILE_SIZE;
+use crate::maps::explored::ExploredMemory;
 use crate::maps::generated_map::GeneratedMap;
+use crate::maps::lighting::{LightMap, LightSource};
+use crate::maps::navigator::{FovMode, Navigator};
 use crate::maps::overworld::VisitedState;
-use crate::maps::{GRID_HEIGHT, GRID_WIDTH, TILE_SIZE, navigator::Navigator};
-use crate::monster::MonsterArc;
+use crate::maps::visibility::VisibilityGrid;
 use crate::monster::{Monster, MonsterRc};
+use crate::monster_kind::Faction;
 use crate::player::Player;
 use crate::position::POSITION_INVALID;
 use crate::position::Position;
 use crate::spell_type::SpellStrategy;
-use crate::tile::{NO_CREATURE, PLAYER_CREATURE_ID};
+use crate::tile::{DoorState, NO_CREATURE, PLAYER_CREATURE_ID, Tile, TileKind};
+use crate::tile_def::get_tile_defs;
+use crate::tile_map::TileMap;
 use crate::ui::point_f::PointF;
 use external_rand::seq::SliceRandom;
 use std::cell::RefCell;
-use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
@@ -54,7 +60,9 @@ use std::sync::{Arc, RwLock};
 pub struct SpellFovCache {
     pub radius: u32,
     pub origin: Position,
-    pub area: HashSet<Position>,
+    pub area: VisibilityGrid,
+    // Terrain revision the area was computed against, `None` before the first time
+    revision: Option<u64>,
 }
 
 impl SpellFovCache {
@@ -62,9 +70,32 @@ impl SpellFovCache {
         Self {
             radius: 0,
             origin: POSITION_INVALID,
-            area: HashSet::new(),
+            area: VisibilityGrid::default(),
+            revision: None,
         }
     }
+
+    /// Recomputes the area only if its origin, its radius or the terrain changed.
+    pub fn update(&mut self, tiles: &TileMap, origin: Position, radius: u32) {
+        let revision = Some(tiles.revision());
+        if self.origin == origin && self.radius == radius && self.revision == revision {
+            return;
+        }
+        self.origin = origin;
+        self.radius = radius;
+        self.revision = revision;
+        self.area = Navigator::compute_fov(tiles, origin, radius as usize, FovMode::Symmetric);
+    }
+}
+
+/// The player's line of sight before lighting is applied, reused until the
+/// player moves or the terrain changes.
+#[derive(Clone, Debug)]
+struct SightCache {
+    origin: Position,
+    radius: usize,
+    revision: u64,
+    sight: VisibilityGrid,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -82,37 +113,36 @@ pub struct Map {
     pub hovered_tile_changed: bool,
     pub spell_or_attack_fov_cache: SpellFovCache,
     pub shown_fov: FovToShow,
+    pub explored: ExploredMemory,
+    pub light: LightMap,
+    sight_cache: Option<SightCache>,
 }
 
 impl Map {
-    pub fn new(generated_map: GeneratedMap) -> Self {
-        let mut m = Self {
+    pub fn new(mut generated_map: GeneratedMap) -> Self {
+        let explored = ExploredMemory::new(generated_map.width(), generated_map.height());
+        let light = LightMap::compute(&generated_map.tiles, generated_map.ambient_light, &[]);
+        let monsters = std::mem::take(&mut generated_map.monsters)
+            .into_iter()
+            .map(|monster| (monster.id, Rc::new(RefCell::new(monster))))
+            .collect();
+        Self {
             generated_map,
-            monsters: HashMap::new(),
+            monsters,
             hovered_tile: None,
             hovered_tile_changed: false,
             spell_or_attack_fov_cache: SpellFovCache::new(),
             shown_fov: FovToShow::None,
-        };
-
-        m.monsters = Self::convert_monsters(m.generated_map.monsters.clone());
-        m
-    }
-
-    fn convert_monsters(monsters: Vec<MonsterArc>) -> HashMap<u32, MonsterRc> {
-        monsters
-            .into_iter()
-            .map(|arc_mutex| {
-                let monster = arc_mutex.read().unwrap();
-                (monster.id, Rc::new(RefCell::new(monster.clone())))
-            })
-            .collect()
+            explored,
+            light,
+            sight_cache: None,
+        }
     }
 
     pub fn remove_creature<T: Creature>(&mut self, creature: &mut T) {
         let pos = creature.pos();
-        if pos.x < GRID_WIDTH && pos.y < GRID_HEIGHT {
-            self.generated_map.tiles[pos].creature = NO_CREATURE; // Remove creature from tile
+        if self.generated_map.tiles.in_bounds(pos) {
+            self.generated_map.tiles.set_creature(pos, NO_CREATURE); // Remove creature from tile
             creature.set_pos(POSITION_INVALID); // Set creature position to invalid
         } else {
             println!("Creature position out of bounds, cannot remove.");
@@ -122,18 +152,10 @@ impl Map {
     pub fn remove_downstairs_teleport(&mut self) {
         //self.generated_map.tiles[self.generated_map.downstair_teleport].remove;
         if let Some(teleport_pos) = self.generated_map.downstair_teleport {
-            let items_to_remove: Vec<_> = self.generated_map.tiles[teleport_pos]
-                .items
-                .iter()
-                .filter(|item| matches!(item, ItemKind::Teleport(_)))
-                .cloned()
-                .collect();
-
-            for item in items_to_remove {
-                self.generated_map.tiles[teleport_pos]
-                    .items
-                    .retain(|i| *i != item);
-            }
+            self.generated_map.tiles.retain_items(
+                teleport_pos,
+                |item| !matches!(item, ItemKind::Teleport(teleport) if !teleport.up),
+            );
         } else {
             println!("Downstairs teleport position is not set.");
         }
@@ -141,16 +163,27 @@ impl Map {
         self.generated_map.downstair_teleport = None; // Clear the teleport position
     }
 
+    /// Puts the stairs back to the floor above at `pos`, unless the map already has them.
+    pub fn add_upstairs_teleport(&mut self, pos: Position) {
+        if self.generated_map.upstair_teleport.is_some() {
+            return;
+        }
+        self.generated_map.tiles.add_upstairs(pos);
+        self.generated_map.upstair_teleport = Some(pos);
+    }
+
     pub fn add_player(&mut self, player: &mut Player, pos: Position) {
         if !self.is_tile_walkable(pos) {
             println!("Position is not walkable, cannot set player position.");
             return;
         }
 
-        self.generated_map.tiles[pos].creature = PLAYER_CREATURE_ID;
+        self.generated_map
+            .tiles
+            .set_creature(pos, PLAYER_CREATURE_ID);
         player.set_pos(pos);
 
-        self.compute_player_fov(player, max(GRID_WIDTH, GRID_HEIGHT));
+        self.compute_player_fov(player, self.max_fov_radius());
     }
 
     pub fn add_player_first_map(&mut self, player: &mut Player) {
@@ -174,9 +207,9 @@ impl Map {
             for i in 1..6 {
                 container.add_item(i);
             }
-            self.generated_map.tiles[pos]
-                .items
-                .push(ItemKind::Container(container));
+            self.generated_map
+                .tiles
+                .add_item(pos, ItemKind::Container(container));
             self.generated_map
                 .available_walkable_cache
                 .retain(|&p| p != pos); // Remove chest position from available walkable cache
@@ -187,13 +220,94 @@ impl Map {
 
     pub fn compute_player_fov(&mut self, player: &mut Player, radius: usize) {
         let pos = { player.pos() };
-        let visible = Navigator::compute_fov(&self.generated_map.tiles, pos, radius);
+        self.update_light(player);
+        let revision = self.generated_map.tiles.revision();
+        let mut visible = match &self.sight_cache {
+            Some(cache)
+                if cache.origin == pos && cache.radius == radius && cache.revision == revision =>
+            {
+                cache.sight.clone()
+            }
+            _ => {
+                let sight = Navigator::compute_fov(
+                    &self.generated_map.tiles,
+                    pos,
+                    radius,
+                    FovMode::Symmetric,
+                );
+                self.sight_cache = Some(SightCache {
+                    origin: pos,
+                    radius,
+                    revision,
+                    sight: sight.clone(),
+                });
+                sight
+            }
+        };
+        // Whatever is in the dark stays unseen, even in plain line of sight
+        visible.retain(|tile_pos| tile_pos == pos || self.light.is_lit(tile_pos));
+        // Peeking from another map doesn't count as having been here
+        if self.generated_map.visited_state == VisitedState::Visited {
+            for pos in visible.iter() {
+                self.explored
+                    .remember(pos, self.generated_map.tiles.items(pos));
+            }
+        }
         player.line_of_sight = visible;
     }
 
+    /// Relights the map from the player, glowing monsters and glowing tiles.
+    fn update_light(&mut self, player: &Player) {
+        let mut sources = vec![LightSource {
+            pos: player.pos(),
+            radius: player.light_radius(),
+        }];
+        for monster in self.monsters.values() {
+            // A monster in the middle of its own update is left out rather than panicking
+            if let Ok(monster) = monster.try_borrow()
+                && monster.kind.light_radius > 0
+            {
+                sources.push(LightSource {
+                    pos: monster.pos(),
+                    radius: monster.kind.light_radius,
+                });
+            }
+        }
+        for x in 0..self.generated_map.width() {
+            for y in 0..self.generated_map.height() {
+                let pos = Position::new(x, y);
+                let radius = self.generated_map.tiles[pos].def().light_radius;
+                if radius > 0 {
+                    sources.push(LightSource { pos, radius });
+                }
+            }
+        }
+        self.light = LightMap::compute(
+            &self.generated_map.tiles,
+            self.generated_map.ambient_light,
+            &sources,
+        );
+    }
+
+    /// Whether the player should be shown what is on `pos` right now. Maps are
+    /// shown whole while peeking, the fog only covers the map the player is on.
+    pub fn is_tile_in_view(&self, player: &Player, pos: Position) -> bool {
+        self.generated_map.visited_state != VisitedState::Visited
+            || player.line_of_sight.contains(pos)
+    }
+
     fn update_fov_caches(&mut self, player: &mut Player) {
         self.shown_fov = FovToShow::None;
 
+        // The terrain changed since the player last looked, e.g. a script dug through a wall
+        if self
+            .sight_cache
+            .as_ref()
+            .is_some_and(|cache| cache.revision != self.generated_map.tiles.revision())
+        {
+            self.compute_player_fov(player, self.max_fov_radius());
+        }
+
         let mut spell_fov_needs_update = false;
         if let Some(selected_spell) = player.selected_spell {
             if selected_spell == u8::MAX {
@@ -223,13 +337,10 @@ impl Map {
                     }
                 } else {
                     self.shown_fov = FovToShow::Spell;
-                    self.spell_or_attack_fov_cache.origin = player.pos();
-                    self.spell_or_attack_fov_cache.radius =
-                        player_spell.spell_type.area_radius.unwrap_or(0);
-                    self.spell_or_attack_fov_cache.area = Navigator::compute_fov(
+                    self.spell_or_attack_fov_cache.update(
                         &self.generated_map.tiles,
-                        self.spell_or_attack_fov_cache.origin,
-                        self.spell_or_attack_fov_cache.radius as usize,
+                        player.pos(),
+                        player_spell.spell_type.area_radius.unwrap_or(0),
                     );
                     return;
                 }
@@ -238,30 +349,26 @@ impl Map {
 
         if spell_fov_needs_update {
             if player.selected_spell.unwrap() == u8::MAX {
-                self.spell_or_attack_fov_cache.radius = player
+                let radius = player
                     .equipment
                     .weapon
                     .as_ref()
                     .and_then(|w| w.range)
                     .unwrap_or(1);
-                self.spell_or_attack_fov_cache.origin = player.pos();
-                self.spell_or_attack_fov_cache.area = Navigator::compute_fov(
+                self.spell_or_attack_fov_cache.update(
                     &self.generated_map.tiles,
-                    self.spell_or_attack_fov_cache.origin,
-                    self.spell_or_attack_fov_cache.radius as usize,
+                    player.pos(),
+                    radius,
                 );
             } else {
-                self.spell_or_attack_fov_cache.radius = player.spells
-                    [player.selected_spell.unwrap() as usize]
+                let radius = player.spells[player.selected_spell.unwrap() as usize]
                     .spell_type
                     .area_radius
                     .unwrap_or(0);
-                self.spell_or_attack_fov_cache.origin =
-                    self.hovered_tile.unwrap_or(POSITION_INVALID);
-                self.spell_or_attack_fov_cache.area = Navigator::compute_fov(
+                self.spell_or_attack_fov_cache.update(
                     &self.generated_map.tiles,
-                    self.spell_or_attack_fov_cache.origin,
-                    self.spell_or_attack_fov_cache.radius as usize,
+                    self.hovered_tile.unwrap_or(POSITION_INVALID),
+                    radius,
                 );
             }
         } else {
@@ -277,18 +384,50 @@ impl Map {
         animate_for: f32,
     ) {
         self.update_fov_caches(player);
+        let has_hostiles = self.has_hostiles();
 
-        for x in 0..GRID_WIDTH {
-            for y in 0..GRID_HEIGHT {
+        for x in 0..self.generated_map.width() {
+            for y in 0..self.generated_map.height() {
                 let tile = &self.generated_map.tiles[Position::new(x, y)];
+                if !self.is_tile_in_view(player, Position::new(x, y)) {
+                    if self.explored.is_explored(Position::new(x, y)) {
+                        tile.draw_remembered(
+                            Position::new(x, y),
+                            offset,
+                            self.generated_map.tiles.is_border(Position::new(x, y)),
+                            has_hostiles,
+                            self.explored.items(Position::new(x, y)),
+                        );
+                    }
+                    continue;
+                }
                 tile.draw(
                     Position::new(x, y),
                     offset,
-                    !self.monsters.is_empty(),
+                    self.generated_map.tiles.is_border(Position::new(x, y)),
+                    has_hostiles,
+                    self.generated_map.tiles.items(Position::new(x, y)),
+                    self.generated_map.tiles.creature(Position::new(x, y)) != NO_CREATURE,
                     animating_effects.get(&Position::new(x, y)),
                     animate_for,
                 );
 
+                let darkness = 1.0 - self.light.level(Position::new(x, y));
+                if darkness > 0.0 {
+                    draw_rectangle(
+                        offset.x + x as f32 * TILE_SIZE,
+                        offset.y + y as f32 * TILE_SIZE,
+                        TILE_SIZE,
+                        TILE_SIZE,
+                        Color {
+                            r: 0.0,
+                            g: 0.0,
+                            b: 0.0,
+                            a: darkness * 0.6,
+                        },
+                    );
+                }
+
                 if self.shown_fov != FovToShow::None && animate_for == 0.0 {
                     let player_pos = player.pos();
                     let tile_pos = Position { x, y };
@@ -297,7 +436,7 @@ impl Map {
                         && (player_pos
                             .in_range(&tile_pos, self.spell_or_attack_fov_cache.radius as usize)
                             || player_pos.is_neighbor(&tile_pos))
-                        && player.line_of_sight.contains(&tile_pos)
+                        && player.line_of_sight.contains(tile_pos)
                     {
                         draw_rectangle(
                             offset.x + x as f32 * TILE_SIZE,
@@ -322,7 +461,7 @@ impl Map {
                         if spell.spell_type.range.is_some()
                             && player_pos
                                 .in_range(&tile_pos, spell.spell_type.range.unwrap() as usize)
-                            && player.line_of_sight.contains(&tile_pos)
+                            && player.line_of_sight.contains(tile_pos)
                         {
                             draw_rectangle(
                                 offset.x + x as f32 * TILE_SIZE,
@@ -341,7 +480,7 @@ impl Map {
                         if self
                             .spell_or_attack_fov_cache
                             .area
-                            .contains(&Position { x, y })
+                            .contains(Position { x, y })
                         {
                             draw_rectangle(
                                 offset.x + x as f32 * TILE_SIZE,
@@ -365,7 +504,10 @@ impl Map {
         gl_use_material(&material);
 
         for (_, monster) in &self.monsters {
-            monster.borrow().draw(&mut material, offset);
+            let monster = monster.borrow();
+            if self.is_tile_in_view(player, monster.pos()) {
+                monster.draw(&mut material, offset);
+            }
         }
 
         if self.generated_map.visited_state == VisitedState::Visited {
@@ -376,44 +518,339 @@ impl Map {
     }
 
     pub fn is_tile_enemy_occupied(&self, pos: Position) -> bool {
-        pos.x < GRID_WIDTH && pos.y < GRID_HEIGHT && self.generated_map.tiles[pos].has_enemy()
+        self.faction_at(pos)
+            .is_some_and(|faction| faction != Faction::Allied)
+    }
+
+    pub fn is_tile_ally_occupied(&self, pos: Position) -> bool {
+        self.faction_at(pos) == Some(Faction::Allied)
+    }
+
+    fn faction_at(&self, pos: Position) -> Option<Faction> {
+        if !self.generated_map.tiles.in_bounds(pos) {
+            return None;
+        }
+        let tiles = &self.generated_map.tiles;
+        if !tiles.has_monster(pos) {
+            return None;
+        }
+        self.monsters
+            .get(&tiles.creature(pos))
+            .map(|monster| monster.borrow().faction)
+    }
+
+    /// True while anything not on the player's side is still alive on this map.
+    pub fn has_hostiles(&self) -> bool {
+        self.monsters
+            .values()
+            .any(|monster| monster.borrow().faction != Faction::Allied)
+    }
+
+    /// A radius large enough for the field of view to cover the whole map.
+    pub fn max_fov_radius(&self) -> usize {
+        max(self.generated_map.width(), self.generated_map.height())
     }
 
     pub fn is_tile_walkable(&self, pos: Position) -> bool {
-        pos.x < GRID_WIDTH && pos.y < GRID_HEIGHT && self.generated_map.tiles[pos].is_walkable()
+        self.generated_map.tiles.in_bounds(pos) && self.generated_map.tiles.is_walkable(pos)
     }
 
     pub fn is_tile_blocking(&self, pos: Position) -> bool {
-        pos.x >= GRID_WIDTH || pos.y >= GRID_HEIGHT || self.generated_map.tiles[pos].is_blocking()
+        !self.generated_map.tiles.in_bounds(pos) || self.generated_map.tiles.is_blocking(pos)
     }
 
     pub fn is_tile_blocking_by_object(&self, pos: Position) -> bool {
-        pos.x >= GRID_WIDTH
-            || pos.y >= GRID_HEIGHT
+        !self.generated_map.tiles.in_bounds(pos)
             || self.generated_map.tiles[pos].is_solid_blocking()
     }
 
+    /// What the terrain at `pos` would turn into if destroyed. The outer ring is
+    /// left alone so the borders keep leading where the overworld expects.
+    fn remains_at(&self, pos: Position) -> Option<TileKind> {
+        let tiles = &self.generated_map.tiles;
+        if !tiles.in_bounds(pos)
+            || pos.x == 0
+            || pos.y == 0
+            || pos.x == tiles.width() - 1
+            || pos.y == tiles.height() - 1
+        {
+            return None;
+        }
+        get_tile_defs().remains_of(tiles[pos].kind())
+    }
+
+    pub fn is_tile_destructible(&self, pos: Position) -> bool {
+        self.remains_at(pos).is_some()
+    }
+
+    /// Turns the terrain at `pos` into its remains, usually rubble, and keeps the
+    /// walkable tiles and the rims of neighboring chasms in step with it.
+    /// Returns whether anything was destroyed.
+    pub fn destroy_terrain(&mut self, pos: Position) -> bool {
+        let Some(remains) = self.remains_at(pos) else {
+            return false;
+        };
+
+        let tiles = &mut self.generated_map.tiles;
+        let was_walkable = tiles[pos].def().walkable;
+        tiles[pos] = Tile::new(remains);
+        let walkable = tiles[pos].def().walkable;
+
+        for neighbor in std::iter::once(pos).chain(pos.positions_around()) {
+            if tiles.in_bounds(neighbor) {
+                tiles[neighbor].edge = tiles.chasm_edges(neighbor);
+            }
+        }
+
+        if walkable && !was_walkable {
+            self.generated_map.walkable_cache.push(pos);
+            self.generated_map.available_walkable_cache.push(pos);
+        } else if !walkable && was_walkable {
+            self.generated_map.walkable_cache.retain(|&p| p != pos);
+            self.generated_map
+                .available_walkable_cache
+                .retain(|&p| p != pos);
+        }
+        true
+    }
+
+    /// Destroys the destructible terrain a blast covering `area` runs into.
+    /// Returns how many tiles were destroyed.
+    pub fn blast_terrain(&mut self, area: &[Position]) -> usize {
+        let mut hit: Vec<Position> = area
+            .iter()
+            .flat_map(|pos| pos.positions_around())
+            .filter(|&pos| self.is_tile_destructible(pos))
+            .collect();
+        hit.sort_by_key(|pos| (pos.y, pos.x));
+        hit.dedup();
+        hit.into_iter()
+            .filter(|&pos| self.destroy_terrain(pos))
+            .count()
+    }
+
+    /// Opens the door at `pos`, spending one of `keys` when it is locked.
+    /// Returns whether the door opened.
+    pub fn open_door(&mut self, pos: Position, keys: &mut u32) -> bool {
+        if !self.generated_map.tiles.in_bounds(pos) {
+            return false;
+        }
+        let tile = &mut self.generated_map.tiles[pos];
+        match tile.door {
+            Some(DoorState::Closed) => {}
+            Some(DoorState::Locked) if *keys > 0 => {
+                *keys -= 1;
+                println!("Unlocked the door, {} keys left.", keys);
+            }
+            Some(DoorState::Locked) => {
+                println!("The door is locked.");
+                return false;
+            }
+            Some(DoorState::Open) | None => return false,
+        }
+        tile.door = Some(DoorState::Open);
+        true
+    }
+
+    /// Closes the open door at `pos` unless something stands in the doorway.
+    pub fn close_door(&mut self, pos: Position) -> bool {
+        if !self.generated_map.tiles.in_bounds(pos) {
+            return false;
+        }
+        let tiles = &mut self.generated_map.tiles;
+        if tiles[pos].door != Some(DoorState::Open)
+            || tiles.creature(pos) != NO_CREATURE
+            || !tiles.items(pos).is_empty()
+        {
+            return false;
+        }
+        tiles[pos].door = Some(DoorState::Closed);
+        true
+    }
+
     pub fn get_chest_items(&self, position: &Position) -> Option<&Vec<u32>> {
-        if position.x < GRID_WIDTH && position.y < GRID_HEIGHT {
-            let tile = &self.generated_map.tiles[*position];
-            if let Some(item) = tile.get_top_item() {
-                if let ItemKind::Container(container) = item {
-                    return Some(&container.items);
-                }
+        if self.generated_map.tiles.in_bounds(*position) {
+            if let Some(ItemKind::Container(container)) =
+                self.generated_map.tiles.top_item(*position)
+            {
+                return Some(&container.items);
             }
         }
         None
     }
 
     pub fn remove_chest(&mut self, position: Position) {
-        for (idx, item) in self.generated_map.tiles[position].items.iter().enumerate() {
-            if let ItemKind::Container(_) = item {
-                self.generated_map.tiles[position].items.remove(idx);
-                return; // Exit after removing the first container
+        let tiles = &mut self.generated_map.tiles;
+        if let Some(idx) = tiles
+            .items(position)
+            .iter()
+            .position(|item| matches!(item, ItemKind::Container(_)))
+        {
+            tiles.remove_item(position, idx);
+        }
+    }
+
+    /// Opens the stairs and drops the guaranteed reward once the arena boss dies.
+    pub fn on_boss_defeated(&mut self, dead_pos: Position) {
+        let Some(boss) = self.generated_map.boss.as_mut() else {
+            return;
+        };
+
+        boss.defeated = true;
+        let stairs_pos = boss.stairs_pos;
+        let reward = boss.reward.clone();
+        println!("{} has fallen! The way down is open.", boss.name);
+
+        self.generated_map.tiles.add_teleport(stairs_pos);
+        self.generated_map.downstair_teleport = Some(stairs_pos);
+
+        if !reward.is_empty() {
+            let mut container = Container::new();
+            for item_id in reward {
+                container.add_item(item_id);
             }
+
+            let chest_pos = if dead_pos != stairs_pos {
+                dead_pos
+            } else {
+                self.get_random_adjacent_position(dead_pos, true)
+                    .unwrap_or(dead_pos)
+            };
+            self.generated_map
+                .tiles
+                .add_item(chest_pos, ItemKind::Container(container));
         }
     }
 
+    /// Checks the map population cap and, for spawned monsters, the spawner's own cap.
+    pub fn can_add_monster(&self, spawner_id: Option<u32>) -> bool {
+        if self.monsters.len() >= self.generated_map.population_cap {
+            return false;
+        }
+
+        let Some(spawner_id) = spawner_id else {
+            return true;
+        };
+
+        let spawn_cap = self
+            .monsters
+            .get(&spawner_id)
+            .and_then(|spawner| spawner.borrow().kind.spawn_cap);
+
+        match spawn_cap {
+            Some(cap) => {
+                let alive = self
+                    .monsters
+                    .values()
+                    .filter(|monster| monster.borrow().spawner_id == Some(spawner_id))
+                    .count();
+                alive < cap as usize
+            }
+            None => true,
+        }
+    }
+
+    /// Pulls every allied monster off this map so it can travel with the player.
+    pub fn take_allies(&mut self) -> Vec<MonsterRc> {
+        let ids: Vec<u32> = self
+            .monsters
+            .iter()
+            .filter(|(_, monster)| monster.borrow().faction == Faction::Allied)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut allies = Vec::new();
+        for id in ids {
+            if let Some(monster) = self.monsters.remove(&id) {
+                let pos = monster.borrow().pos();
+                if self.generated_map.tiles.creature(pos) == id {
+                    self.generated_map.tiles.set_creature(pos, NO_CREATURE);
+                }
+                allies.push(monster);
+            }
+        }
+        allies
+    }
+
+    /// Drops a monster on the closest free walkable tile around `origin`.
+    /// Returns false when no tile could be found and the monster is left behind.
+    pub fn place_monster_near(&mut self, monster: MonsterRc, origin: Position) -> bool {
+        let Some(pos) = self.find_free_tile_near(origin) else {
+            return false;
+        };
+
+        let id = {
+            let mut monster = monster.borrow_mut();
+            monster.set_pos(pos);
+            monster.id
+        };
+        self.generated_map.tiles.set_creature(pos, id);
+        self.monsters.insert(id, monster);
+        true
+    }
+
+    /// Closest unoccupied, walkable, non-border tile to `origin`, `origin` itself included.
+    pub fn find_free_tile_near(&self, origin: Position) -> Option<Position> {
+        let radius = max(self.generated_map.width(), self.generated_map.height());
+        for r in 0..radius {
+            let min_x = origin.x.saturating_sub(r);
+            let min_y = origin.y.saturating_sub(r);
+            for x in min_x..=min(origin.x + r, self.generated_map.width() - 1) {
+                for y in min_y..=min(origin.y + r, self.generated_map.height() - 1) {
+                    let pos = Position::new(x, y);
+                    let tiles = &self.generated_map.tiles;
+                    if tiles.creature(pos) != NO_CREATURE
+                        || !tiles.is_walkable(pos)
+                        || tiles.is_border(pos)
+                    {
+                        continue;
+                    }
+                    return Some(pos);
+                }
+            }
+        }
+        None
+    }
+
+    /// Rolls each hidden trap within `radius` of `origin` against its detect chance.
+    /// Returns how many traps were revealed.
+    pub fn search_for_traps(&mut self, origin: Position, radius: usize) -> usize {
+        let mut rng = thread_rng();
+        let mut found = 0;
+        let min_x = origin.x.saturating_sub(radius);
+        let min_y = origin.y.saturating_sub(radius);
+        for x in min_x..=min(origin.x + radius, self.generated_map.width() - 1) {
+            for y in min_y..=min(origin.y + radius, self.generated_map.height() - 1) {
+                let tile = &mut self.generated_map.tiles[Position::new(x, y)];
+                if let Some(trap) = tile.trap.as_mut().filter(|trap| trap.hidden)
+                    && rng.gen_bool(trap.kind().detect_chance)
+                {
+                    trap.hidden = false;
+                    found += 1;
+                }
+            }
+        }
+        found
+    }
+
+    /// A random free tile away from the borders and any traps, used to scatter creatures.
+    pub fn get_random_free_position(&self) -> Option<Position> {
+        let candidates: Vec<Position> = self
+            .generated_map
+            .walkable_cache
+            .iter()
+            .copied()
+            .filter(|&pos| {
+                let tiles = &self.generated_map.tiles;
+                tiles.is_walkable(pos)
+                    && tiles.creature(pos) == NO_CREATURE
+                    && tiles[pos].trap.is_none()
+                    && !tiles.is_border(pos)
+            })
+            .collect();
+        candidates.choose(&mut thread_rng()).copied()
+    }
+
     pub fn get_random_adjacent_position(
         &mut self,
         position: Position,
@@ -449,6 +886,18 @@ impl UserData for MapRc {
             Ok(this.0.borrow().generated_map.tier)
         });
 
+        methods.add_method("get_population", |_, this, ()| {
+            Ok(this.0.borrow().monsters.len())
+        });
+
+        methods.add_method("get_population_cap", |_, this, ()| {
+            Ok(this.0.borrow().generated_map.population_cap)
+        });
+
+        methods.add_method("is_boss_arena", |_, this, ()| {
+            Ok(this.0.borrow().generated_map.boss.is_some())
+        });
+
         methods.add_method("get_monster_kinds", |_, this, ()| {
             let test = this.0.borrow().generated_map.monster_kinds.clone();
             println!("Monster kinds: {:?}", test);
@@ -469,6 +918,14 @@ impl UserData for MapRc {
             Ok(tbl)
         });
 
+        methods.add_method("destroy_terrain", |_, this, pos: Table| {
+            let position = Position {
+                x: pos.get("x")?,
+                y: pos.get("y")?,
+            };
+            Ok(this.0.borrow_mut().destroy_terrain(position))
+        });
+
         methods.add_method(
             "get_random_adjacent_position",
             |lua, this, (pos, must_be_walkable): (Table, bool)| {
@@ -504,7 +961,7 @@ impl UserData for MapRc {
         //         .expect("Monster type not found");
 
         //     let monster = Rc::new(RefCell::new(Monster::new(p, kind.clone())));
-        //     this.generated_map.tiles[p].creature = id;
+        //     this.generated_map.tiles.set_creature(p, id);
         //     let id = monster.borrow().id;
         //     this.monsters.insert(id, monster);
         //     Ok(())
@@ -0,0 +1,719 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use rand::Rng;
+use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+
+use crate::maps::MapTheme;
+use crate::maps::map_generator::{GenerationParams, MapGenerator};
+use crate::position::Position;
+use crate::tile::{DoorState, Tile, TileKind};
+
+/// Layout algorithm used to carve the walkable part of a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapAlgorithm {
+    RandomWalk,
+    Rooms,
+    Caves,
+    DrunkardsWalk,
+    Maze,
+    /// A Lua layout script from `assets/mapgen`, by file name.
+    Scripted(String),
+}
+
+impl MapAlgorithm {
+    const ALL: [MapAlgorithm; 5] = [
+        MapAlgorithm::RandomWalk,
+        MapAlgorithm::Rooms,
+        MapAlgorithm::Caves,
+        MapAlgorithm::DrunkardsWalk,
+        MapAlgorithm::Maze,
+    ];
+
+    /// Picks one of the built-in algorithms or one of the given layout scripts.
+    pub fn random(rng: &mut impl Rng, scripted: &[String]) -> Self {
+        let index = rng.gen_range(0..Self::ALL.len() + scripted.len());
+        match Self::ALL.get(index) {
+            Some(algorithm) => algorithm.clone(),
+            None => MapAlgorithm::Scripted(scripted[index - Self::ALL.len()].clone()),
+        }
+    }
+
+    /// Parses a built-in algorithm name, or the name of one of the given layout scripts.
+    pub fn from_name(name: &str, scripted: &[String]) -> Option<Self> {
+        match name {
+            "random_walk" => Some(MapAlgorithm::RandomWalk),
+            "rooms" => Some(MapAlgorithm::Rooms),
+            "caves" => Some(MapAlgorithm::Caves),
+            "drunkards_walk" => Some(MapAlgorithm::DrunkardsWalk),
+            "maze" => Some(MapAlgorithm::Maze),
+            _ => scripted
+                .iter()
+                .find(|script| *script == name)
+                .map(|script| MapAlgorithm::Scripted(script.clone())),
+        }
+    }
+
+    /// Built-in generators only, scripted layouts need the generator thread's Lua state.
+    pub fn generator(&self) -> Option<Box<dyn MapLayoutGenerator>> {
+        match self {
+            MapAlgorithm::RandomWalk => Some(Box::new(RandomWalkGenerator)),
+            MapAlgorithm::Rooms => Some(Box::new(RoomsGenerator)),
+            MapAlgorithm::Caves => Some(Box::new(CavesGenerator)),
+            MapAlgorithm::DrunkardsWalk => Some(Box::new(DrunkardsWalkGenerator)),
+            MapAlgorithm::Maze => Some(Box::new(MazeGenerator)),
+            MapAlgorithm::Scripted(_) => None,
+        }
+    }
+}
+
+/// Content a layout asks for, placed once the map gets populated.
+#[derive(Debug, Clone)]
+pub enum LayoutPlacement {
+    Monster(u32, Position),
+    /// A monster picked from the map's tier pool.
+    RandomMonster(Position),
+    Orb(Position),
+    Chest(Position),
+    /// Opens one of the map's locked doors.
+    Key(Position),
+    /// Downstairs, used instead of a random spot when the map leads down.
+    Teleport(Position),
+}
+
+impl LayoutPlacement {
+    pub fn position(&self) -> Position {
+        match self {
+            LayoutPlacement::Monster(_, pos)
+            | LayoutPlacement::RandomMonster(pos)
+            | LayoutPlacement::Orb(pos)
+            | LayoutPlacement::Chest(pos)
+            | LayoutPlacement::Key(pos)
+            | LayoutPlacement::Teleport(pos) => *pos,
+        }
+    }
+}
+
+/// Tiles being carved by a layout generator, together with the walkable cache
+/// that has to follow every carved tile.
+pub struct LayoutCanvas<'a> {
+    tiles: &'a mut Vec<Vec<Tile>>,
+    walkable_cache: &'a mut Vec<Position>,
+    base: TileKind,
+    placements: Vec<LayoutPlacement>,
+}
+
+impl<'a> LayoutCanvas<'a> {
+    pub fn new(
+        tiles: &'a mut Vec<Vec<Tile>>,
+        walkable_cache: &'a mut Vec<Position>,
+        base: TileKind,
+    ) -> Self {
+        Self {
+            tiles,
+            walkable_cache,
+            base,
+            placements: Vec::new(),
+        }
+    }
+
+    /// Whether `pos` holds walkable terrain, plain floor or otherwise.
+    pub fn is_floor(&self, pos: Position) -> bool {
+        pos.x < self.width() && pos.y < self.height() && self.tiles[pos.x][pos.y].def().walkable
+    }
+
+    pub fn kind(&self, pos: Position) -> TileKind {
+        self.tiles[pos.x][pos.y].kind()
+    }
+
+    pub fn width(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles[0].len()
+    }
+
+    /// Whether `pos` lies inside the outer ring kept for border entrances.
+    pub fn is_inner(&self, pos: Position) -> bool {
+        pos.x > 0 && pos.y > 0 && pos.x < self.width() - 1 && pos.y < self.height() - 1
+    }
+
+    pub fn walkable(&self) -> &[Position] {
+        self.walkable_cache
+    }
+
+    /// Turns a carved tile back into the map's base tile.
+    pub fn fill(&mut self, pos: Position) {
+        self.set_kind(pos, self.base);
+    }
+
+    /// Overwrites a tile with any kind, keeping the walkable cache in sync.
+    pub fn set_kind(&mut self, pos: Position, kind: TileKind) {
+        if pos.x >= self.width()
+            || pos.y >= self.height()
+            || self.tiles[pos.x][pos.y].kind() == kind
+        {
+            return;
+        }
+        match (self.is_floor(pos), kind.def().walkable) {
+            (true, false) => self.walkable_cache.retain(|&p| p != pos),
+            (false, true) => self.walkable_cache.push(pos),
+            _ => {}
+        }
+        self.tiles[pos.x][pos.y] = Tile::new(kind);
+    }
+
+    pub fn set_door(&mut self, pos: Position, state: DoorState) {
+        let tile = &mut self.tiles[pos.x][pos.y];
+        if tile.door.is_some() {
+            tile.door = Some(state);
+        }
+    }
+
+    pub fn place(&mut self, placement: LayoutPlacement) {
+        self.placements.push(placement);
+    }
+
+    pub fn take_placements(&mut self) -> Vec<LayoutPlacement> {
+        std::mem::take(&mut self.placements)
+    }
+
+    pub fn carve(&mut self, pos: Position) {
+        MapGenerator::carve_tile(self.tiles, pos.x, pos.y, self.walkable_cache);
+    }
+
+    pub fn carve_path(&mut self, from: Position, to: Position, rng: &mut ThreadRng, radius: usize) {
+        self.carve_jagged(from, to, rng, radius, false);
+    }
+
+    /// Same as `carve_path`, but leaves the outer ring alone so no border
+    /// entrance opens up that the neighbouring maps do not know about.
+    pub fn carve_inner_path(
+        &mut self,
+        from: Position,
+        to: Position,
+        rng: &mut ThreadRng,
+        radius: usize,
+    ) {
+        self.carve_jagged(from, to, rng, radius, true);
+    }
+
+    fn carve_jagged(
+        &mut self,
+        from: Position,
+        to: Position,
+        rng: &mut ThreadRng,
+        radius: usize,
+        inner_only: bool,
+    ) {
+        let (width, height) = (self.width(), self.height());
+        let (tiles, walkable_cache) = (&mut *self.tiles, &mut *self.walkable_cache);
+        MapGenerator::carve_jagged_path(width, height, from, to, rng, radius, |x, y| {
+            let inner = x > 0 && y > 0 && x < width - 1 && y < height - 1;
+            if inner || !inner_only {
+                MapGenerator::carve_tile(tiles, x, y, walkable_cache);
+            }
+        });
+    }
+
+    /// Carves an L shaped corridor, horizontal leg first.
+    pub fn carve_corridor(&mut self, from: Position, to: Position) {
+        MapGenerator::carve_straight_path(self.tiles, from, to, self.walkable_cache);
+        self.carve(to);
+    }
+
+    /// Links every anchor to the closest tile of `layout` so border entrances
+    /// and fixed start positions are never cut off.
+    pub fn connect_anchors(
+        &mut self,
+        anchors: &[Position],
+        layout: &[Position],
+        rng: &mut ThreadRng,
+    ) {
+        for &anchor in anchors {
+            let closest = layout
+                .iter()
+                .min_by_key(|pos| pos.x.abs_diff(anchor.x) + pos.y.abs_diff(anchor.y));
+            match closest {
+                Some(&goal) => {
+                    self.carve_path(anchor, goal, rng, 0);
+                    self.carve(goal);
+                }
+                None => self.carve(anchor),
+            }
+        }
+    }
+}
+
+pub trait MapLayoutGenerator {
+    /// Carves the map layout. Every position in `anchors` (border entrances and
+    /// predefined start positions) must end up connected to it.
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        params: &GenerationParams,
+        rng: &mut ThreadRng,
+    );
+}
+
+/// A handful of random walks chained together by jagged paths.
+pub struct RandomWalkGenerator;
+
+impl MapLayoutGenerator for RandomWalkGenerator {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let num_walks: usize = if matches!(params.theme, MapTheme::Arena) {
+            0
+        } else {
+            params.num_walks
+        };
+        let walk_length: usize = params.walk_length;
+        let min_distance_between_starts: usize = params.min_dist_between_starts;
+
+        let mut start_positions = anchors.to_vec();
+
+        for i in 0..num_walks + anchors.len() {
+            let mut x;
+            let mut y;
+
+            if i >= start_positions.len() {
+                // Ensure new walk starts away from previous walks
+                loop {
+                    x = rng.gen_range(3..width - 3);
+                    y = rng.gen_range(3..height - 3);
+
+                    let too_close = start_positions.iter().any(|&pos: &Position| {
+                        let dx = pos.x as isize - x as isize;
+                        let dy = pos.y as isize - y as isize;
+                        dx.abs() + dy.abs() < min_distance_between_starts as isize
+                    });
+
+                    if !too_close {
+                        break;
+                    }
+                }
+
+                start_positions.push(Position { x, y });
+            } else {
+                // Use existing start position
+                x = start_positions[i].x;
+                y = start_positions[i].y;
+            }
+
+            // Apply random walk
+            for _ in 0..walk_length {
+                if x >= width || y >= height {
+                    break;
+                }
+
+                canvas.carve(Position { x, y });
+
+                match rng.gen_range(0..8) {
+                    0 if x > 1 => x -= 1,
+                    1 if x < width - 2 => x += 1,
+                    2 if y > 1 => y -= 1,
+                    3 if y < height - 2 => y += 1,
+                    4 if x > 1 && y > 1 => {
+                        x -= 1;
+                        y -= 1;
+                    }
+                    5 if x < width - 2 && y > 1 => {
+                        x += 1;
+                        y -= 1;
+                    }
+                    6 if x > 1 && y < height - 2 => {
+                        x -= 1;
+                        y += 1;
+                    }
+                    7 if x < width - 2 && y < height - 2 => {
+                        x += 1;
+                        y += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        start_positions.sort_by_key(|pos| pos.x + pos.y);
+
+        for i in 1..start_positions.len() {
+            let prev = start_positions[i - 1];
+            let current = start_positions[i];
+            canvas.carve_path(prev, current, rng, params.radius);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl Rect {
+    fn center(&self) -> Position {
+        Position::new(self.x + self.w / 2, self.y + self.h / 2)
+    }
+}
+
+/// Rooms placed in the leaves of a binary space partition, siblings joined by corridors.
+pub struct RoomsGenerator;
+
+impl RoomsGenerator {
+    const MIN_LEAF: usize = 7;
+    const MAX_DEPTH: u32 = 4;
+
+    /// Splits `area` and carves its rooms, returning a tile inside the subtree
+    /// that the parent uses to connect it to its sibling.
+    fn split(
+        area: Rect,
+        depth: u32,
+        canvas: &mut LayoutCanvas,
+        layout: &mut Vec<Position>,
+        rng: &mut ThreadRng,
+    ) -> Position {
+        let can_split_x = area.w >= Self::MIN_LEAF * 2;
+        let can_split_y = area.h >= Self::MIN_LEAF * 2;
+
+        if depth < Self::MAX_DEPTH && (can_split_x || can_split_y) {
+            let split_x = if can_split_x && can_split_y {
+                area.w > area.h || (area.w == area.h && rng.gen_bool(0.5))
+            } else {
+                can_split_x
+            };
+
+            let (a, b) = if split_x {
+                let cut = rng.gen_range(Self::MIN_LEAF..=area.w - Self::MIN_LEAF);
+                (
+                    Rect { w: cut, ..area },
+                    Rect {
+                        x: area.x + cut,
+                        w: area.w - cut,
+                        ..area
+                    },
+                )
+            } else {
+                let cut = rng.gen_range(Self::MIN_LEAF..=area.h - Self::MIN_LEAF);
+                (
+                    Rect { h: cut, ..area },
+                    Rect {
+                        y: area.y + cut,
+                        h: area.h - cut,
+                        ..area
+                    },
+                )
+            };
+
+            let left = Self::split(a, depth + 1, canvas, layout, rng);
+            let right = Self::split(b, depth + 1, canvas, layout, rng);
+            canvas.carve_corridor(left, right);
+            layout.extend(corridor_positions(left, right));
+            return if rng.gen_bool(0.5) { left } else { right };
+        }
+
+        // Leave a one tile margin so rooms in neighboring leaves never merge
+        let w = rng.gen_range(3..=area.w - 2);
+        let h = rng.gen_range(3..=area.h - 2);
+        let room = Rect {
+            x: area.x + rng.gen_range(1..=area.w - w - 1),
+            y: area.y + rng.gen_range(1..=area.h - h - 1),
+            w,
+            h,
+        };
+
+        for x in room.x..room.x + room.w {
+            for y in room.y..room.y + room.h {
+                canvas.carve(Position::new(x, y));
+                layout.push(Position::new(x, y));
+            }
+        }
+        room.center()
+    }
+}
+
+fn corridor_positions(from: Position, to: Position) -> Vec<Position> {
+    let mut positions = Vec::new();
+    for x in from.x.min(to.x)..=from.x.max(to.x) {
+        positions.push(Position::new(x, from.y));
+    }
+    for y in from.y.min(to.y)..=from.y.max(to.y) {
+        positions.push(Position::new(to.x, y));
+    }
+    positions
+}
+
+impl MapLayoutGenerator for RoomsGenerator {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        _params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let area = Rect {
+            x: 1,
+            y: 1,
+            w: width - 2,
+            h: height - 2,
+        };
+        let mut layout = Vec::new();
+        Self::split(area, 0, canvas, &mut layout, rng);
+        canvas.connect_anchors(anchors, &layout, rng);
+    }
+}
+
+/// Cellular automata caves, keeping only the largest open region.
+pub struct CavesGenerator;
+
+impl CavesGenerator {
+    const FILL_CHANCE: f64 = 0.45;
+    const SMOOTH_STEPS: usize = 4;
+
+    fn walls_around(grid: &[Vec<bool>], x: usize, y: usize) -> usize {
+        let mut walls = 0;
+        for dx in -1isize..=1 {
+            for dy in -1isize..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let nx = x as isize + dx;
+                let ny = y as isize + dy;
+                if nx < 0
+                    || ny < 0
+                    || nx >= grid.len() as isize
+                    || ny >= grid[0].len() as isize
+                    || grid[nx as usize][ny as usize]
+                {
+                    walls += 1;
+                }
+            }
+        }
+        walls
+    }
+}
+
+impl MapLayoutGenerator for CavesGenerator {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let is_edge = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+        // true = wall
+        let mut grid: Vec<Vec<bool>> = (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| is_edge(x, y) || rng.gen_bool(Self::FILL_CHANCE))
+                    .collect()
+            })
+            .collect();
+
+        for _ in 0..Self::SMOOTH_STEPS {
+            grid = (0..width)
+                .map(|x| {
+                    (0..height)
+                        .map(|y| is_edge(x, y) || Self::walls_around(&grid, x, y) >= 5)
+                        .collect()
+                })
+                .collect();
+        }
+
+        // Flood fill every open region and keep the biggest one
+        let mut region_of = vec![vec![usize::MAX; height]; width];
+        let mut largest: Vec<Position> = Vec::new();
+        for x in 0..width {
+            for y in 0..height {
+                if grid[x][y] || region_of[x][y] != usize::MAX {
+                    continue;
+                }
+                let region_id = x * height + y;
+                let mut region = Vec::new();
+                let mut stack = vec![Position::new(x, y)];
+                region_of[x][y] = region_id;
+                while let Some(pos) = stack.pop() {
+                    region.push(pos);
+                    for next in pos.positions_around() {
+                        if next.x < width
+                            && next.y < height
+                            && (next.x == pos.x || next.y == pos.y)
+                            && !grid[next.x][next.y]
+                            && region_of[next.x][next.y] == usize::MAX
+                        {
+                            region_of[next.x][next.y] = region_id;
+                            stack.push(next);
+                        }
+                    }
+                }
+                if region.len() > largest.len() {
+                    largest = region;
+                }
+            }
+        }
+
+        if largest.is_empty() {
+            DrunkardsWalkGenerator.carve(canvas, anchors, params, rng);
+            return;
+        }
+
+        for &pos in &largest {
+            canvas.carve(pos);
+        }
+        canvas.connect_anchors(anchors, &largest, rng);
+    }
+}
+
+/// A single walker wandering until a share of the map has been opened up.
+pub struct DrunkardsWalkGenerator;
+
+impl DrunkardsWalkGenerator {
+    const FLOOR_PERCENT: usize = 40;
+    const MAX_STEPS: usize = 20_000;
+}
+
+impl MapLayoutGenerator for DrunkardsWalkGenerator {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        _params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let target = (width - 2) * (height - 2) * Self::FLOOR_PERCENT / 100;
+        let mut opened = vec![vec![false; height]; width];
+        let mut layout = Vec::new();
+        let mut pos = Position::new(width / 2, height / 2);
+
+        for _ in 0..Self::MAX_STEPS {
+            if !opened[pos.x][pos.y] {
+                opened[pos.x][pos.y] = true;
+                canvas.carve(pos);
+                layout.push(pos);
+                if layout.len() >= target {
+                    break;
+                }
+            }
+
+            match rng.gen_range(0..4) {
+                0 if pos.x > 1 => pos.x -= 1,
+                1 if pos.x < width - 2 => pos.x += 1,
+                2 if pos.y > 1 => pos.y -= 1,
+                3 if pos.y < height - 2 => pos.y += 1,
+                _ => {}
+            }
+        }
+
+        canvas.connect_anchors(anchors, &layout, rng);
+    }
+}
+
+/// Recursive backtracker maze on odd coordinates, with a few extra openings
+/// so there is more than one way around.
+pub struct MazeGenerator;
+
+impl MazeGenerator {
+    const LOOP_CHANCE: f64 = 0.1;
+}
+
+impl MapLayoutGenerator for MazeGenerator {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        _params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        let (width, height) = (canvas.width(), canvas.height());
+        let cells_w = (width - 1) / 2;
+        let cells_h = (height - 1) / 2;
+        let to_pos = |cx: usize, cy: usize| Position::new(cx * 2 + 1, cy * 2 + 1);
+
+        let mut visited = vec![vec![false; cells_h]; cells_w];
+        let mut layout = Vec::new();
+        let start = (rng.gen_range(0..cells_w), rng.gen_range(0..cells_h));
+        let mut stack = vec![start];
+        visited[start.0][start.1] = true;
+        canvas.carve(to_pos(start.0, start.1));
+        layout.push(to_pos(start.0, start.1));
+
+        while let Some(&(cx, cy)) = stack.last() {
+            let mut neighbors = Vec::new();
+            if cx > 0 && !visited[cx - 1][cy] {
+                neighbors.push((cx - 1, cy));
+            }
+            if cx + 1 < cells_w && !visited[cx + 1][cy] {
+                neighbors.push((cx + 1, cy));
+            }
+            if cy > 0 && !visited[cx][cy - 1] {
+                neighbors.push((cx, cy - 1));
+            }
+            if cy + 1 < cells_h && !visited[cx][cy + 1] {
+                neighbors.push((cx, cy + 1));
+            }
+
+            let Some(&(nx, ny)) = neighbors.choose(rng) else {
+                stack.pop();
+                continue;
+            };
+
+            let from = to_pos(cx, cy);
+            let to = to_pos(nx, ny);
+            let wall = Position::new((from.x + to.x) / 2, (from.y + to.y) / 2);
+            canvas.carve(wall);
+            canvas.carve(to);
+            layout.push(wall);
+            layout.push(to);
+            visited[nx][ny] = true;
+            stack.push((nx, ny));
+        }
+
+        // Knock down some of the remaining inner walls between two corridors
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                let horizontal = x % 2 == 0 && y % 2 == 1;
+                let vertical = x % 2 == 1 && y % 2 == 0;
+                if (horizontal || vertical) && rng.gen_bool(Self::LOOP_CHANCE) {
+                    canvas.carve(Position::new(x, y));
+                    layout.push(Position::new(x, y));
+                }
+            }
+        }
+
+        canvas.connect_anchors(anchors, &layout, rng);
+    }
+}
@@ -0,0 +1,480 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Dumps generated maps to plain text and PNG files, so generators can be looked at
+//! without playing through them. Run with `--export-maps <count>`, see `ExportOptions`.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use macroquad::color::{BLACK, Color};
+use macroquad::texture::Image;
+use rand::thread_rng;
+
+use crate::items::base_item::ItemKind;
+use crate::items::collection::Items;
+use crate::lua_interface::LuaInterface;
+use crate::maps::generated_map::GeneratedMap;
+use crate::maps::lighting::ambient_light;
+use crate::maps::map_algorithms::MapAlgorithm;
+use crate::maps::map_generator::{GenerationParams, MIN_MAP_SIZE, MapGenerator};
+use crate::maps::scripted_generator::MapGenScripts;
+use crate::maps::{BorderFlags, MapTheme};
+use crate::position::Position;
+use crate::tile::{DoorState, EdgeKind, Tile};
+use crate::{monster_kind, spell_type, tile_def, trap};
+
+const DEFAULT_OUT_DIR: &str = "map_exports";
+// Size of a tile in the exported PNGs
+const PNG_TILE_PX: u32 = 8;
+// Largest map side whose PNG still fits the 16 bit image dimensions
+const MAX_EXPORT_SIZE: usize = u16::MAX as usize / PNG_TILE_PX as usize;
+
+const EDGE_COLOR: Color = Color::new(0.95, 0.95, 0.95, 1.0);
+const ANCHOR_COLOR: Color = Color::new(0.2, 0.9, 0.3, 1.0);
+const MONSTER_COLOR: Color = Color::new(0.9, 0.15, 0.15, 1.0);
+const ITEM_COLOR: Color = Color::new(0.95, 0.8, 0.2, 1.0);
+const DOWNSTAIRS_COLOR: Color = Color::new(0.2, 0.8, 0.95, 1.0);
+const UPSTAIRS_COLOR: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+const TRAP_COLOR: Color = Color::new(0.8, 0.3, 0.9, 1.0);
+const HIDDEN_TRAP_COLOR: Color = Color::new(0.45, 0.2, 0.5, 1.0);
+
+/// What `--export-maps` asked for on the command line.
+pub struct ExportOptions {
+    pub count: usize,
+    pub out_dir: PathBuf,
+    pub floor: usize,
+    pub tier: u32,
+    pub theme: MapTheme,
+    // Picked at random for every map when not given
+    pub algorithm: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+}
+
+impl ExportOptions {
+    /// Returns `Ok(None)` when the game should start normally.
+    ///
+    /// `--export-maps <count> [--out <dir>] [--floor <n>] [--tier <n>] [--theme <name>]
+    /// [--algorithm <name>] [--width <n>] [--height <n>]`
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let args: Vec<String> = args.collect();
+        let Some(start) = args.iter().position(|arg| arg == "--export-maps") else {
+            return Ok(None);
+        };
+
+        let mut options = Self {
+            count: 0,
+            out_dir: PathBuf::from(DEFAULT_OUT_DIR),
+            floor: 0,
+            tier: 1,
+            theme: MapTheme::Any,
+            algorithm: None,
+            width: None,
+            height: None,
+        };
+
+        let mut iter = args[start..].iter();
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--export-maps" => options.count = parse_number(flag, value)?,
+                "--out" => options.out_dir = PathBuf::from(value),
+                "--floor" => options.floor = parse_number(flag, value)?,
+                "--tier" => options.tier = parse_number(flag, value)?,
+                "--theme" => {
+                    options.theme = MapTheme::from_name(value)
+                        .ok_or_else(|| format!("Unknown theme: {}", value))?
+                }
+                "--algorithm" => options.algorithm = Some(value.clone()),
+                "--width" => options.width = Some(parse_size(flag, value)?),
+                "--height" => options.height = Some(parse_size(flag, value)?),
+                _ => return Err(format!("Unknown export option: {}", flag)),
+            }
+        }
+
+        Ok(Some(options))
+    }
+
+    fn params(&self, algorithm: MapAlgorithm) -> GenerationParams {
+        let mut params = GenerationParams::default();
+        params.borders = BorderFlags::TOP
+            | BorderFlags::BOTTOM
+            | BorderFlags::LEFT
+            | BorderFlags::RIGHT
+            | BorderFlags::DOWN;
+        params.theme = self.theme.clone();
+        params.algorithm = algorithm;
+        params.tier = self.tier;
+        params.ambient_light = ambient_light(self.floor, &self.theme);
+        if let Some(width) = self.width {
+            params.width = width;
+        }
+        if let Some(height) = self.height {
+            params.height = height;
+        }
+        params
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<usize, String> {
+    let size = parse_number(flag, value)?;
+    if !(MIN_MAP_SIZE..=MAX_EXPORT_SIZE).contains(&size) {
+        return Err(format!(
+            "{} must be between {} and {}, got {}",
+            flag, MIN_MAP_SIZE, MAX_EXPORT_SIZE, size
+        ));
+    }
+    Ok(size)
+}
+
+/// Loads the game data generation relies on, then generates and writes out
+/// `options.count` maps as `map_NNN.txt` and `map_NNN.png`.
+pub async fn run_export(options: ExportOptions) {
+    let lua_interface = LuaInterface::new();
+
+    let spell_types = spell_type::load_spell_types().await;
+    spell_type::set_global_spell_types(spell_types);
+
+    let tile_defs = tile_def::load_tile_defs().await;
+    tile_def::set_global_tile_defs(tile_defs);
+
+    let trap_kinds = trap::load_trap_kinds(&lua_interface).await;
+    trap::set_global_trap_kinds(trap_kinds);
+
+    let monster_kinds = monster_kind::load_monster_kinds(&lua_interface).await;
+    let monster_kinds = monster_kinds.read().unwrap().vec.clone();
+
+    let mut items = Items::new();
+    items.load_holdable_items(&lua_interface).await;
+    let items = Arc::new(RwLock::new(items));
+
+    let generator = MapGenerator::new(&lua_interface, &monster_kinds, &items).await;
+    let layout_scripts = MapGenScripts::load();
+
+    let algorithm = match &options.algorithm {
+        Some(name) => match MapAlgorithm::from_name(name, &MapGenScripts::available()) {
+            Some(algorithm) => Some(algorithm),
+            None => {
+                eprintln!("[MapExport] Unknown algorithm: {}", name);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    if let Err(e) = fs::create_dir_all(&options.out_dir) {
+        eprintln!(
+            "[MapExport] Could not create {}: {}",
+            options.out_dir.display(),
+            e
+        );
+        return;
+    }
+
+    let mut rng = thread_rng();
+    for index in 0..options.count {
+        let algorithm = algorithm
+            .clone()
+            .unwrap_or_else(|| generator.random_algorithm(&mut rng));
+        println!(
+            "[MapExport] Generating map {}/{} with {:?}",
+            index + 1,
+            options.count,
+            algorithm
+        );
+
+        let map = generator.generate_blocking(&layout_scripts, &options.params(algorithm));
+
+        let base = options.out_dir.join(format!("map_{:03}", index));
+        if let Err(e) = export_ascii(&map, &base.with_extension("txt")) {
+            eprintln!("[MapExport] Could not write {}: {}", base.display(), e);
+            return;
+        }
+        export_png(&map, &base.with_extension("png"));
+    }
+
+    println!(
+        "[MapExport] Wrote {} maps to {}",
+        options.count,
+        options.out_dir.display()
+    );
+}
+
+/// Writes the tiles, edges, content, teleports and border anchors of `map` as text.
+pub fn export_ascii(map: &GeneratedMap, path: &Path) -> io::Result<()> {
+    fs::write(path, map_to_ascii(map))
+}
+
+pub fn map_to_ascii(map: &GeneratedMap) -> String {
+    let (width, height) = (map.width(), map.height());
+    let mut out = String::new();
+
+    let report = map.reachability_report();
+    let _ = writeln!(out, "tier: {}", map.tier);
+    let _ = writeln!(out, "size: {}x{}", width, height);
+    let _ = writeln!(out, "ambient_light: {:.2}", map.ambient_light);
+    let _ = writeln!(out, "population_cap: {}", map.population_cap);
+    let _ = writeln!(out, "regions: {}", report.regions.len());
+    if let Some(boss) = &map.boss {
+        let _ = writeln!(out, "boss: {}", boss.name);
+    }
+    let _ = writeln!(
+        out,
+        "downstairs: {}",
+        format_position(map.downstair_teleport)
+    );
+    let _ = writeln!(out, "upstairs: {}", format_position(map.upstair_teleport));
+
+    // Monsters go on top of everything else, the way they are drawn in game
+    let mut monster_glyphs = vec![None; width * height];
+    for monster in &map.monsters {
+        monster_glyphs[monster.position.y * width + monster.position.x] = Some(monster.kind.glyph);
+    }
+
+    let _ = writeln!(
+        out,
+        "\n[tiles] > downstairs, < upstairs, * orb, - key, ! item, = chest, ^ trap, ' open door"
+    );
+    for y in 0..height {
+        for x in 0..width {
+            let pos = Position::new(x, y);
+            let glyph = monster_glyphs[y * width + x]
+                .unwrap_or_else(|| tile_glyph(&map.tiles[pos], map.tiles.top_item(pos)));
+            out.push(glyph);
+        }
+        out.push('\n');
+    }
+
+    let _ = writeln!(out, "\n[edges] EdgeKind bits in hex, .. for none");
+    for y in 0..height {
+        let row: Vec<String> = (0..width)
+            .map(|x| {
+                let edge = map.tiles[Position::new(x, y)].edge;
+                if edge == EdgeKind::NONE {
+                    "..".to_string()
+                } else {
+                    format!("{:02x}", edge.bits())
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "{}", row.join(" "));
+    }
+
+    let _ = writeln!(out, "\n[borders]");
+    for (name, positions) in ["top", "right", "bottom", "left"]
+        .iter()
+        .zip(map.border_positions.iter())
+    {
+        let anchors: Vec<String> = positions
+            .iter()
+            .map(|pos| format!("({}, {})", pos.x, pos.y))
+            .collect();
+        let _ = writeln!(out, "{}: {}", name, anchors.join(" "));
+    }
+
+    let _ = writeln!(out, "\n[monsters]");
+    for monster in &map.monsters {
+        let _ = writeln!(
+            out,
+            "({}, {}) {} hp {}",
+            monster.position.x, monster.position.y, monster.name, monster.hp
+        );
+    }
+
+    let _ = writeln!(out, "\n[items]");
+    for y in 0..height {
+        for x in 0..width {
+            let pos = Position::new(x, y);
+            let tile = &map.tiles[pos];
+            for item in map.tiles.items(pos) {
+                let _ = writeln!(out, "({}, {}) {}", x, y, item_name(item));
+            }
+            if let Some(trap) = &tile.trap {
+                let hidden = if trap.hidden { " (hidden)" } else { "" };
+                let _ = writeln!(out, "({}, {}) trap {}{}", x, y, trap.kind().name, hidden);
+            }
+        }
+    }
+
+    out
+}
+
+fn format_position(pos: Option<Position>) -> String {
+    match pos {
+        Some(pos) => format!("({}, {})", pos.x, pos.y),
+        None => "none".to_string(),
+    }
+}
+
+fn tile_glyph(tile: &Tile, top_item: Option<&ItemKind>) -> char {
+    if let Some(item) = top_item {
+        return match item {
+            ItemKind::Teleport(teleport) if teleport.up => '<',
+            ItemKind::Teleport(_) => '>',
+            ItemKind::Orb(_) => '*',
+            ItemKind::Key(_) => '-',
+            ItemKind::Holdable(_) => '!',
+            ItemKind::Container(_) => '=',
+        };
+    }
+    if tile.trap.is_some() {
+        return '^';
+    }
+    if tile.door == Some(DoorState::Open) {
+        return '\'';
+    }
+    tile.def().glyph
+}
+
+fn item_name(item: &ItemKind) -> String {
+    match item {
+        ItemKind::Teleport(teleport) if teleport.up => "upstairs".to_string(),
+        ItemKind::Teleport(_) => "downstairs".to_string(),
+        ItemKind::Orb(_) => "orb".to_string(),
+        ItemKind::Key(_) => "key".to_string(),
+        ItemKind::Holdable(group) => format!("{:?}", group),
+        ItemKind::Container(container) => container.base_item.name.clone(),
+    }
+}
+
+/// Renders `map` on the CPU with one flat color per tile and markers for edges,
+/// border anchors and content.
+pub fn export_png(map: &GeneratedMap, path: &Path) {
+    let (width, height) = (map.width() as u32, map.height() as u32);
+    let mut image = Image::gen_image_color(
+        (width * PNG_TILE_PX) as u16,
+        (height * PNG_TILE_PX) as u16,
+        BLACK,
+    );
+
+    for y in 0..height {
+        for x in 0..width {
+            let pos = Position::new(x as usize, y as usize);
+            let tile = &map.tiles[pos];
+            let [r, g, b, _] = tile.def().color;
+            fill_tile(&mut image, x, y, 0, Color::new(r, g, b, 1.0));
+            draw_edges(&mut image, x, y, tile.edge);
+
+            if let Some(trap) = &tile.trap {
+                let color = if trap.hidden {
+                    HIDDEN_TRAP_COLOR
+                } else {
+                    TRAP_COLOR
+                };
+                fill_tile(&mut image, x, y, 2, color);
+            }
+            if let Some(item) = map.tiles.top_item(pos) {
+                let color = match item {
+                    ItemKind::Teleport(teleport) if teleport.up => UPSTAIRS_COLOR,
+                    ItemKind::Teleport(_) => DOWNSTAIRS_COLOR,
+                    _ => ITEM_COLOR,
+                };
+                fill_tile(&mut image, x, y, 2, color);
+            }
+        }
+    }
+
+    for positions in &map.border_positions {
+        for pos in positions {
+            outline_tile(&mut image, pos.x as u32, pos.y as u32, ANCHOR_COLOR);
+        }
+    }
+
+    for monster in &map.monsters {
+        let pos = monster.position;
+        fill_tile(&mut image, pos.x as u32, pos.y as u32, 2, MONSTER_COLOR);
+    }
+
+    image.export_png(&path.to_string_lossy());
+}
+
+// `Image::export_png` flips rows for GL textures, so rows are written bottom-up here
+fn put_pixel(image: &mut Image, x: u32, y: u32, color: Color) {
+    let flipped_y = image.height() as u32 - 1 - y;
+    image.set_pixel(x, flipped_y, color);
+}
+
+fn fill_tile(image: &mut Image, tile_x: u32, tile_y: u32, inset: u32, color: Color) {
+    for py in inset..PNG_TILE_PX - inset {
+        for px in inset..PNG_TILE_PX - inset {
+            put_pixel(
+                image,
+                tile_x * PNG_TILE_PX + px,
+                tile_y * PNG_TILE_PX + py,
+                color,
+            );
+        }
+    }
+}
+
+fn outline_tile(image: &mut Image, tile_x: u32, tile_y: u32, color: Color) {
+    let (left, top) = (tile_x * PNG_TILE_PX, tile_y * PNG_TILE_PX);
+    let last = PNG_TILE_PX - 1;
+    for i in 0..PNG_TILE_PX {
+        put_pixel(image, left + i, top, color);
+        put_pixel(image, left + i, top + last, color);
+        put_pixel(image, left, top + i, color);
+        put_pixel(image, left + last, top + i, color);
+    }
+}
+
+fn draw_edges(image: &mut Image, tile_x: u32, tile_y: u32, edge: EdgeKind) {
+    let (left, top) = (tile_x * PNG_TILE_PX, tile_y * PNG_TILE_PX);
+    let last = PNG_TILE_PX - 1;
+    for i in 0..PNG_TILE_PX {
+        if edge.contains(EdgeKind::TOP) {
+            put_pixel(image, left + i, top, EDGE_COLOR);
+        }
+        if edge.contains(EdgeKind::BOTTOM) {
+            put_pixel(image, left + i, top + last, EDGE_COLOR);
+        }
+        if edge.contains(EdgeKind::LEFT) {
+            put_pixel(image, left, top + i, EDGE_COLOR);
+        }
+        if edge.contains(EdgeKind::RIGHT) {
+            put_pixel(image, left + last, top + i, EDGE_COLOR);
+        }
+    }
+    let corners = [
+        (EdgeKind::TOP_LEFT, left, top),
+        (EdgeKind::TOP_RIGHT, left + last, top),
+        (EdgeKind::BOTTOM_LEFT, left, top + last),
+        (EdgeKind::BOTTOM_RIGHT, left + last, top + last),
+    ];
+    for (corner, x, y) in corners {
+        if edge.contains(corner) {
+            put_pixel(image, x, y, EDGE_COLOR);
+        }
+    }
+}
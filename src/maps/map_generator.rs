@@ -20,36 +20,99 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::collections::HashMap;
-use std::sync::mpsc::{self, Sender};
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use rand::rngs::ThreadRng;
 use rand::{Rng, thread_rng};
 use serde_json::Map;
 
+use crate::boss::{BossEncounter, BossKind};
 use crate::items::base_item::ItemKind;
 use crate::items::collection::{Items, ItemsArc};
-use crate::items::container::Container;
 use crate::lua_interface::LuaInterfaceRc;
-use crate::maps::generated_map::GeneratedMap;
+use crate::maps::connectivity::{self, ReachabilityReport};
+use crate::maps::decoration::decorate;
+use crate::maps::doors::place_doors;
+use crate::maps::generated_map::{GeneratedMap, random_tier_chest};
+use crate::maps::map_algorithms::{
+    LayoutCanvas, LayoutPlacement, MapAlgorithm, MapLayoutGenerator,
+};
 use crate::maps::overworld::OverworldPos;
-use crate::maps::{BorderFlags, GRID_HEIGHT, GRID_WIDTH, MapTheme};
-use crate::monster_kind::{MonsterKind, MonsterKinds, MonsterKindsVecArc};
+use crate::maps::prefab::{Prefab, load_prefabs, stamp_prefabs};
+use crate::maps::scripted_generator::{MapGenScripts, ScriptedGenerator};
+use crate::maps::{
+    ARENA_MARGIN, Border, BorderFlags, DEFAULT_POPULATION_CAP, GRID_HEIGHT, GRID_WIDTH, MapTheme,
+};
+use crate::monster::Monster;
+use crate::monster_kind::{Faction, MonsterKind, MonsterKinds, MonsterKindsVecArc};
 use crate::position::Position;
-use crate::tile::{EdgeKind, Tile, TileFactory, TileKind};
+use crate::tile::{Tile, TileKind};
 use crate::{monster, tile};
 use rand::seq::SliceRandom;
 
+const MAX_GENERATION_ATTEMPTS: usize = 5;
+/// Moves a neighbour's border position onto our own edge, given our map size.
+type MirrorFn = fn(&mut Position, usize, usize);
+/// Item dropped by a boss when no tier has anything to offer (Rusted Short Sword).
+const FALLBACK_BOSS_REWARD: u32 = 1;
+/// Smallest width or height the layout generators can carve. Random walks start
+/// at least three tiles in from the edge and need room to spread their starts apart.
+pub const MIN_MAP_SIZE: usize = 16;
+
 #[derive(Debug, Clone)]
 pub enum MapStatus {
     NotRequested,
     Requested,
-    Ready(Arc<Mutex<GeneratedMap>>),
+    /// Done and waiting in its `MapSlot` for the game to take it.
+    Ready(Arc<MapOutline>),
+    /// The game owns the map now, see `MapGenerator::take`.
+    Taken(Arc<MapOutline>),
+}
+
+/// Status of a map along with the map itself while it is ready. The slot holds
+/// the only copy, so the game can move it out without cloning it.
+#[derive(Debug)]
+pub struct MapSlot {
+    pub status: MapStatus,
+    map: Option<GeneratedMap>,
+}
+
+impl MapSlot {
+    fn new(status: MapStatus) -> Self {
+        Self { status, map: None }
+    }
+
+    fn ready(map: GeneratedMap) -> Self {
+        Self {
+            status: MapStatus::Ready(Arc::new(MapOutline::of(&map))),
+            map: Some(map),
+        }
+    }
 }
 
-type SharedMapStatus = Arc<(Mutex<MapStatus>, Condvar)>;
+/// What is left of a map once the game took it over, enough to line up the
+/// borders of the maps generated around it.
+#[derive(Debug)]
+pub struct MapOutline {
+    pub width: usize,
+    pub height: usize,
+    pub border_positions: [Vec<Position>; 4],
+}
+
+impl MapOutline {
+    pub fn of(map: &GeneratedMap) -> Self {
+        Self {
+            width: map.width(),
+            height: map.height(),
+            border_positions: map.border_positions.clone(),
+        }
+    }
+}
+
+type SharedMapStatus = Arc<Mutex<MapSlot>>;
 
 impl PartialEq for MapStatus {
     fn eq(&self, other: &Self) -> bool {
@@ -58,6 +121,7 @@ impl PartialEq for MapStatus {
             (NotRequested, NotRequested) => true,
             (Requested, Requested) => true,
             (Ready(a), Ready(b)) => Arc::ptr_eq(a, b),
+            (Taken(a), Taken(b)) => Arc::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -69,12 +133,18 @@ pub struct GenerationParams {
     pub walk_length: usize,
     pub min_dist_between_starts: usize,
     pub radius: usize,
+    pub width: usize,
+    pub height: usize,
     pub borders: BorderFlags,
     pub theme: MapTheme,
+    pub algorithm: MapAlgorithm,
     pub predefined_borders: [Vec<Position>; 4],
     pub predefined_start_pos: Option<Position>,
     pub force_regen: bool,
     pub tier: u32,
+    pub boss: Option<Arc<BossKind>>,
+    pub population_cap: usize,
+    pub ambient_light: f32,
 }
 
 impl GenerationParams {
@@ -84,8 +154,11 @@ impl GenerationParams {
             walk_length: 80,
             min_dist_between_starts: 6,
             radius: 1, // 0 = 1x1, 1 = 3x3, or even 2 = 5x5
+            width: GRID_WIDTH,
+            height: GRID_HEIGHT,
             borders: BorderFlags::NONE,
             theme: MapTheme::Any,
+            algorithm: MapAlgorithm::RandomWalk,
             predefined_borders: [
                 Vec::new(), // Up border
                 Vec::new(), // Right border
@@ -95,27 +168,98 @@ impl GenerationParams {
             predefined_start_pos: None,
             force_regen: false,
             tier: 1,
+            boss: None,
+            population_cap: DEFAULT_POPULATION_CAP,
+            ambient_light: 1.0,
         }
     }
 }
 
-enum Command {
-    Generate(OverworldPos, GenerationParams),
-    Stop,
+/// Lower values are generated first, equal ones in the order they were requested.
+pub type GenerationPriority = u32;
+/// For maps the player is not heading towards yet.
+pub const BACKGROUND_PRIORITY: GenerationPriority = GenerationPriority::MAX;
+
+// Workers on top of the main thread, each with its own Lua state for layout scripts
+const MAX_WORKERS: usize = 4;
+
+struct QueuedRequest {
+    priority: GenerationPriority,
+    ticket: u64,
+    opos: OverworldPos,
+    params: Box<GenerationParams>,
+}
+
+#[derive(Default)]
+struct GenerationQueue {
+    pending: Vec<QueuedRequest>,
+    // Ticket of the request currently wanted for each position, queued or being
+    // generated. Results whose ticket is no longer here were cancelled.
+    live: HashMap<OverworldPos, u64>,
+    // Positions a worker is busy with
+    generating: HashSet<OverworldPos>,
+    next_ticket: u64,
+    stopping: bool,
+}
+
+impl GenerationQueue {
+    fn pop_next(&mut self) -> Option<QueuedRequest> {
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| !self.next_to_generating(request.opos))
+            .min_by_key(|(_, request)| (request.priority, request.ticket))
+            .map(|(index, _)| index)?;
+        let request = self.pending.swap_remove(index);
+        self.generating.insert(request.opos);
+        Some(request)
+    }
+
+    /// Neighbouring maps are generated one after the other, so the later one
+    /// can line its borders up with the earlier one.
+    fn next_to_generating(&self, opos: OverworldPos) -> bool {
+        self.generating.iter().any(|pos| {
+            pos.floor == opos.floor && pos.x.abs_diff(opos.x) + pos.y.abs_diff(opos.y) == 1
+        })
+    }
+}
+
+type SharedQueue = Arc<(Mutex<GenerationQueue>, Condvar)>;
+
+/// Timings of the maps generated so far.
+#[derive(Clone, Debug, Default)]
+pub struct GenerationMetrics {
+    pub generated: usize,
+    pub cancelled: usize,
+    pub total_time: Duration,
+    pub longest: Duration,
+}
+
+impl GenerationMetrics {
+    pub fn average(&self) -> Duration {
+        if self.generated == 0 {
+            Duration::ZERO
+        } else {
+            self.total_time / self.generated as u32
+        }
+    }
 }
 
 pub struct MapAssignment {
     pub opos: OverworldPos,
-    pub map: Arc<Mutex<GeneratedMap>>,
+    pub downstair_teleport: Option<Position>,
 }
 
 pub struct MapGenerator {
-    command_tx: Option<Sender<Command>>,
-    thread_handle: Option<JoinHandle<()>>,
+    queue: SharedQueue,
+    workers: Vec<JoinHandle<()>>,
+    metrics: Arc<Mutex<GenerationMetrics>>,
     monster_kinds: MonsterKindsVecArc,
     monster_kinds_by_tier: Vec<Vec<u32>>,
-    tile_factory: Arc<RwLock<TileFactory>>,
     items: ItemsArc,
+    layout_scripts: Vec<String>,
+    prefabs: Arc<Vec<Prefab>>,
     pub map_statuses: Arc<Mutex<HashMap<OverworldPos, SharedMapStatus>>>,
 }
 
@@ -126,18 +270,24 @@ impl MapGenerator {
         items: &ItemsArc,
     ) -> Self {
         let mut mg = Self {
-            command_tx: None,
-            thread_handle: None,
+            queue: Arc::new((Mutex::new(GenerationQueue::default()), Condvar::new())),
+            workers: Vec::new(),
+            metrics: Arc::new(Mutex::new(GenerationMetrics::default())),
             monster_kinds: monster_kinds.clone(),
             monster_kinds_by_tier: Vec::new(),
-            tile_factory: Arc::new(RwLock::new(TileFactory::new())),
             items: items.clone(),
+            layout_scripts: MapGenScripts::available(),
+            prefabs: Arc::new(load_prefabs()),
             map_statuses: Arc::new(Mutex::new(HashMap::new())),
         };
 
         // Initialize monster types by tier
         let monster_kinds_guard = monster_kinds.read().unwrap();
         for mt in monster_kinds_guard.iter() {
+            // Bosses only ever appear in their own arenas, allies are summoned
+            if mt.boss || mt.faction == Faction::Allied {
+                continue;
+            }
             if mg.monster_kinds_by_tier.len() <= mt.tier as usize {
                 mg.monster_kinds_by_tier
                     .resize(mt.tier as usize + 1, Vec::new());
@@ -145,189 +295,412 @@ impl MapGenerator {
             mg.monster_kinds_by_tier[mt.tier as usize].push(mt.id);
         }
 
-        mg.tile_factory.write().unwrap().init().await;
-
         mg
     }
 
+    pub fn random_algorithm(&self, rng: &mut impl Rng) -> MapAlgorithm {
+        MapAlgorithm::random(rng, &self.layout_scripts)
+    }
+
+    /// Size and border anchors of the map at `opos`, once it is generated.
+    pub fn outline(&self, opos: &OverworldPos) -> Option<Arc<MapOutline>> {
+        Self::outline_in(&self.map_statuses, opos)
+    }
+
+    fn outline_in(
+        statuses: &Mutex<HashMap<OverworldPos, SharedMapStatus>>,
+        opos: &OverworldPos,
+    ) -> Option<Arc<MapOutline>> {
+        let shared_status = statuses.lock().unwrap().get(opos).cloned()?;
+        match &shared_status.lock().unwrap().status {
+            MapStatus::Ready(outline) | MapStatus::Taken(outline) => Some(Arc::clone(outline)),
+            _ => None,
+        }
+    }
+
+    /// Lines the borders of `opos` up with the neighbours already generated. A
+    /// neighbour still waiting in the queue reads ours once its turn comes.
+    fn fill_predefined_borders(
+        statuses: &Mutex<HashMap<OverworldPos, SharedMapStatus>>,
+        opos: OverworldPos,
+        params: &mut GenerationParams,
+    ) {
+        use Border::*;
+
+        // The mirror gets our map size, since neighbours may be sized differently
+        const DIRS: &[(Border, i32, i32, MirrorFn)] = &[
+            // neighbour ABOVE us → take its BOTTOM edge, then clamp to y=0
+            (Top, 0, -1, |p, w, _| {
+                p.y = 0;
+                p.x = p.x.clamp(1, w - 2);
+            }),
+            // neighbour to our RIGHT → take its LEFT edge, then clamp to x=width-1
+            (Right, 1, 0, |p, w, h| {
+                p.x = w - 1;
+                p.y = p.y.clamp(1, h - 2);
+            }),
+            // neighbour BELOW us → take its TOP edge, then clamp to y=height-1
+            (Bottom, 0, 1, |p, w, h| {
+                p.y = h - 1;
+                p.x = p.x.clamp(1, w - 2);
+            }),
+            // neighbour to our LEFT → take its RIGHT edge, then clamp to x=0
+            (Left, -1, 0, |p, _, h| {
+                p.x = 0;
+                p.y = p.y.clamp(1, h - 2);
+            }),
+        ];
+
+        for (side, dx, dy, mirror) in DIRS {
+            let nx = opos.x as i32 + dx;
+            let ny = opos.y as i32 + dy;
+            if nx < 0 || ny < 0 {
+                continue;
+            }
+
+            // Positions outside the floor never get a status
+            let neighbor = OverworldPos::new(opos.floor, nx as usize, ny as usize);
+            if let Some(neigh_map) = Self::outline_in(statuses, &neighbor) {
+                let mut vec = neigh_map.border_positions[side.opposite() as usize].clone();
+
+                // convert neighbour coordinates to *our* side
+                for p in &mut vec {
+                    mirror(p, params.width, params.height);
+                }
+                vec.dedup();
+
+                params.predefined_borders[*side as usize] = vec;
+
+                // make sure this side is opened in the new map too
+                params.borders |= match side {
+                    Top => BorderFlags::TOP,
+                    Right => BorderFlags::RIGHT,
+                    Bottom => BorderFlags::BOTTOM,
+                    Left => BorderFlags::LEFT,
+                };
+            }
+        }
+    }
+
     pub fn get_map_status(&self, opos: &OverworldPos) -> MapStatus {
         if let Some(shared_status) = self.map_statuses.lock().unwrap().get(opos) {
-            let (mutex, _) = &**shared_status;
-            mutex.lock().unwrap().clone()
+            shared_status.lock().unwrap().status.clone()
         } else {
             MapStatus::NotRequested
         }
     }
 
-    pub fn set_callback(&mut self, callback: Box<dyn Fn(MapAssignment) + Send + 'static>) {
-        let (command_tx, command_rx) = mpsc::channel::<Command>();
-        self.command_tx = Some(command_tx);
-        let monster_kinds = Arc::clone(&self.monster_kinds);
-        let monster_kinds_by_tier = self.monster_kinds_by_tier.clone();
-        let tile_factory = self.tile_factory.clone();
-        let items = Arc::clone(&self.items);
-
-        let statuses = Arc::clone(&self.map_statuses);
-        self.thread_handle = Some(thread::spawn(move || {
-            for command in command_rx {
-                match command {
-                    Command::Generate(pos, params) => {
-                        let mut map: GeneratedMap = Self::generate_map(&tile_factory, &params);
-
-                        // determine edges based on borders between floor and chasm tiles
-                        for x in 0..GRID_WIDTH {
-                            for y in 0..GRID_HEIGHT {
-                                if map.tiles[Position::new(x, y)].kind() == TileKind::Chasm {
-                                    if y > 0
-                                        && map.tiles[Position::new(x, y - 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::TOP;
-                                    }
-                                    if x + 1 < GRID_WIDTH
-                                        && map.tiles[Position::new(x + 1, y)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::RIGHT;
-                                    }
-                                    if y + 1 < GRID_HEIGHT
-                                        && map.tiles[Position::new(x, y + 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::BOTTOM;
-                                    }
-                                    if x > 0
-                                        && map.tiles[Position::new(x - 1, y)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::LEFT;
-                                    }
-                                    if x + 1 < GRID_WIDTH
-                                        && y > 0
-                                        && map.tiles[Position::new(x + 1, y - 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::TOP_RIGHT;
-                                    }
-                                    if x + 1 < GRID_WIDTH
-                                        && y + 1 < GRID_HEIGHT
-                                        && map.tiles[Position::new(x + 1, y + 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |=
-                                            EdgeKind::BOTTOM_RIGHT;
-                                    }
-                                    if x > 0
-                                        && y + 1 < GRID_HEIGHT
-                                        && map.tiles[Position::new(x - 1, y + 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |=
-                                            EdgeKind::BOTTOM_LEFT;
-                                    }
-                                    if x > 0
-                                        && y > 0
-                                        && map.tiles[Position::new(x - 1, y - 1)].kind()
-                                            == TileKind::Floor
-                                    {
-                                        map.tiles[Position::new(x, y)].edge |= EdgeKind::TOP_LEFT;
-                                    }
-                                }
+    /// Starts the worker threads, `callback` is called from them with every map
+    /// that finishes and was not cancelled in the meantime.
+    pub fn set_callback(&mut self, callback: Box<dyn Fn(MapAssignment) + Send + Sync + 'static>) {
+        let callback: Arc<dyn Fn(MapAssignment) + Send + Sync> = Arc::from(callback);
+        let worker_count = thread::available_parallelism()
+            .map_or(1, |n| n.get().saturating_sub(1))
+            .clamp(1, MAX_WORKERS);
+        println!("[MapGenerator] Starting {} workers", worker_count);
+
+        for worker in 0..worker_count {
+            let callback = Arc::clone(&callback);
+            let queue = Arc::clone(&self.queue);
+            let metrics = Arc::clone(&self.metrics);
+            let monster_kinds = Arc::clone(&self.monster_kinds);
+            let monster_kinds_by_tier = self.monster_kinds_by_tier.clone();
+            let items = Arc::clone(&self.items);
+            let prefabs = Arc::clone(&self.prefabs);
+            let statuses = Arc::clone(&self.map_statuses);
+
+            self.workers.push(thread::spawn(move || {
+                // Lua states are not shareable, so layout scripts get their own on this thread
+                let layout_scripts = MapGenScripts::load();
+
+                loop {
+                    let request = {
+                        let (lock, cvar) = &*queue;
+                        let mut queue = lock.lock().unwrap();
+                        loop {
+                            if queue.stopping {
+                                return;
                             }
+                            if let Some(request) = queue.pop_next() {
+                                break request;
+                            }
+                            queue = cvar.wait(queue).unwrap();
                         }
-
-                        Self::populate_map(
-                            &mut map,
-                            &params,
-                            &monster_kinds,
-                            &monster_kinds_by_tier,
-                            &items,
+                    };
+                    let pos = request.opos;
+                    let mut params = request.params;
+                    Self::fill_predefined_borders(&statuses, pos, &mut params);
+
+                    let started = Instant::now();
+                    let map = Self::build_map(
+                        &params,
+                        &layout_scripts,
+                        &prefabs,
+                        &monster_kinds,
+                        &monster_kinds_by_tier,
+                        &items,
+                    );
+                    let elapsed = started.elapsed();
+
+                    let report = map.reachability_report();
+                    if !report.is_connected() {
+                        eprintln!(
+                            "[MapGenerator] Map {:?} has {} regions, unreachable: {:?}",
+                            pos,
+                            report.regions.len(),
+                            report.unreachable_targets
                         );
-                        let map_arc = Arc::new(Mutex::new(map));
-                        {
-                            let mut statuses = statuses.lock().unwrap();
-                            if let Some(shared_status) = statuses.get(&pos) {
-                                let (mutex, cvar) = &**shared_status;
-                                let mut status = mutex.lock().unwrap();
-                                *status = MapStatus::Ready(Arc::clone(&map_arc));
-                                cvar.notify_all();
-                            } else {
-                                let shared_status = Arc::new((
-                                    Mutex::new(MapStatus::Ready(Arc::clone(&map_arc))),
-                                    Condvar::new(),
-                                ));
-                                statuses.insert(pos, shared_status);
-                            }
+                    }
+
+                    let downstair_teleport = map.downstair_teleport;
+                    {
+                        // Holding the queue while publishing keeps `cancel` from
+                        // slipping in between the check and the status update
+                        let (lock, cvar) = &*queue;
+                        let mut queue = lock.lock().unwrap();
+                        // Its neighbours can be picked up now
+                        queue.generating.remove(&pos);
+                        cvar.notify_all();
+                        if queue.live.get(&pos) != Some(&request.ticket) {
+                            metrics.lock().unwrap().cancelled += 1;
+                            println!("[MapGenerator] Discarding cancelled map {:?}", pos);
+                            continue;
+                        }
+                        queue.live.remove(&pos);
+
+                        let mut statuses = statuses.lock().unwrap();
+                        if let Some(shared_status) = statuses.get(&pos) {
+                            *shared_status.lock().unwrap() = MapSlot::ready(map);
+                        } else {
+                            statuses.insert(pos, Arc::new(Mutex::new(MapSlot::ready(map))));
                         }
-                        callback(MapAssignment {
-                            opos: pos,
-                            map: Arc::clone(&map_arc),
-                        });
                     }
-                    Command::Stop => {
-                        println!("[MapGenerator] Stopping...");
-                        break;
+
+                    {
+                        let mut metrics = metrics.lock().unwrap();
+                        metrics.generated += 1;
+                        metrics.total_time += elapsed;
+                        metrics.longest = metrics.longest.max(elapsed);
                     }
+                    println!(
+                        "[MapGenerator] Worker {} generated {:?} in {:.1} ms",
+                        worker,
+                        pos,
+                        elapsed.as_secs_f64() * 1000.0
+                    );
+
+                    callback(MapAssignment {
+                        opos: pos,
+                        downstair_teleport,
+                    });
                 }
+            }));
+        }
+    }
+
+    /// Synchronous generation on the calling thread, used when exporting maps.
+    pub fn generate_blocking(
+        &self,
+        layout_scripts: &MapGenScripts,
+        params: &GenerationParams,
+    ) -> GeneratedMap {
+        Self::build_map(
+            params,
+            layout_scripts,
+            &self.prefabs,
+            &self.monster_kinds,
+            &self.monster_kinds_by_tier,
+            &self.items,
+        )
+    }
+
+    /// Carves, edges and populates a single map.
+    #[allow(clippy::too_many_arguments)]
+    fn build_map(
+        params: &GenerationParams,
+        layout_scripts: &MapGenScripts,
+        prefabs: &[Prefab],
+        monster_kinds: &MonsterKindsVecArc,
+        monster_kinds_by_tier: &Vec<Vec<u32>>,
+        items: &ItemsArc,
+    ) -> GeneratedMap {
+        let (mut map, placements) = Self::generate_connected_map(layout_scripts, prefabs, params);
+
+        // determine edges based on borders between walkable and chasm tiles
+        for x in 0..map.width() {
+            for y in 0..map.height() {
+                let pos = Position::new(x, y);
+                let edge = map.tiles.chasm_edges(pos);
+                map.tiles[pos].edge |= edge;
             }
-        }));
+        }
+
+        Self::populate_map(
+            &mut map,
+            params,
+            &placements,
+            monster_kinds,
+            monster_kinds_by_tier,
+            items,
+        );
+        map
     }
 
-    pub fn request_generation(&mut self, opos: OverworldPos, params: GenerationParams) {
+    /// Queues a map for generation. Asking again for a map still in the queue only
+    /// moves it up when the new priority is more urgent.
+    pub fn request_generation(
+        &mut self,
+        opos: OverworldPos,
+        params: GenerationParams,
+        priority: GenerationPriority,
+    ) {
         let mut statuses = self.map_statuses.lock().unwrap();
         let entry = statuses
             .entry(opos)
-            .or_insert_with(|| Arc::new((Mutex::new(MapStatus::NotRequested), Condvar::new())));
+            .or_insert_with(|| Arc::new(Mutex::new(MapSlot::new(MapStatus::NotRequested))));
 
-        let (lock, _) = &**entry;
-        let mut state = lock.lock().unwrap();
+        let mut state = entry.lock().unwrap();
 
-        match *state {
+        match state.status {
             MapStatus::NotRequested => {
-                *state = MapStatus::Requested;
+                state.status = MapStatus::Requested;
+            }
+            MapStatus::Requested => {
+                drop(state);
+                drop(statuses);
+                self.prioritize(opos, priority);
+                return;
             }
-            MapStatus::Requested | MapStatus::Ready(_) => {
-                return; // Already requested or done
+            MapStatus::Ready(_) | MapStatus::Taken(_) => {
+                return; // Already done
             }
         }
 
-        drop(state); // release lock before sending
+        drop(state); // release lock before queueing
+        drop(statuses);
+
+        println!(
+            "[MapGenerator] Requesting generation for {:?} (priority {})",
+            opos, priority
+        );
+
+        let (lock, cvar) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        queue.live.insert(opos, ticket);
+        queue.pending.push(QueuedRequest {
+            priority,
+            ticket,
+            opos,
+            params: Box::new(params),
+        });
+        cvar.notify_one();
+    }
 
-        println!("[MapGenerator] Requesting generation for {:?}", opos);
+    /// Moves a queued map up, for when the player is about to need it.
+    pub fn prioritize(&self, opos: OverworldPos, priority: GenerationPriority) {
+        let mut queue = self.queue.0.lock().unwrap();
+        if let Some(request) = queue
+            .pending
+            .iter_mut()
+            .find(|request| request.opos == opos)
+        {
+            request.priority = request.priority.min(priority);
+        }
+    }
 
-        if let Some(ref tx) = self.command_tx {
-            let _ = tx.send(Command::Generate(opos, params));
+    /// Drops the request for `opos`, whether it is still queued or being generated,
+    /// along with its status.
+    pub fn cancel(&self, opos: OverworldPos) {
+        let mut queue = self.queue.0.lock().unwrap();
+        let queued = queue.pending.len();
+        queue.pending.retain(|request| request.opos != opos);
+        if queue.pending.len() < queued {
+            self.metrics.lock().unwrap().cancelled += 1;
+        }
+        queue.live.remove(&opos);
+        if let Some(shared_status) = self.map_statuses.lock().unwrap().remove(&opos) {
+            // Anyone still holding on to the slot finds the map gone
+            *shared_status.lock().unwrap() = MapSlot::new(MapStatus::NotRequested);
+        }
+    }
+
+    /// Cancels every request whose position matches `filter` that no worker has
+    /// picked up yet. Maps being generated or already done are kept.
+    pub fn cancel_queued(&self, filter: impl Fn(&OverworldPos) -> bool) {
+        let queued: Vec<OverworldPos> = self
+            .queue
+            .0
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .map(|request| request.opos)
+            .filter(|pos| filter(pos))
+            .collect();
+        for opos in queued {
+            println!("[MapGenerator] Cancelling queued map {:?}", opos);
+            self.cancel(opos);
         }
     }
 
+    /// Hands a finished map over to the game, leaving its outline behind.
+    pub fn take(&self, opos: OverworldPos) -> Option<GeneratedMap> {
+        let shared_status = self.map_statuses.lock().unwrap().get(&opos).cloned()?;
+        let mut slot = shared_status.lock().unwrap();
+        let MapStatus::Ready(outline) = &slot.status else {
+            return None;
+        };
+        slot.status = MapStatus::Taken(Arc::clone(outline));
+        slot.map.take()
+    }
+
+    pub fn metrics(&self) -> GenerationMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
     pub fn stop(&mut self) {
-        if let Some(ref tx) = self.command_tx {
-            let _ = tx.send(Command::Stop);
+        if self.workers.is_empty() {
+            return;
         }
-        if let Some(handle) = self.thread_handle.take() {
+        println!("[MapGenerator] Stopping...");
+        {
+            let (lock, cvar) = &*self.queue;
+            lock.lock().unwrap().stopping = true;
+            cvar.notify_all();
+        }
+        for handle in self.workers.drain(..) {
             let _ = handle.join();
         }
+
+        let metrics = self.metrics();
+        println!(
+            "[MapGenerator] Generated {} maps, {} cancelled, average {:.1} ms, longest {:.1} ms",
+            metrics.generated,
+            metrics.cancelled,
+            metrics.average().as_secs_f64() * 1000.0,
+            metrics.longest.as_secs_f64() * 1000.0
+        );
     }
 
-    fn carve_tile(
+    pub(super) fn carve_tile(
         tiles: &mut Vec<Vec<Tile>>,
-        tile_factory: &Arc<RwLock<TileFactory>>,
         x: usize,
         y: usize,
         walkable_cache: &mut Vec<Position>,
     ) {
-        if x >= GRID_WIDTH || y >= GRID_HEIGHT {
+        if x >= tiles.len() || y >= tiles[0].len() {
             return;
         }
-        if tiles[x][y].kind() != TileKind::Floor {
-            tiles[x][y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+        if !tiles[x][y].def().walkable {
+            tiles[x][y] = Tile::new(TileKind::FLOOR);
             walkable_cache.push(Position { x, y });
         }
     }
 
-    fn carve_straight_path(
+    pub(super) fn carve_straight_path(
         tiles: &mut Vec<Vec<Tile>>,
-        tile_factory: &Arc<RwLock<TileFactory>>,
         start: Position,
         end: Position,
         walkable_cache: &mut Vec<Position>,
@@ -336,77 +709,51 @@ impl MapGenerator {
         let mut y = start.y;
 
         while x != end.x {
-            if tiles[x][y].kind() != TileKind::Floor {
-                tiles[x][y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+            if !tiles[x][y].def().walkable {
+                tiles[x][y] = Tile::new(TileKind::FLOOR);
                 walkable_cache.push(Position { x, y });
             }
             x = if end.x > x { x + 1 } else { x - 1 };
         }
 
         while y != end.y {
-            if tiles[x][y].kind() != TileKind::Floor {
-                tiles[x][y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+            if !tiles[x][y].def().walkable {
+                tiles[x][y] = Tile::new(TileKind::FLOOR);
                 walkable_cache.push(Position { x, y });
             }
             y = if end.y > y { y + 1 } else { y - 1 };
         }
     }
 
-    fn carve_jagged_path(
-        tiles: &mut Vec<Vec<Tile>>,
-        tile_factory: &Arc<RwLock<TileFactory>>,
+    /// Walks a jagged path from `current` to `goal`, handing every tile it
+    /// covers to `carve`.
+    pub(super) fn carve_jagged_path(
+        width: usize,
+        height: usize,
         mut current: Position,
         goal: Position,
-        walkable_cache: &mut Vec<Position>,
         rng: &mut ThreadRng,
         radius: usize, // 0 = 1x1, 1 = 3x3, or even 2 = 5x5
+        mut carve: impl FnMut(usize, usize),
     ) {
         while current != goal {
             if radius == 0 {
                 // Exact 1x1
-                Self::carve_tile(tiles, tile_factory, current.x, current.y, walkable_cache);
+                carve(current.x, current.y);
             } else if radius == 1 {
                 // Exact 2x2 (square)
-                Self::carve_tile(tiles, tile_factory, current.x, current.y, walkable_cache);
-                Self::carve_tile(
-                    tiles,
-                    tile_factory,
-                    current.x + 1,
-                    current.y,
-                    walkable_cache,
-                );
-                Self::carve_tile(
-                    tiles,
-                    tile_factory,
-                    current.x,
-                    current.y + 1,
-                    walkable_cache,
-                );
-                Self::carve_tile(
-                    tiles,
-                    tile_factory,
-                    current.x + 1,
-                    current.y + 1,
-                    walkable_cache,
-                );
+                carve(current.x, current.y);
+                carve(current.x + 1, current.y);
+                carve(current.x, current.y + 1);
+                carve(current.x + 1, current.y + 1);
             } else {
                 // Circular area
                 for dx in -(radius as isize)..=(radius as isize) {
                     for dy in -(radius as isize)..=(radius as isize) {
                         let nx = current.x as isize + dx;
                         let ny = current.y as isize + dy;
-                        if nx >= 0
-                            && ny >= 0
-                            && nx < GRID_WIDTH as isize
-                            && ny < GRID_HEIGHT as isize
-                        {
-                            Self::carve_tile(
-                                tiles,
-                                tile_factory,
-                                nx as usize,
-                                ny as usize,
-                                walkable_cache,
-                            );
+                        if nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize {
+                            carve(nx as usize, ny as usize);
                         }
                     }
                 }
@@ -433,20 +780,19 @@ impl MapGenerator {
                 step_y = dy.signum();
             }
 
-            let new_x = (current.x as isize + step_x).clamp(0, GRID_WIDTH as isize - 1) as usize;
-            let new_y = (current.y as isize + step_y).clamp(0, GRID_HEIGHT as isize - 1) as usize;
+            let new_x = (current.x as isize + step_x).clamp(0, width as isize - 1) as usize;
+            let new_y = (current.y as isize + step_y).clamp(0, height as isize - 1) as usize;
             current = Position { x: new_x, y: new_y };
         }
     }
 
     fn place_border_anchors(
         tiles: &mut Vec<Vec<Tile>>,
-        tile_factory: &Arc<RwLock<TileFactory>>,
         params: &GenerationParams,
     ) -> Vec<(Position, Position)> {
         let mut anchors = Vec::new();
-        let width = GRID_WIDTH;
-        let height = GRID_HEIGHT;
+        let width = params.width;
+        let height = params.height;
         let mut rng = thread_rng();
         let borders = &params.borders;
 
@@ -457,7 +803,7 @@ impl MapGenerator {
         if borders.contains(BorderFlags::TOP) {
             if !params.predefined_borders[0].is_empty() {
                 for &pos in &params.predefined_borders[0] {
-                    tiles[pos.x][pos.y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[pos.x][pos.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((pos, Position::new(pos.x, pos.y + 1)));
                 }
             } else {
@@ -466,8 +812,7 @@ impl MapGenerator {
                 for dx in 0..anchor_width {
                     let border = Position::new(start_x + dx, 0);
                     let neighbor = Position::new(border.x, border.y + 1);
-                    tiles[border.x][border.y] =
-                        tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[border.x][border.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((border, neighbor));
                 }
             }
@@ -475,7 +820,7 @@ impl MapGenerator {
         if borders.contains(BorderFlags::RIGHT) {
             if !params.predefined_borders[1].is_empty() {
                 for &pos in &params.predefined_borders[1] {
-                    tiles[pos.x][pos.y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[pos.x][pos.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((pos, Position::new(pos.x - 1, pos.y)));
                 }
             } else {
@@ -484,8 +829,7 @@ impl MapGenerator {
                 for dy in 0..anchor_height {
                     let border = Position::new(width - 1, start_y + dy);
                     let neighbor = Position::new(border.x - 1, border.y);
-                    tiles[border.x][border.y] =
-                        tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[border.x][border.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((border, neighbor));
                 }
             }
@@ -493,7 +837,7 @@ impl MapGenerator {
         if borders.contains(BorderFlags::BOTTOM) {
             if !params.predefined_borders[2].is_empty() {
                 for &pos in &params.predefined_borders[2] {
-                    tiles[pos.x][pos.y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[pos.x][pos.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((pos, Position::new(pos.x, pos.y - 1)));
                 }
             } else {
@@ -502,8 +846,7 @@ impl MapGenerator {
                 for dx in 0..anchor_width {
                     let border = Position::new(start_x + dx, height - 1);
                     let neighbor = Position::new(border.x, border.y - 1);
-                    tiles[border.x][border.y] =
-                        tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[border.x][border.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((border, neighbor));
                 }
             }
@@ -511,7 +854,7 @@ impl MapGenerator {
         if borders.contains(BorderFlags::LEFT) {
             if !params.predefined_borders[3].is_empty() {
                 for &pos in &params.predefined_borders[3] {
-                    tiles[pos.x][pos.y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[pos.x][pos.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((pos, Position::new(pos.x + 1, pos.y)));
                 }
             } else {
@@ -520,8 +863,7 @@ impl MapGenerator {
                 for dy in 0..anchor_height {
                     let border = Position::new(0, start_y + dy);
                     let neighbor = Position::new(border.x + 1, border.y);
-                    tiles[border.x][border.y] =
-                        tile_factory.read().unwrap().create_tile(TileKind::Floor);
+                    tiles[border.x][border.y] = Tile::new(TileKind::FLOOR);
                     anchors.push((border, neighbor));
                 }
             }
@@ -530,147 +872,165 @@ impl MapGenerator {
         anchors
     }
 
+    /// Generates a map whose floor is a single region. Maps that come out split
+    /// are regenerated when `force_regen` is set, and repaired otherwise.
+    fn generate_connected_map(
+        layout_scripts: &MapGenScripts,
+        prefabs: &[Prefab],
+        params: &GenerationParams,
+    ) -> (GeneratedMap, Vec<LayoutPlacement>) {
+        let mut attempt = 1;
+        loop {
+            let allow_regen = params.force_regen && attempt < MAX_GENERATION_ATTEMPTS;
+            if let Some(generated) =
+                Self::generate_map(layout_scripts, prefabs, params, allow_regen)
+            {
+                return generated;
+            }
+            println!(
+                "[MapGenerator] Disconnected layout, regenerating (attempt {})",
+                attempt
+            );
+            attempt += 1;
+        }
+    }
+
+    /// Returns `None` when the layout is disconnected and `allow_regen` is set,
+    /// otherwise disconnected regions get linked back together.
     fn generate_map(
-        tile_factory: &Arc<RwLock<TileFactory>>,
+        layout_scripts: &MapGenScripts,
+        prefabs: &[Prefab],
         params: &GenerationParams,
-    ) -> GeneratedMap {
+        allow_regen: bool,
+    ) -> Option<(GeneratedMap, Vec<LayoutPlacement>)> {
         let mut rng = thread_rng();
 
         let tile_type = match params.theme {
             MapTheme::Any => {
                 if rng.gen_bool(0.5) {
-                    TileKind::Chasm
+                    TileKind::CHASM
                 } else {
-                    TileKind::Wall
+                    TileKind::WALL
                 }
             }
-            MapTheme::Chasm => TileKind::Chasm,
-            MapTheme::Wall => TileKind::Wall,
+            MapTheme::Chasm => TileKind::CHASM,
+            MapTheme::Wall | MapTheme::Arena => TileKind::WALL,
         };
+        let is_arena = matches!(params.theme, MapTheme::Arena);
 
-        let mut tiles = vec![
-            vec![tile_factory.read().unwrap().create_tile(tile_type); GRID_HEIGHT];
-            GRID_WIDTH
-        ];
+        let mut tiles = vec![vec![Tile::new(tile_type); params.height]; params.width];
         let mut walkable_cache = Vec::new();
-        let mut visited = vec![vec![false; GRID_HEIGHT]; GRID_WIDTH];
 
         //let borders = Self::choose_border_exits(params.exits as usize);
-        let anchor_pairs = Self::place_border_anchors(&mut tiles, tile_factory, params);
-
-        let num_walks: usize = params.num_walks;
-        let walk_length: usize = params.walk_length;
-        let min_distance_between_starts: usize = params.min_dist_between_starts;
+        let anchor_pairs = Self::place_border_anchors(&mut tiles, params);
 
-        let mut start_positions = Vec::new();
+        // Positions the layout has to connect, whatever algorithm carves it
+        let mut anchors = Vec::new();
 
         if let Some(predefined_start_pos) = params.predefined_start_pos {
-            start_positions.push(predefined_start_pos);
+            anchors.push(predefined_start_pos);
             println!(
                 "Using predefined start position: {:?}",
                 predefined_start_pos
             );
         }
 
-        for &(_, neighbor) in &anchor_pairs {
-            if !visited[neighbor.x][neighbor.y] {
-                tiles[neighbor.x][neighbor.y] =
-                    tile_factory.read().unwrap().create_tile(TileKind::Floor);
-                walkable_cache.push(neighbor);
-                visited[neighbor.x][neighbor.y] = true;
-            }
-            start_positions.push(neighbor);
+        if is_arena {
+            // Every entrance gets carved towards the middle of the arena
+            anchors.push(Position::new(params.width / 2, params.height / 2));
         }
 
-        for i in 0..num_walks + start_positions.len() {
-            let mut x;
-            let mut y;
-
-            if i >= start_positions.len() {
-                // Ensure new walk starts away from previous walks
-                loop {
-                    x = rng.gen_range(3..GRID_WIDTH - 3);
-                    y = rng.gen_range(3..GRID_HEIGHT - 3);
-
-                    let too_close = start_positions.iter().any(|&pos: &Position| {
-                        let dx = pos.x as isize - x as isize;
-                        let dy = pos.y as isize - y as isize;
-                        dx.abs() + dy.abs() < min_distance_between_starts as isize
-                    });
+        for &(_, neighbor) in &anchor_pairs {
+            Self::carve_tile(&mut tiles, neighbor.x, neighbor.y, &mut walkable_cache);
+            anchors.push(neighbor);
+        }
 
-                    if !too_close {
-                        break;
-                    }
+        let algorithm = if is_arena {
+            MapAlgorithm::RandomWalk
+        } else {
+            params.algorithm.clone()
+        };
+        println!("[MapGenerator] Carving layout with {:?}", algorithm);
+        let mut footprints = Vec::new();
+        let placements = {
+            let mut canvas = LayoutCanvas::new(&mut tiles, &mut walkable_cache, tile_type);
+            let generator: Box<dyn MapLayoutGenerator + '_> = match &algorithm {
+                MapAlgorithm::Scripted(name) => {
+                    Box::new(ScriptedGenerator::new(layout_scripts, name))
                 }
-
-                start_positions.push(Position { x, y });
-            } else {
-                // Use existing start position
-                x = start_positions[i].x;
-                y = start_positions[i].y;
+                builtin => builtin
+                    .generator()
+                    .expect("Built-in layouts have a generator"),
+            };
+            generator.carve(&mut canvas, &anchors, params, &mut rng);
+
+            if !is_arena {
+                footprints = stamp_prefabs(&mut canvas, prefabs, &anchors, params.tier, &mut rng);
             }
 
-            // Apply random walk
-            for _ in 0..walk_length {
-                if x >= GRID_WIDTH || y >= GRID_HEIGHT {
-                    break;
+            let mut placements = canvas.take_placements();
+            let targets: Vec<Position> = anchors
+                .iter()
+                .copied()
+                .chain(anchor_pairs.iter().map(|&(border, _)| border))
+                .chain(placements.iter().map(|placement| placement.position()))
+                .collect();
+            let report = ReachabilityReport::analyze(
+                canvas.width(),
+                canvas.height(),
+                |pos| canvas.is_floor(pos),
+                &targets,
+            );
+            if !report.is_connected() {
+                if allow_regen {
+                    return None;
                 }
+                let repaired = connectivity::repair(&mut canvas, &targets);
+                println!(
+                    "[MapGenerator] Joined {} regions, {} left",
+                    report.regions.len(),
+                    repaired.regions.len()
+                );
+            }
 
-                if !visited[x][y] {
-                    tiles[x][y] = tile_factory.read().unwrap().create_tile(TileKind::Floor);
-                    walkable_cache.push(Position { x, y });
-                    visited[x][y] = true;
-                }
+            if !is_arena {
+                place_doors(&mut canvas, &anchors, &targets, &footprints, &mut rng);
+                let keys = canvas.take_placements();
+                let reserved: Vec<Position> = targets
+                    .iter()
+                    .copied()
+                    .chain(keys.iter().map(|placement| placement.position()))
+                    .collect();
+                placements.extend(keys);
+                decorate(&mut canvas, &reserved, &footprints, params.tier, &mut rng);
+            }
+            placements
+        };
 
-                match rng.gen_range(0..8) {
-                    0 if x > 1 => x -= 1,
-                    1 if x < GRID_WIDTH - 2 => x += 1,
-                    2 if y > 1 => y -= 1,
-                    3 if y < GRID_HEIGHT - 2 => y += 1,
-                    4 if x > 1 && y > 1 => {
-                        x -= 1;
-                        y -= 1;
-                    }
-                    5 if x < GRID_WIDTH - 2 && y > 1 => {
-                        x += 1;
-                        y -= 1;
-                    }
-                    6 if x > 1 && y < GRID_HEIGHT - 2 => {
-                        x -= 1;
-                        y += 1;
-                    }
-                    7 if x < GRID_WIDTH - 2 && y < GRID_HEIGHT - 2 => {
-                        x += 1;
-                        y += 1;
-                    }
-                    _ => {}
+        if is_arena {
+            for x in ARENA_MARGIN..params.width - ARENA_MARGIN {
+                for y in ARENA_MARGIN..params.height - ARENA_MARGIN {
+                    Self::carve_tile(&mut tiles, x, y, &mut walkable_cache);
                 }
             }
         }
 
-        start_positions.sort_by_key(|pos| pos.x + pos.y);
-
-        for i in 1..start_positions.len() {
-            let prev = start_positions[i - 1];
-            let current = start_positions[i];
-
-            //carve_path_between(&mut tiles, prev, current, &mut walkable_cache);
-            Self::carve_jagged_path(
-                &mut tiles,
-                tile_factory,
-                prev,
-                current,
-                &mut walkable_cache,
-                &mut rng,
-                params.radius,
-            );
-        }
+        // Tiles can be carved, filled and carved again by the layout
+        let mut seen = HashSet::new();
+        walkable_cache.retain(|&pos| seen.insert(pos));
 
         let mut available_walkable_cache = walkable_cache.clone();
 
+        // Vault contents are hand placed, random content stays outside
+        available_walkable_cache
+            .retain(|&p| !footprints.iter().any(|footprint| footprint.contains(p)));
+        // Nothing spawns standing in lava or in a doorway
+        available_walkable_cache
+            .retain(|&p| tiles[p.x][p.y].def().on_step.is_none() && tiles[p.x][p.y].door.is_none());
+
         // Exclude borders from available walkable positions
         for (pos, neighbor) in &anchor_pairs {
-            if tiles[pos.x][pos.y].kind() == TileKind::Floor {
+            if tiles[pos.x][pos.y].def().walkable {
                 available_walkable_cache.retain(|&p| p != *pos && p != *neighbor);
             }
         }
@@ -678,52 +1038,81 @@ impl MapGenerator {
         available_walkable_cache.shuffle(&mut rng);
         let mut map =
             GeneratedMap::new(params.tier, tiles, walkable_cache, available_walkable_cache);
+        map.population_cap = params.population_cap;
+        map.ambient_light = params.ambient_light;
 
-        for x in 0..GRID_WIDTH {
-            if map.tiles[Position::new(x, 0)].kind() == TileKind::Floor {
+        for x in 0..params.width {
+            if map.tiles[Position::new(x, 0)].def().walkable {
                 map.border_positions[0].push(Position { x, y: 0 });
             }
-            if map.tiles[Position::new(x, GRID_HEIGHT - 1)].kind() == TileKind::Floor {
+            if map.tiles[Position::new(x, params.height - 1)]
+                .def()
+                .walkable
+            {
                 map.border_positions[2].push(Position {
                     x,
-                    y: GRID_HEIGHT - 1,
+                    y: params.height - 1,
                 });
             }
         }
-        for y in 0..GRID_HEIGHT {
-            if map.tiles[Position::new(0, y)].kind() == TileKind::Floor {
+        for y in 0..params.height {
+            if map.tiles[Position::new(0, y)].def().walkable {
                 map.border_positions[3].push(Position { x: 0, y });
             }
-            if map.tiles[Position::new(GRID_WIDTH - 1, y)].kind() == TileKind::Floor {
+            if map.tiles[Position::new(params.width - 1, y)].def().walkable {
                 map.border_positions[1].push(Position {
-                    x: GRID_WIDTH - 1,
+                    x: params.width - 1,
                     y,
                 });
             }
         }
 
-        map
+        Some((map, placements))
     }
 
     fn populate_map(
         map: &mut GeneratedMap,
         params: &GenerationParams,
+        placements: &[LayoutPlacement],
         monster_kinds: &MonsterKindsVecArc,
         monster_kinds_by_tier: &Vec<Vec<u32>>,
         items_arc: &ItemsArc,
     ) {
+        if let Some(boss) = &params.boss {
+            Self::populate_arena(map, boss, monster_kinds, items_arc);
+            return;
+        }
+
         let monster_kinds_guard = monster_kinds.read().unwrap();
-        map.add_random_monsters(&*monster_kinds_guard, monster_kinds_by_tier, params.tier);
+        // Whatever the layout asked for goes first so random content never takes its spot
+        map.add_layout_placements(
+            placements,
+            &monster_kinds_guard,
+            monster_kinds_by_tier,
+            &items_arc.read().unwrap(),
+            params.tier,
+            params.borders.contains(BorderFlags::DOWN),
+        );
+        map.add_random_monsters(
+            &*monster_kinds_guard,
+            monster_kinds_by_tier,
+            &items_arc.read().unwrap(),
+            params.tier,
+        );
 
         let mut len = map.available_walkable_cache.len();
-        let mut positions: Vec<Position> = map
-            .available_walkable_cache
-            .drain(len.saturating_sub(1)..)
-            .collect();
+        let mut positions: Vec<Position>;
 
-        for pos in positions {
-            map.tiles[pos].add_teleport();
-            map.downstair_teleport = Some(pos);
+        if params.borders.contains(BorderFlags::DOWN) && map.downstair_teleport.is_none() {
+            positions = map
+                .available_walkable_cache
+                .drain(len.saturating_sub(1)..)
+                .collect();
+
+            for pos in positions {
+                map.tiles.add_teleport(pos);
+                map.downstair_teleport = Some(pos);
+            }
         }
 
         len = map.available_walkable_cache.len();
@@ -733,43 +1122,89 @@ impl MapGenerator {
             .collect();
 
         for pos in positions {
-            map.tiles[pos].add_orb();
+            map.tiles.add_orb(pos);
         }
 
         if params.tier > 1 {
             let chest_pos_opt = map.available_walkable_cache.pop();
             if let Some(chest_pos) = chest_pos_opt {
-                let mut container = Container::new();
-
-                let items = items_arc.read().unwrap();
-
-                // get 3 random items from items of the specified tier
-                let mut rng = thread_rng();
-                let items_of_tier: Vec<u32> = items
-                    .items_ids_by_tier
-                    .get(params.tier as usize)
-                    .cloned()
-                    .unwrap_or_default()
-                    .into_iter()
-                    .collect();
+                let container = random_tier_chest(&items_arc.read().unwrap(), params.tier);
+                map.tiles
+                    .add_item(chest_pos, ItemKind::Container(container));
+            }
+        }
 
-                let selected_items = items_of_tier
-                    .choose_multiple(&mut rng, 3)
-                    .cloned()
-                    .collect::<Vec<u32>>();
+        map.add_random_traps(params.tier, params.borders.contains(BorderFlags::DOWN));
+    }
 
-                // Add them to the container, ensuring they are unique (0 to 3, depending on availability)
-                for item_id in selected_items {
-                    if let Some(_) = items.items_by_id.get(&item_id) {
-                        container.add_item(item_id);
-                    }
-                }
+    fn populate_arena(
+        map: &mut GeneratedMap,
+        boss: &BossKind,
+        monster_kinds: &MonsterKindsVecArc,
+        items_arc: &ItemsArc,
+    ) {
+        let kind = {
+            let monster_kinds_guard = monster_kinds.read().unwrap();
+            monster_kinds_guard
+                .iter()
+                .find(|mt| mt.id == boss.monster_kind)
+                .cloned()
+        };
+
+        let Some(kind) = kind else {
+            println!(
+                "Boss {} references unknown monster kind {}",
+                boss.name, boss.monster_kind
+            );
+            return;
+        };
+
+        println!("[MapGenerator] Placing boss {} ({})", boss.name, boss.id);
+
+        let center = Position::new(map.width() / 2, map.height() / 2);
+        // The stairs only show up once the boss is dead, right behind where it stood
+        let stairs_pos = Position::new(center.x, center.y + 3);
+        map.available_walkable_cache
+            .retain(|&p| p != center && p != stairs_pos);
+
+        let mut monster = Monster::new(center, kind);
+        monster.name = boss.name.clone();
+        let monster_id = monster.id;
+        map.tiles.set_creature(center, monster_id);
+        map.monsters.push(monster);
 
-                map.tiles[chest_pos]
-                    .items
-                    .push(ItemKind::Container(container));
+        // Fall back to lower tiers when the reward tier has no items yet
+        let items = items_arc.read().unwrap();
+        let mut rng = thread_rng();
+        let mut reward = Vec::new();
+        for reward_tier in (0..=boss.reward_tier as usize).rev() {
+            reward = items
+                .items_ids_by_tier
+                .get(reward_tier)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<Vec<u32>>()
+                .choose_multiple(&mut rng, boss.reward_count)
+                .cloned()
+                .collect::<Vec<u32>>();
+            if !reward.is_empty() {
+                break;
             }
         }
+        if reward.is_empty() {
+            reward.push(FALLBACK_BOSS_REWARD);
+        }
+
+        map.boss = Some(BossEncounter {
+            monster_id,
+            name: boss.name.clone(),
+            phases: boss.phases.clone(),
+            phase: 0,
+            stairs_pos,
+            reward,
+            defeated: false,
+        });
     }
 }
 
@@ -778,3 +1213,39 @@ impl Drop for MapGenerator {
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_with(positions: &[OverworldPos]) -> GenerationQueue {
+        let mut queue = GenerationQueue::default();
+        for &opos in positions {
+            let ticket = queue.next_ticket;
+            queue.next_ticket += 1;
+            queue.pending.push(QueuedRequest {
+                priority: BACKGROUND_PRIORITY,
+                ticket,
+                opos,
+                params: Box::new(GenerationParams::default()),
+            });
+        }
+        queue
+    }
+
+    #[test]
+    fn neighbours_are_not_generated_together() {
+        let center = OverworldPos::new(0, 1, 1);
+        let right = OverworldPos::new(0, 2, 1);
+        let far = OverworldPos::new(0, 3, 3);
+        let mut queue = queue_with(&[center, right, far]);
+
+        assert_eq!(queue.pop_next().map(|request| request.opos), Some(center));
+        // The right neighbour waits for the center, the far map does not
+        assert_eq!(queue.pop_next().map(|request| request.opos), Some(far));
+        assert!(queue.pop_next().is_none());
+
+        queue.generating.remove(&center);
+        assert_eq!(queue.pop_next().map(|request| request.opos), Some(right));
+    }
+}
@@ -20,12 +20,22 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod connectivity;
+mod decoration;
+mod doors;
+pub mod explored;
 pub mod generated_map;
+pub mod lighting;
 pub mod map;
-mod map_generator;
+mod map_algorithms;
+pub mod map_export;
+pub mod map_generator;
 pub mod navigator;
 pub mod overworld;
 pub mod overworld_generator;
+mod prefab;
+mod scripted_generator;
+pub mod visibility;
 
 use bitflags::bitflags;
 
@@ -34,6 +44,19 @@ pub enum MapTheme {
     Any,
     Chasm,
     Wall,
+    Arena,
+}
+
+impl MapTheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(MapTheme::Any),
+            "chasm" => Some(MapTheme::Chasm),
+            "wall" => Some(MapTheme::Wall),
+            "arena" => Some(MapTheme::Arena),
+            _ => None,
+        }
+    }
 }
 
 bitflags! {
@@ -68,5 +91,8 @@ impl Border {
 }
 
 pub const TILE_SIZE: f32 = 32.0;
+// Default map size, each map carries its own in its tiles
 pub const GRID_WIDTH: usize = 33;
 pub const GRID_HEIGHT: usize = 33;
+pub const ARENA_MARGIN: usize = 8;
+pub const DEFAULT_POPULATION_CAP: usize = 25;
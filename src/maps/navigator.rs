@@ -21,14 +21,61 @@
 // SOFTWARE.
 
 use pathfinding::prelude::astar;
-use std::collections::HashSet;
 
-use crate::{
-    maps::{GRID_HEIGHT, GRID_WIDTH},
-    position::Position,
-    tile::TileKind,
-    tile_map::TileMap,
-};
+use crate::maps::visibility::VisibilityGrid;
+use crate::{position::Position, scheduler::ACTION_COST_NORMAL, tile_map::TileMap};
+
+/// How `Navigator::compute_fov` decides what is in view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FovMode {
+    /// Recursive shadowcasting. Generous around corners, but a tile in view
+    /// can't always see the origin back.
+    Permissive,
+    /// Symmetric shadowcasting: a tile is in view only if the origin would be
+    /// in view from it too.
+    Symmetric,
+}
+
+/// Slope of a shadowcasting ray as `num / den`, `den` always positive.
+#[derive(Clone, Copy)]
+struct Slope {
+    num: isize,
+    den: isize,
+}
+
+/// One row of a symmetric shadowcasting quadrant, `depth` tiles away from the origin.
+#[derive(Clone, Copy)]
+struct Row {
+    depth: isize,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    /// First column of the row, rounding ties towards the end slope.
+    fn min_col(&self) -> isize {
+        (2 * self.depth * self.start.num + self.start.den).div_euclid(2 * self.start.den)
+    }
+
+    /// Last column of the row, rounding ties towards the start slope.
+    fn max_col(&self) -> isize {
+        -((self.end.den - 2 * self.depth * self.end.num).div_euclid(2 * self.end.den))
+    }
+
+    /// Whether the center of `col` lies between the row's slopes, which is what
+    /// makes the origin visible from it in return.
+    fn is_symmetric(&self, col: isize) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Self {
+        Self {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+}
 
 pub struct Navigator {}
 
@@ -37,6 +84,18 @@ impl Navigator {
         start_pos: Position,
         goal_pos: Position,
         is_walkable: impl Fn(Position) -> bool,
+    ) -> Option<Vec<Position>> {
+        Self::find_weighted_path(start_pos, goal_pos, |pos| {
+            is_walkable(pos).then_some(ACTION_COST_NORMAL)
+        })
+    }
+
+    /// Like `find_path`, but `step_cost` prices entering each tile, with
+    /// `ACTION_COST_NORMAL` being a plain step and `None` meaning impassable.
+    pub fn find_weighted_path(
+        start_pos: Position,
+        goal_pos: Position,
+        step_cost: impl Fn(Position) -> Option<u32>,
     ) -> Option<Vec<Position>> {
         let start = (start_pos.x, start_pos.y);
         let goal = (goal_pos.x, goal_pos.y);
@@ -60,9 +119,10 @@ impl Navigator {
                     let ny = y as isize + dy;
                     if nx >= 0 && ny >= 0 {
                         let (ux, uy) = (nx as usize, ny as usize);
-                        if is_walkable(Position { x: ux, y: uy }) {
+                        if let Some(tile_cost) = step_cost(Position { x: ux, y: uy }) {
                             // Diagonal steps have slightly higher cost
                             let cost = if dx != 0 && dy != 0 { 14 } else { 10 };
+                            let cost = cost * tile_cost as isize / ACTION_COST_NORMAL as isize;
                             neighbors.push(((ux, uy), cost));
                         }
                     }
@@ -83,19 +143,16 @@ impl Navigator {
     }
 
     fn is_opaque(tiles: &TileMap, x: isize, y: isize) -> bool {
-        if x < 0 || y < 0 || x as usize >= GRID_WIDTH || y as usize >= GRID_HEIGHT {
+        if x < 0 || y < 0 || !tiles.in_bounds(Position::new(x as usize, y as usize)) {
             true // treat out-of-bounds as walls
         } else {
-            matches!(
-                tiles[Position::new(x as usize, y as usize)].kind(),
-                TileKind::Wall
-            )
+            tiles[Position::new(x as usize, y as usize)].blocks_sight()
         }
     }
 
     fn cast_light(
         tiles: &TileMap,
-        visible: &mut HashSet<Position>,
+        visible: &mut VisibilityGrid,
         cx: isize,
         cy: isize,
         row: isize,
@@ -138,7 +195,8 @@ impl Navigator {
                 }
 
                 let distance = dx * dx + dy * dy;
-                if nx >= 0 && ny >= 0 && nx < GRID_WIDTH as isize && ny < GRID_HEIGHT as isize {
+                if nx >= 0 && ny >= 0 && nx < tiles.width() as isize && ny < tiles.height() as isize
+                {
                     let is_blocking = Self::is_opaque(tiles, nx, ny);
 
                     // Only insert tiles that are not opaque
@@ -186,10 +244,20 @@ impl Navigator {
         }
     }
 
-    pub fn compute_fov(tiles: &TileMap, origin: Position, max_radius: usize) -> HashSet<Position> {
-        let mut visible = HashSet::new();
+    pub fn compute_fov(
+        tiles: &TileMap,
+        origin: Position,
+        max_radius: usize,
+        mode: FovMode,
+    ) -> VisibilityGrid {
+        let mut visible = VisibilityGrid::new(tiles.width(), tiles.height());
         visible.insert(origin); // Always see self
 
+        if mode == FovMode::Symmetric {
+            Self::cast_symmetric(tiles, &mut visible, origin, max_radius as isize);
+            return visible;
+        }
+
         for octant in 0..8 {
             Self::cast_light(
                 tiles,
@@ -206,6 +274,68 @@ impl Navigator {
 
         visible
     }
+
+    fn cast_symmetric(
+        tiles: &TileMap,
+        visible: &mut VisibilityGrid,
+        origin: Position,
+        radius: isize,
+    ) {
+        let (cx, cy) = (origin.x as isize, origin.y as isize);
+
+        for quadrant in 0..4 {
+            let transform = |depth: isize, col: isize| match quadrant {
+                0 => (cx + col, cy - depth), // north
+                1 => (cx + depth, cy + col), // east
+                2 => (cx + col, cy + depth), // south
+                _ => (cx - depth, cy + col), // west
+            };
+
+            let mut rows = vec![Row {
+                depth: 1,
+                start: Slope { num: -1, den: 1 },
+                end: Slope { num: 1, den: 1 },
+            }];
+            while let Some(mut row) = rows.pop() {
+                if row.depth > radius {
+                    continue;
+                }
+
+                let mut prev_opaque = None;
+                for col in row.min_col()..=row.max_col() {
+                    let (x, y) = transform(row.depth, col);
+                    let opaque = Self::is_opaque(tiles, x, y);
+
+                    // Only floor tiles are shown, same as the permissive mode
+                    if !opaque
+                        && row.is_symmetric(col)
+                        && col * col + row.depth * row.depth <= radius * radius
+                    {
+                        visible.insert(Position::new(x as usize, y as usize));
+                    }
+
+                    // Left edge of a tile, as seen from the origin
+                    let tile_slope = Slope {
+                        num: 2 * col - 1,
+                        den: 2 * row.depth,
+                    };
+                    match (prev_opaque, opaque) {
+                        (Some(true), false) => row.start = tile_slope,
+                        (Some(false), true) => rows.push(Row {
+                            end: tile_slope,
+                            ..row.next()
+                        }),
+                        _ => {}
+                    }
+                    prev_opaque = Some(opaque);
+                }
+
+                if prev_opaque == Some(false) {
+                    rows.push(row.next());
+                }
+            }
+        }
+    }
 }
 
 pub fn find_path<F>(
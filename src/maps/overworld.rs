@@ -23,14 +23,20 @@
 use core::panic;
 use std::{
     cell::RefCell,
+    collections::{HashMap, hash_map::Entry},
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
+use macroquad::file::load_string;
+use serde::Deserialize;
+use serde_json::from_str;
+
 use crate::maps::{
     generated_map::GeneratedMap,
     map::{Map, MapRc},
 };
+use crate::position::Position;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum VisitedState {
@@ -52,69 +58,109 @@ impl OverworldPos {
     }
 }
 
+/// Size of a single floor, overriding the overworld default.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FloorSize {
+    pub floor: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// How many maps each floor of the overworld spans.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OverworldLayout {
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub floors: Vec<FloorSize>,
+}
+
+pub type OverworldLayoutArc = Arc<OverworldLayout>;
+
+pub async fn load_overworld_layout() -> OverworldLayoutArc {
+    let file: String = load_string("assets/overworld.json").await.unwrap();
+    let layout: OverworldLayout = from_str(&file).unwrap();
+    Arc::new(layout)
+}
+
+impl OverworldLayout {
+    /// Width and height of `floor`, in maps.
+    pub fn dimensions(&self, floor: usize) -> (usize, usize) {
+        self.floors
+            .iter()
+            .find(|size| size.floor == floor)
+            .map(|size| (size.width, size.height))
+            .unwrap_or((self.width, self.height))
+    }
+
+    /// Where the player starts a floor, and where the stairs from above lead.
+    pub fn center(&self, floor: usize) -> OverworldPos {
+        let (width, height) = self.dimensions(floor);
+        OverworldPos::new(floor, width / 2, height / 2)
+    }
+}
+
 pub struct Overworld {
-    pub maps: Rc<RefCell<Vec<[[Option<MapRc>; 5]; 5]>>>,
+    pub layout: OverworldLayoutArc,
+    pub maps: Rc<RefCell<HashMap<OverworldPos, MapRc>>>,
+    /// Where the upstairs of each floor lead: the map and stairs the player last came down by.
+    pub stairs_up: HashMap<usize, (OverworldPos, Position)>,
 }
 
 impl Overworld {
-    pub fn new() -> Self {
-        let maps: Rc<RefCell<Vec<[[Option<MapRc>; 5]; 5]>>> =
-            Rc::new(RefCell::new(vec![std::array::from_fn(|_| {
-                std::array::from_fn(|_| None)
-            })]));
-        Self { maps: maps }
+    pub fn new(layout: OverworldLayoutArc) -> Self {
+        Self {
+            layout,
+            maps: Rc::new(RefCell::new(HashMap::new())),
+            stairs_up: HashMap::new(),
+        }
     }
 
-    pub fn clear_unvisited(&mut self, opos: OverworldPos) {
-        let to_clear = {
-            let maps = self.maps.borrow();
-            let mut result = Vec::new();
-            for (row_idx, row) in maps[opos.floor].iter().enumerate() {
-                for (col_idx, map_opt) in row.iter().enumerate() {
-                    if (row_idx, col_idx) == (opos.x, opos.y) {
-                        continue; // Skip the current position
-                    }
-                    if let Some(map) = map_opt {
-                        if map.0.borrow().generated_map.visited_state != VisitedState::Visited {
-                            result.push((row_idx, col_idx));
-                        }
-                    }
-                }
+    /// Drops the maps of the floor the player has not been on, returning where they were.
+    pub fn clear_unvisited(&mut self, opos: OverworldPos) -> Vec<OverworldPos> {
+        let mut cleared = Vec::new();
+        let mut maps = self.maps.borrow_mut();
+        maps.retain(|&pos, map| {
+            // Skip the current position and the other floors
+            if pos.floor != opos.floor
+                || pos == opos
+                || map.0.borrow().generated_map.visited_state == VisitedState::Visited
+            {
+                return true;
             }
-            result
-        };
+            println!(
+                "Overworld: Clearing unvisited map at position: ({}, {}) on floor {}",
+                pos.x, pos.y, pos.floor
+            );
+            cleared.push(pos);
+            false
+        });
 
+        // Clear the map below unless the player has already been down there
+        let below = self.layout.center(opos.floor + 1);
+        if maps
+            .get(&below)
+            .is_some_and(|map| map.0.borrow().generated_map.visited_state != VisitedState::Visited)
         {
-            let mut maps = self.maps.borrow_mut();
-            for (row_idx, col_idx) in to_clear {
-                println!(
-                    "Overworld: Clearing unvisited map at position: ({}, {}) on floor {}",
-                    row_idx, col_idx, opos.floor
-                );
-                maps[opos.floor][row_idx][col_idx] = None;
-            }
-
-            if maps.len() > opos.floor + 1 {
-                // Clear the map below if it exists
-                maps[opos.floor + 1][2][2] = None;
-            }
+            maps.remove(&below);
+            cleared.push(below);
         }
+        cleared
     }
 
-    pub fn add_map(&self, opos: OverworldPos, generated_map: Arc<Mutex<GeneratedMap>>) -> MapRc {
+    pub fn is_visited(&self, opos: OverworldPos) -> bool {
+        self.maps
+            .borrow()
+            .get(&opos)
+            .is_some_and(|map| map.0.borrow().generated_map.visited_state == VisitedState::Visited)
+    }
+
+    pub fn add_map(&self, opos: OverworldPos, generated_map: GeneratedMap) -> MapRc {
         let mut maps_guard = self.maps.borrow_mut();
-        if opos.floor >= maps_guard.len() {
-            maps_guard.resize_with(opos.floor + 1, || {
-                std::array::from_fn(|_| std::array::from_fn(|_| None))
-            });
-        }
 
-        if maps_guard[opos.floor][opos.x][opos.y].is_none() {
-            // This is getting ridiculous
-            let map = MapRc(Rc::new(RefCell::new(Map::new(
-                generated_map.lock().unwrap().clone(),
-            ))));
-            maps_guard[opos.floor][opos.x][opos.y] = Some(map.clone());
+        if let Entry::Vacant(entry) = maps_guard.entry(opos) {
+            let map = MapRc(Rc::new(RefCell::new(Map::new(generated_map))));
+            entry.insert(map.clone());
             map
         } else {
             panic!("Map at position {:?} already exists!", opos);
@@ -122,11 +168,6 @@ impl Overworld {
     }
 
     pub fn get_map_ptr(&self, opos: OverworldPos) -> Option<MapRc> {
-        let maps_guard = self.maps.borrow();
-        if opos.floor < maps_guard.len() && opos.x < 5 && opos.y < 5 {
-            maps_guard[opos.floor][opos.x][opos.y].as_ref().cloned()
-        } else {
-            None
-        }
+        self.maps.borrow().get(&opos).cloned()
     }
 }
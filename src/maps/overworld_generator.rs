@@ -22,129 +22,70 @@
 
 use std::sync::{Arc, Mutex};
 
+use rand::thread_rng;
+
 use crate::{
+    boss::{BossKindsArc, boss_for_floor},
     items::collection::ItemsArc,
     lua_interface::LuaInterfaceRc,
     maps::{
-        Border, BorderFlags, GRID_HEIGHT, GRID_WIDTH, MapTheme,
+        BorderFlags, MapTheme,
         generated_map::GeneratedMap,
-        map_generator::{GenerationParams, MapAssignment, MapGenerator, MapStatus},
-        overworld::{OverworldPos, VisitedState},
+        lighting::ambient_light,
+        map_generator::{
+            BACKGROUND_PRIORITY, GenerationParams, GenerationPriority, MapAssignment, MapGenerator,
+            MapOutline, MapStatus,
+        },
+        overworld::{OverworldLayoutArc, OverworldPos},
     },
     monster_kind::{MonsterKinds, MonsterKindsVecArc},
     position::Position,
 };
 
 pub struct OverworldGenerator {
-    pub generated_maps: Arc<Mutex<Vec<[[Option<Arc<Mutex<GeneratedMap>>>; 5]; 5]>>>,
+    pub layout: OverworldLayoutArc,
     map_generator: MapGenerator,
+    bosses: BossKindsArc,
 }
 
 impl OverworldGenerator {
-    fn fill_predefined_borders(&self, opos: OverworldPos, params: &mut GenerationParams) {
-        use Border::*;
-
-        const DIRS: &[(Border, i32, i32, fn(&mut Position))] = &[
-            // neighbour ABOVE us → take its BOTTOM edge, then clamp to y=0
-            (Top, 0, -1, |p| p.y = 0),
-            // neighbour to our RIGHT → take its LEFT edge, then clamp to x=GRID_WIDTH-1
-            (Right, 1, 0, |p| p.x = GRID_WIDTH - 1),
-            // neighbour BELOW us → take its TOP edge, then clamp to y=GRID_HEIGHT-1
-            (Bottom, 0, 1, |p| p.y = GRID_HEIGHT - 1),
-            // neighbour to our LEFT → take its RIGHT edge, then clamp to x=0
-            (Left, -1, 0, |p| p.x = 0),
-        ];
-
-        for (side, dx, dy, mirror) in DIRS {
-            let nx = opos.x as i32 + dx;
-            let ny = opos.y as i32 + dy;
-
-            // outside the 5×5 slice – ignore
-            if !(0..5).contains(&nx) || !(0..5).contains(&ny) {
-                continue;
-            }
-
-            if let Some(neigh_map) = self.get_generated_map_ptr(OverworldPos {
-                floor: opos.floor,
-                x: nx as usize,
-                y: ny as usize,
-            }) {
-                let mut vec =
-                    neigh_map.lock().unwrap().border_positions[side.opposite() as usize].clone();
-
-                // convert neighbour coordinates to *our* side
-                for p in &mut vec {
-                    mirror(p);
-                }
-
-                params.predefined_borders[*side as usize] = vec;
-
-                // make sure this side is opened in the new map too
-                params.borders |= match side {
-                    Top => BorderFlags::TOP,
-                    Right => BorderFlags::RIGHT,
-                    Bottom => BorderFlags::BOTTOM,
-                    Left => BorderFlags::LEFT,
-                };
-            }
-        }
-    }
-
     pub async fn new(
         lua_interface: &LuaInterfaceRc,
         monster_kinds: MonsterKindsVecArc,
         items: &ItemsArc,
+        bosses: BossKindsArc,
+        layout: OverworldLayoutArc,
     ) -> Arc<Mutex<Self>> {
-        let generated_maps: Arc<Mutex<Vec<[[Option<Arc<Mutex<GeneratedMap>>>; 5]; 5]>>> =
-            Arc::new(Mutex::new(vec![std::array::from_fn(|_| {
-                std::array::from_fn(|_| None)
-            })]));
-
         let map_generator = MapGenerator::new(lua_interface, &monster_kinds, items).await;
 
         let overworld = Arc::new(Mutex::new(Self {
-            generated_maps: Arc::clone(&generated_maps),
+            layout: Arc::clone(&layout),
             map_generator,
+            bosses: bosses.clone(),
         }));
 
         let overworld_weak = Arc::downgrade(&overworld);
-        let generated_maps_clone = Arc::clone(&generated_maps);
         let first_map_generation = Arc::new(std::sync::atomic::AtomicBool::new(true));
 
         let first_map_generation_clone = Arc::clone(&first_map_generation);
+        let center = layout.center(0);
         let callback = Box::new(move |assignment: MapAssignment| {
-            let mut generated_maps = generated_maps_clone.lock().unwrap();
             let OverworldPos { floor, x, y } = assignment.opos;
 
-            if floor >= generated_maps.len() {
-                generated_maps.resize_with(floor + 1, || {
-                    std::array::from_fn(|_| std::array::from_fn(|_| None))
-                });
-            }
-
             println!(
                 "Generated map at position: {:?}, floor: {}, x: {}, y: {}",
                 assignment.opos, floor, x, y
             );
-            generated_maps[floor][x][y] = Some(assignment.map);
-            drop(generated_maps);
 
-            if x == 2
-                && y == 2
-                && floor == 0
+            if assignment.opos == center
                 && first_map_generation_clone.swap(false, std::sync::atomic::Ordering::Relaxed)
             {
-                let stairs_pos = generated_maps_clone
-                    .lock()
-                    .unwrap()
-                    .get(0)
-                    .and_then(|floor| floor[2][2].as_ref())
-                    .and_then(|map| map.lock().unwrap().downstair_teleport);
+                let stairs_pos = assignment.downstair_teleport;
 
                 // This is the center map, setup adjacent maps
                 if let Some(overworld_strong) = overworld_weak.upgrade() {
                     let mut o = overworld_strong.lock().unwrap();
-                    o.setup_adjacent_maps(2, floor, x, y, stairs_pos);
+                    o.setup_adjacent_maps(2, floor, x, y, stairs_pos, None);
                 }
             }
         });
@@ -157,61 +98,46 @@ impl OverworldGenerator {
 
         // Request center map
         let mut gen_params = GenerationParams::default();
-        gen_params.borders = BorderFlags::TOP
-            | BorderFlags::BOTTOM
-            | BorderFlags::LEFT
-            | BorderFlags::RIGHT
-            | BorderFlags::DOWN;
+        gen_params.borders =
+            BorderFlags::TOP | BorderFlags::BOTTOM | BorderFlags::LEFT | BorderFlags::RIGHT;
+        if boss_for_floor(&bosses, 0).is_none() {
+            gen_params.borders |= BorderFlags::DOWN;
+        }
         gen_params.theme = MapTheme::Chasm;
+        gen_params.ambient_light = ambient_light(0, &gen_params.theme);
 
-        let center = OverworldPos {
-            floor: 0,
-            x: 2,
-            y: 2,
-        };
         overworld
             .lock()
             .unwrap()
             .map_generator
-            .request_generation(center, gen_params);
+            .request_generation(center, gen_params, 0);
 
         overworld
     }
 
+    /// Drops the requests of the floor no worker has started on, they would not
+    /// line up with the new map anyway. Maps already generated are kept.
     pub fn clear_unvisited(&mut self, overworld_pos: OverworldPos) {
-        let mut generated_maps = self.generated_maps.lock().unwrap();
-        for (row_idx, row) in generated_maps[overworld_pos.floor].iter_mut().enumerate() {
-            for (col_idx, map_opt) in row.iter_mut().enumerate() {
-                if let Some(map) = map_opt {
-                    if map.lock().unwrap().visited_state != VisitedState::Visited {
-                        *map_opt = None; // Clear unvisited maps
-                        self.map_generator
-                            .map_statuses
-                            .lock()
-                            .unwrap()
-                            .remove(&OverworldPos {
-                                floor: overworld_pos.floor,
-                                x: row_idx,
-                                y: col_idx,
-                            });
-                        println!(
-                            "OverworldGenerator: Clearing unvisited map at position: ({}, {}) on floor {}",
-                            row_idx, col_idx, overworld_pos.floor
-                        );
-                    }
-                }
-            }
+        self.map_generator
+            .cancel_queued(|pos| pos.floor == overworld_pos.floor);
+
+        // The floor below starts at the stairs of the map it was asked for from, so
+        // it is made again for the new one unless the game already took it
+        let below = self.layout.center(overworld_pos.floor + 1);
+        if !matches!(self.map_status(below), MapStatus::Taken(_)) {
+            self.map_generator.cancel(below);
         }
+    }
 
-        self.map_generator
-            .map_statuses
-            .lock()
-            .unwrap()
-            .remove(&OverworldPos {
-                floor: overworld_pos.floor + 1,
-                x: overworld_pos.x,
-                y: overworld_pos.y,
-            });
+    /// Forgets maps the game let go of, so they are generated anew when needed.
+    pub fn forget(&mut self, positions: &[OverworldPos]) {
+        for &opos in positions {
+            self.map_generator.cancel(opos);
+        }
+    }
+
+    pub fn take_map(&self, opos: OverworldPos) -> Option<GeneratedMap> {
+        self.map_generator.take(opos)
     }
 
     pub fn setup_adjacent_maps(
@@ -221,7 +147,17 @@ impl OverworldGenerator {
         x: usize,
         y: usize,
         stairs_pos: Option<Position>,
+        player_pos: Option<Position>,
     ) {
+        // On floors guarded by a boss, only its arena leads further down
+        let floor_boss = boss_for_floor(&self.bosses, floor).cloned();
+        let stairs_pos = match &floor_boss {
+            Some(boss) if !boss.is_at(floor, x, y) => None,
+            _ => stairs_pos,
+        };
+        let mut rng = thread_rng();
+        let (width, height) = self.layout.dimensions(floor);
+
         for dx in -1i32..=1 {
             for dy in -1i32..=1 {
                 // Skip diagonals
@@ -230,88 +166,127 @@ impl OverworldGenerator {
                 }
                 let new_x = x as i32 + dx;
                 let new_y = y as i32 + dy;
-                if new_x >= 0 && new_x < 5 && new_y >= 0 && new_y < 5 {
+                if new_x >= 0 && new_x < width as i32 && new_y >= 0 && new_y < height as i32 {
                     let opos = OverworldPos {
                         floor,
                         x: new_x as usize,
                         y: new_y as usize,
                     };
-                    // Check if this adjacent map is already generated
-                    let existing_map = self.get_generated_map_ptr(opos);
-                    if existing_map.is_none() {
+                    // Check if this adjacent map is already generated or on its way,
+                    // without waiting on it
+                    if self.map_generator.get_map_status(&opos) == MapStatus::NotRequested {
                         let mut gen_params = GenerationParams::default();
                         gen_params.tier = tier;
+                        gen_params.algorithm = self.map_generator.random_algorithm(&mut rng);
                         if new_x != 0 {
                             gen_params.borders |= BorderFlags::LEFT;
                         }
-                        if new_x != 4 {
+                        if new_x != width as i32 - 1 {
                             gen_params.borders |= BorderFlags::RIGHT;
                         }
                         if new_y != 0 {
                             gen_params.borders |= BorderFlags::TOP;
                         }
-                        if new_y != 4 {
+                        if new_y != height as i32 - 1 {
                             gen_params.borders |= BorderFlags::BOTTOM;
                         }
-                        gen_params.borders |= BorderFlags::DOWN;
 
-                        gen_params.theme = MapTheme::Chasm;
-                        self.fill_predefined_borders(opos, &mut gen_params);
-                        self.map_generator.request_generation(opos, gen_params);
+                        match &floor_boss {
+                            Some(boss) if boss.is_at(floor, opos.x, opos.y) => {
+                                if let Some([width, height]) = boss.map_size {
+                                    gen_params.width = width;
+                                    gen_params.height = height;
+                                }
+                                gen_params.theme = MapTheme::Arena;
+                                gen_params.boss = Some(boss.clone());
+                            }
+                            Some(_) => gen_params.theme = MapTheme::Chasm,
+                            None => {
+                                gen_params.borders |= BorderFlags::DOWN;
+                                gen_params.theme = MapTheme::Chasm;
+                            }
+                        }
+                        gen_params.ambient_light = ambient_light(floor, &gen_params.theme);
+                        let priority = match player_pos {
+                            Some(pos) => self.border_distance(floor, x, y, pos, dx, dy),
+                            None => BACKGROUND_PRIORITY,
+                        };
+                        self.map_generator
+                            .request_generation(opos, gen_params, priority);
                     }
                 }
             }
         }
         if let Some(downstairs_pos) = stairs_pos {
-            let opos = OverworldPos {
-                floor: floor + 1,
-                x: 2,
-                y: 2,
-            };
+            let opos = self.layout.center(floor + 1);
+            // Once the game has taken the floor below it stays as it is
+            if matches!(
+                self.map_generator.get_map_status(&opos),
+                MapStatus::Taken(_)
+            ) {
+                return;
+            }
             let mut gen_params = GenerationParams::default();
             gen_params.tier = tier + 2; // Increment tier by 2 for the downstairs map
-            gen_params.borders = BorderFlags::TOP
-                | BorderFlags::BOTTOM
-                | BorderFlags::LEFT
-                | BorderFlags::RIGHT
-                | BorderFlags::DOWN;
+            gen_params.algorithm = self.map_generator.random_algorithm(&mut rng);
+            gen_params.borders =
+                BorderFlags::TOP | BorderFlags::BOTTOM | BorderFlags::LEFT | BorderFlags::RIGHT;
+            if boss_for_floor(&self.bosses, floor + 1).is_none() {
+                gen_params.borders |= BorderFlags::DOWN;
+            }
             gen_params.theme = MapTheme::Chasm;
             gen_params.force_regen = true;
             gen_params.predefined_start_pos = Some(downstairs_pos);
-            self.map_generator.request_generation(opos, gen_params);
+            gen_params.ambient_light = ambient_light(floor + 1, &gen_params.theme);
+            let priority = match player_pos {
+                Some(pos) => pos
+                    .x
+                    .abs_diff(downstairs_pos.x)
+                    .max(pos.y.abs_diff(downstairs_pos.y))
+                    as GenerationPriority,
+                None => BACKGROUND_PRIORITY,
+            };
+            self.map_generator
+                .request_generation(opos, gen_params, priority);
         }
     }
 
-    pub fn get_generated_map_ptr(&self, opos: OverworldPos) -> Option<Arc<Mutex<GeneratedMap>>> {
-        let shared_status_opt = self
-            .map_generator
-            .map_statuses
-            .lock()
-            .ok()?
-            .get(&opos)
-            .cloned();
-
-        let shared_status = shared_status_opt?;
-        let (lock, cvar) = &*shared_status;
+    /// Steps between `player_pos` and the border of the map at (`x`, `y`) leading to
+    /// its neighbour in direction (`dx`, `dy`), the closest one being peeked first.
+    fn border_distance(
+        &self,
+        floor: usize,
+        x: usize,
+        y: usize,
+        player_pos: Position,
+        dx: i32,
+        dy: i32,
+    ) -> GenerationPriority {
+        let Some(map) = self.map_outline(OverworldPos { floor, x, y }) else {
+            return BACKGROUND_PRIORITY;
+        };
+        let (width, height) = (map.width, map.height);
+        let distance = match (dx, dy) {
+            (0, -1) => player_pos.y,
+            (0, 1) => height.saturating_sub(player_pos.y + 1),
+            (-1, 0) => player_pos.x,
+            _ => width.saturating_sub(player_pos.x + 1),
+        };
+        distance as GenerationPriority
+    }
 
-        let mut status = lock.lock().ok()?;
+    /// Size and border anchors of the map at `opos`, once it is generated.
+    pub fn map_outline(&self, opos: OverworldPos) -> Option<Arc<MapOutline>> {
+        self.map_generator.outline(&opos)
+    }
 
-        while let MapStatus::Requested = *status {
-            status = cvar.wait(status).ok()?; // Wait until notified
-        }
+    /// Where the map at `opos` stands, without waiting for a map still being generated.
+    pub fn map_status(&self, opos: OverworldPos) -> MapStatus {
+        self.map_generator.get_map_status(&opos)
+    }
 
-        match &*status {
-            MapStatus::Ready(_map_arc) => {
-                let maps_guard = self.generated_maps.lock().ok()?;
-                if opos.floor < maps_guard.len() && opos.x < 5 && opos.y < 5 {
-                    maps_guard[opos.floor][opos.x][opos.y]
-                        .as_ref()
-                        .map(Arc::clone)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        }
+    /// Puts `opos` at the front of the generation queue.
+    pub fn prioritize(&self, opos: OverworldPos) {
+        self.map_generator.prioritize(opos, 0);
     }
 }
@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fs;
+
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+use crate::maps::map_algorithms::{LayoutCanvas, LayoutPlacement};
+use crate::maps::navigator::Navigator;
+use crate::position::Position;
+use crate::tile::TileKind;
+use crate::tile_def::get_tile_defs;
+
+const PREFABS_DIR: &str = "assets/prefabs";
+pub const MAX_PREFABS_PER_MAP: usize = 2;
+const PLACEMENT_ATTEMPTS: usize = 30;
+
+/// Hand authored vault read from an ASCII `.prefab` file.
+///
+/// The header holds `key: value` lines (`name`, `rarity`, `tiers`, `rotate`,
+/// `mirror`, `legend`) and is separated from the layout by a `---` line.
+/// Layout legend: `M` monster of the map's tier, `C` chest, `T` downstairs,
+/// `O` orb, space leaves the map untouched, and any other character is the
+/// glyph of a tile from `tiles.json` (`#` wall, `.` floor, `~` chasm, ...).
+/// `legend: o=4, W=7` maps extra characters to specific monster kinds on floor.
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub rarity: f64,
+    pub min_tier: u32,
+    pub max_tier: u32,
+    pub rotate: bool,
+    pub mirror: bool,
+    legend: HashMap<char, u32>,
+    rows: Vec<Vec<char>>,
+}
+
+/// Area of the map taken by a stamped prefab.
+#[derive(Debug, Clone, Copy)]
+pub struct PrefabFootprint {
+    pub origin: Position,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PrefabFootprint {
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x >= self.origin.x
+            && pos.y >= self.origin.y
+            && pos.x < self.origin.x + self.width
+            && pos.y < self.origin.y + self.height
+    }
+
+    fn overlaps(&self, other: &PrefabFootprint) -> bool {
+        self.origin.x < other.origin.x + other.width
+            && other.origin.x < self.origin.x + self.width
+            && self.origin.y < other.origin.y + other.height
+            && other.origin.y < self.origin.y + self.height
+    }
+}
+
+pub fn load_prefabs() -> Vec<Prefab> {
+    let Ok(entries) = fs::read_dir(PREFABS_DIR) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "prefab"))
+        .collect();
+    paths.sort();
+
+    let mut prefabs = Vec::new();
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Prefab::parse(&text));
+        match parsed {
+            Ok(prefab) => prefabs.push(prefab),
+            Err(e) => eprintln!("Failed to load prefab {}: {}", path.display(), e),
+        }
+    }
+    prefabs
+}
+
+impl Prefab {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut prefab = Prefab {
+            name: String::from("Unnamed prefab"),
+            rarity: 1.0,
+            min_tier: 0,
+            max_tier: u32::MAX,
+            rotate: false,
+            mirror: false,
+            legend: HashMap::new(),
+            rows: Vec::new(),
+        };
+
+        let mut lines = text.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line == "---" {
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid header line `{}`", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => prefab.name = value.to_string(),
+                "rarity" => {
+                    prefab.rarity = value
+                        .parse()
+                        .map_err(|_| format!("Invalid rarity `{}`", value))?
+                }
+                "tiers" => {
+                    let (min, max) = value.split_once('-').unwrap_or((value, value));
+                    prefab.min_tier = min
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid tiers `{}`", value))?;
+                    prefab.max_tier = max
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid tiers `{}`", value))?;
+                }
+                "rotate" => prefab.rotate = value == "true",
+                "mirror" => prefab.mirror = value == "true",
+                "legend" => {
+                    for entry in value.split(',') {
+                        let (symbol, kind_id) = entry
+                            .split_once('=')
+                            .ok_or_else(|| format!("Invalid legend entry `{}`", entry))?;
+                        let symbol = symbol
+                            .trim()
+                            .chars()
+                            .next()
+                            .ok_or_else(|| format!("Invalid legend entry `{}`", entry))?;
+                        let kind_id = kind_id
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("Invalid legend entry `{}`", entry))?;
+                        prefab.legend.insert(symbol, kind_id);
+                    }
+                }
+                other => return Err(format!("Unknown header key `{}`", other)),
+            }
+        }
+
+        prefab.rows = lines
+            .map(|line| line.chars().collect::<Vec<char>>())
+            .collect();
+        while prefab.rows.last().is_some_and(|row| row.is_empty()) {
+            prefab.rows.pop();
+        }
+
+        let width = prefab.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        if width == 0 {
+            return Err(String::from("Empty layout"));
+        }
+        // Ragged lines are padded with untouched cells
+        for row in prefab.rows.iter_mut() {
+            row.resize(width, ' ');
+        }
+
+        Ok(prefab)
+    }
+
+    pub fn fits_tier(&self, tier: u32) -> bool {
+        tier >= self.min_tier && tier <= self.max_tier
+    }
+
+    /// Returns the layout randomly rotated and mirrored, as allowed by the prefab.
+    fn oriented(&self, rng: &mut ThreadRng) -> Vec<Vec<char>> {
+        let mut rows = self.rows.clone();
+
+        if self.mirror && rng.gen_bool(0.5) {
+            for row in rows.iter_mut() {
+                row.reverse();
+            }
+        }
+
+        if self.rotate {
+            for _ in 0..rng.gen_range(0..4) {
+                // Clockwise quarter turn
+                let height = rows.len();
+                let width = rows[0].len();
+                rows = (0..width)
+                    .map(|x| (0..height).map(|y| rows[height - 1 - y][x]).collect())
+                    .collect();
+            }
+        }
+
+        rows
+    }
+}
+
+impl Prefab {
+    /// The terrain a layout character stands for, `None` for untouched cells.
+    fn tile_kind(&self, symbol: char) -> Option<TileKind> {
+        match symbol {
+            ' ' => None,
+            'M' | 'C' | 'T' | 'O' => Some(TileKind::FLOOR),
+            _ if self.legend.contains_key(&symbol) => Some(TileKind::FLOOR),
+            _ => Some(get_tile_defs().by_glyph(symbol).unwrap_or(TileKind::FLOOR)),
+        }
+    }
+
+    fn is_passable(&self, symbol: char) -> bool {
+        self.tile_kind(symbol)
+            .is_some_and(|kind| kind.def().walkable)
+    }
+}
+
+/// Rolls the prefabs allowed on this tier and stamps the winners onto the canvas,
+/// away from `anchors`. Returns the areas taken so random content stays out of them.
+pub fn stamp_prefabs(
+    canvas: &mut LayoutCanvas,
+    prefabs: &[Prefab],
+    anchors: &[Position],
+    tier: u32,
+    rng: &mut ThreadRng,
+) -> Vec<PrefabFootprint> {
+    let mut footprints: Vec<PrefabFootprint> = Vec::new();
+
+    for prefab in prefabs {
+        if footprints.len() >= MAX_PREFABS_PER_MAP {
+            break;
+        }
+        if !prefab.fits_tier(tier) || !rng.gen_bool(prefab.rarity.clamp(0.0, 1.0)) {
+            continue;
+        }
+
+        let rows = prefab.oriented(rng);
+        let height = rows.len();
+        let width = rows[0].len();
+        if width + 2 > canvas.width() || height + 2 > canvas.height() {
+            continue;
+        }
+
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let footprint = PrefabFootprint {
+                origin: Position::new(
+                    rng.gen_range(1..=canvas.width() - 1 - width),
+                    rng.gen_range(1..=canvas.height() - 1 - height),
+                ),
+                width,
+                height,
+            };
+
+            if anchors.iter().any(|&anchor| footprint.contains(anchor))
+                || footprints.iter().any(|other| other.overlaps(&footprint))
+            {
+                continue;
+            }
+
+            println!("[MapGenerator] Stamping prefab {}", prefab.name);
+            stamp(canvas, prefab, &rows, footprint);
+            connect_entrances(canvas, prefab, &rows, footprint);
+            footprints.push(footprint);
+            break;
+        }
+    }
+
+    footprints
+}
+
+fn stamp(
+    canvas: &mut LayoutCanvas,
+    prefab: &Prefab,
+    rows: &[Vec<char>],
+    footprint: PrefabFootprint,
+) {
+    for (dy, row) in rows.iter().enumerate() {
+        for (dx, &symbol) in row.iter().enumerate() {
+            let pos = Position::new(footprint.origin.x + dx, footprint.origin.y + dy);
+            let Some(kind) = prefab.tile_kind(symbol) else {
+                continue;
+            };
+            canvas.set_kind(pos, kind);
+            match symbol {
+                'M' => canvas.place(LayoutPlacement::RandomMonster(pos)),
+                'C' => canvas.place(LayoutPlacement::Chest(pos)),
+                'T' => canvas.place(LayoutPlacement::Teleport(pos)),
+                'O' => canvas.place(LayoutPlacement::Orb(pos)),
+                other => {
+                    if let Some(&kind_id) = prefab.legend.get(&other) {
+                        canvas.place(LayoutPlacement::Monster(kind_id, pos));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Links every opening on the prefab's outline to the rest of the layout,
+/// routing around the prefab so its walls stay intact.
+fn connect_entrances(
+    canvas: &mut LayoutCanvas,
+    prefab: &Prefab,
+    rows: &[Vec<char>],
+    footprint: PrefabFootprint,
+) {
+    let height = rows.len();
+    let width = rows[0].len();
+
+    for (dy, row) in rows.iter().enumerate() {
+        for (dx, &symbol) in row.iter().enumerate() {
+            if !prefab.is_passable(symbol) {
+                continue;
+            }
+
+            let (x, y) = (footprint.origin.x + dx, footprint.origin.y + dy);
+            let outside = if dy == 0 {
+                Position::new(x, y - 1)
+            } else if dy == height - 1 {
+                Position::new(x, y + 1)
+            } else if dx == 0 {
+                Position::new(x - 1, y)
+            } else if dx == width - 1 {
+                Position::new(x + 1, y)
+            } else {
+                continue;
+            };
+
+            if !canvas.is_inner(outside) || canvas.is_floor(outside) {
+                continue;
+            }
+
+            let goal = canvas
+                .walkable()
+                .iter()
+                .copied()
+                .filter(|&pos| !footprint.contains(pos) && pos != outside)
+                .min_by_key(|pos| pos.x.abs_diff(outside.x) + pos.y.abs_diff(outside.y));
+            let Some(goal) = goal else {
+                continue;
+            };
+
+            let path = Navigator::find_path(outside, goal, |pos| {
+                canvas.is_inner(pos) && !footprint.contains(pos)
+            });
+            for pos in path.unwrap_or_default() {
+                canvas.carve(pos);
+            }
+        }
+    }
+}
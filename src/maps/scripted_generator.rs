@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fs;
+
+use mlua::{Error, Function, Lua, RegistryKey, Result, Table, UserData, UserDataMethods};
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+use crate::lua_interface::LuaInterface;
+use crate::maps::map_algorithms::{
+    LayoutCanvas, LayoutPlacement, MapLayoutGenerator, RandomWalkGenerator,
+};
+use crate::maps::map_generator::GenerationParams;
+use crate::position::Position;
+
+const MAPGEN_SCRIPTS_DIR: &str = "assets/mapgen";
+/// Widest path a script may carve, anything above is slow and eats whole maps.
+const MAX_PATH_RADIUS: usize = 2;
+
+/// Lua layout scripts, living in their own Lua state on the map generator thread.
+pub struct MapGenScripts {
+    lua: Lua,
+    on_generate: HashMap<String, RegistryKey>,
+}
+
+impl MapGenScripts {
+    /// Names of the layout scripts found on disk, usable as `MapAlgorithm::Scripted`.
+    pub fn available() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(MAPGEN_SCRIPTS_DIR) else {
+            return Vec::new();
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn load() -> Self {
+        let mut scripts = Self {
+            lua: Lua::new(),
+            on_generate: HashMap::new(),
+        };
+
+        for name in Self::available() {
+            if let Err(e) = scripts.load_script(&name) {
+                eprintln!(
+                    "[MapGenerator] Failed to load layout script {}: {}",
+                    name, e
+                );
+            }
+        }
+
+        scripts
+    }
+
+    fn load_script(&mut self, name: &str) -> Result<()> {
+        let path = format!("{}/{}.lua", MAPGEN_SCRIPTS_DIR, name);
+        let script = fs::read_to_string(&path)
+            .map_err(|e| Error::external(format!("Failed to read {}: {}", path, e)))?;
+
+        // new, isolated env
+        let env: Table = self.lua.create_table()?;
+        let mt: Table = self.lua.create_table()?;
+        mt.set("__index", self.lua.globals())?;
+        env.set_metatable(Some(mt));
+
+        self.lua
+            .load(&script)
+            .set_name(path.as_str())
+            .set_environment(env.clone())
+            .exec()?;
+
+        let func: Function = env
+            .get::<_, Option<Function>>("on_generate")?
+            .ok_or_else(|| Error::external(format!("{} has no on_generate", path)))?;
+        let key = self.lua.create_registry_value(func)?;
+        self.on_generate.insert(name.to_string(), key);
+        Ok(())
+    }
+
+    fn params_table(&self, anchors: &[Position], params: &GenerationParams) -> Result<Table<'_>> {
+        let table = self.lua.create_table()?;
+        table.set("width", params.width)?;
+        table.set("height", params.height)?;
+        table.set("tier", params.tier)?;
+        table.set("theme", format!("{:?}", params.theme))?;
+        table.set("radius", params.radius)?;
+        table.set("num_walks", params.num_walks)?;
+        table.set("walk_length", params.walk_length)?;
+
+        let lua_anchors = self.lua.create_table()?;
+        for (i, anchor) in anchors.iter().enumerate() {
+            lua_anchors.set(i + 1, LuaInterface::add_position(&self.lua, anchor)?)?;
+        }
+        table.set("anchors", lua_anchors)?;
+        Ok(table)
+    }
+
+    fn run(
+        &self,
+        name: &str,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) -> Result<()> {
+        let key = self
+            .on_generate
+            .get(name)
+            .ok_or_else(|| Error::external(format!("No layout script named `{}`", name)))?;
+        let func: Function = self.lua.registry_value(key)?;
+
+        // Lua's own generator would otherwise produce the same layout every time
+        let math: Table = self.lua.globals().get("math")?;
+        let randomseed: Function = math.get("randomseed")?;
+        randomseed.call::<_, ()>(rng.gen_range(0..i32::MAX))?;
+
+        let lua_params = self.params_table(anchors, params)?;
+        let radius = params.radius;
+        self.lua.scope(|scope| {
+            let grid = scope.create_nonstatic_userdata(LuaGrid {
+                canvas: &mut *canvas,
+                rng: &mut *rng,
+                radius,
+            })?;
+            func.call::<_, ()>((lua_params, grid))
+        })
+    }
+}
+
+/// Generator backed by one of the Lua layout scripts.
+pub struct ScriptedGenerator<'s> {
+    scripts: &'s MapGenScripts,
+    name: String,
+}
+
+impl<'s> ScriptedGenerator<'s> {
+    pub fn new(scripts: &'s MapGenScripts, name: &str) -> Self {
+        Self {
+            scripts,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl MapLayoutGenerator for ScriptedGenerator<'_> {
+    fn carve(
+        &self,
+        canvas: &mut LayoutCanvas,
+        anchors: &[Position],
+        params: &GenerationParams,
+        rng: &mut ThreadRng,
+    ) {
+        if let Err(e) = self.scripts.run(&self.name, canvas, anchors, params, rng) {
+            eprintln!(
+                "[MapGenerator] Layout script {} failed, falling back to random walks: {}",
+                self.name, e
+            );
+            RandomWalkGenerator.carve(canvas, anchors, params, rng);
+            return;
+        }
+
+        // Scripts do not have to care about entrances, they always get linked up
+        let layout: Vec<Position> = canvas
+            .walkable()
+            .iter()
+            .copied()
+            .filter(|pos| !anchors.contains(pos))
+            .collect();
+        canvas.connect_anchors(anchors, &layout, rng);
+    }
+}
+
+/// The `grid` handed to `on_generate`. Only the inner area can be edited, the
+/// outer ring is reserved for border entrances.
+struct LuaGrid<'c, 'a> {
+    canvas: &'c mut LayoutCanvas<'a>,
+    rng: &'c mut ThreadRng,
+    radius: usize,
+}
+
+impl LuaGrid<'_, '_> {
+    fn is_inner(&self, x: usize, y: usize) -> bool {
+        self.canvas.is_inner(Position::new(x, y))
+    }
+
+    fn rect_positions(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<Position> {
+        // Scripts can pass anything, keep the rectangle on the canvas
+        let x_end = x.saturating_add(w).min(self.canvas.width());
+        let y_end = y.saturating_add(h).min(self.canvas.height());
+        (x..x_end)
+            .flat_map(move |px| (y..y_end).map(move |py| Position::new(px, py)))
+            .filter(|&pos| self.canvas.is_inner(pos))
+            .collect()
+    }
+}
+
+impl UserData for LuaGrid<'_, '_> {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("width", |_, this, ()| Ok(this.canvas.width()));
+        methods.add_method("height", |_, this, ()| Ok(this.canvas.height()));
+
+        methods.add_method("is_floor", |_, this, (x, y): (usize, usize)| {
+            Ok(this.canvas.is_floor(Position::new(x, y)))
+        });
+
+        methods.add_method_mut("carve", |_, this, (x, y): (usize, usize)| {
+            if this.is_inner(x, y) {
+                this.canvas.carve(Position::new(x, y));
+            }
+            Ok(())
+        });
+
+        methods.add_method_mut("fill", |_, this, (x, y): (usize, usize)| {
+            if this.is_inner(x, y) {
+                this.canvas.fill(Position::new(x, y));
+            }
+            Ok(())
+        });
+
+        methods.add_method_mut(
+            "carve_rect",
+            |_, this, (x, y, w, h): (usize, usize, usize, usize)| {
+                for pos in this.rect_positions(x, y, w, h) {
+                    this.canvas.carve(pos);
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "fill_rect",
+            |_, this, (x, y, w, h): (usize, usize, usize, usize)| {
+                for pos in this.rect_positions(x, y, w, h) {
+                    this.canvas.fill(pos);
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "carve_path",
+            |_, this, (x1, y1, x2, y2, radius): (usize, usize, usize, usize, Option<usize>)| {
+                if !this.is_inner(x1, y1) || !this.is_inner(x2, y2) {
+                    return Err(Error::external(
+                        "carve_path endpoints must be inside the map",
+                    ));
+                }
+                let radius = radius.unwrap_or(this.radius).min(MAX_PATH_RADIUS);
+                this.canvas.carve_inner_path(
+                    Position::new(x1, y1),
+                    Position::new(x2, y2),
+                    this.rng,
+                    radius,
+                );
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "place_monster",
+            |_, this, (kind_id, x, y): (u32, usize, usize)| {
+                this.canvas
+                    .place(LayoutPlacement::Monster(kind_id, Position::new(x, y)));
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut("place_orb", |_, this, (x, y): (usize, usize)| {
+            this.canvas.place(LayoutPlacement::Orb(Position::new(x, y)));
+            Ok(())
+        });
+    }
+}
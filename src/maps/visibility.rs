@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::position::Position;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Set of the tiles of a map that can be seen, one bit per tile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VisibilityGrid {
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+}
+
+impl VisibilityGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            bits: vec![0; (width * height).div_ceil(WORD_BITS)],
+        }
+    }
+
+    fn index(&self, pos: Position) -> Option<usize> {
+        (pos.x < self.width && pos.y < self.height).then_some(pos.y * self.width + pos.x)
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        self.index(pos)
+            .is_some_and(|i| self.bits[i / WORD_BITS] & (1 << (i % WORD_BITS)) != 0)
+    }
+
+    /// Marks `pos` as visible. Positions off the grid are ignored.
+    pub fn insert(&mut self, pos: Position) {
+        if let Some(i) = self.index(pos) {
+            self.bits[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+        }
+    }
+
+    pub fn remove(&mut self, pos: Position) {
+        if let Some(i) = self.index(pos) {
+            self.bits[i / WORD_BITS] &= !(1 << (i % WORD_BITS));
+        }
+    }
+
+    /// Keeps only the visible positions for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(Position) -> bool) {
+        let hidden: Vec<Position> = self.iter().filter(|&pos| !keep(pos)).collect();
+        for pos in hidden {
+            self.remove(pos);
+        }
+    }
+
+    /// Visible positions in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Position> + '_ {
+        let width = self.width;
+        self.bits
+            .iter()
+            .enumerate()
+            .flat_map(move |(word_index, &word)| {
+                let mut word = word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    let i = word_index * WORD_BITS + bit;
+                    Some(Position::new(i % width, i / width))
+                })
+            })
+    }
+}
@@ -23,17 +23,21 @@
 use crate::creature::Creature;
 use crate::graphics;
 use crate::graphics::graphics_manager::GraphicsManager;
+use crate::items::collection::Items;
+use crate::lua_interface::{LuaInterface, MonsterStateSlot};
 use crate::maps::TILE_SIZE;
-use crate::monster_kind::{MonsterKind, MonsterKindSprite};
+use crate::monster_affix::{MonsterAffix, MonsterRarity};
+use crate::monster_kind::{Faction, MonsterKind, MonsterKindSprite};
 use crate::position::Position;
 use crate::ui::point_f::PointF;
+use ::rand::Rng;
 use macroquad::prelude::*;
 use mlua::{Table, UserData, UserDataMethods};
 use std::cell::RefCell;
 use std::cmp::{max, min};
 use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::atomic::AtomicU32;
-use std::sync::{Arc, RwLock};
 
 #[derive(Debug, Clone)]
 pub struct Monster {
@@ -42,11 +46,21 @@ pub struct Monster {
     pub position: Position,
     pub id: u32,
     pub initialized: bool,
-    pub accumulated_speed: u32,
+
+    pub name: String,
+    pub max_hp: u32,
+    pub speed: u32,
+    pub melee_damage: i32,
+    pub material_colors: [Color; 4],
+    pub rarity: MonsterRarity,
+    pub affixes: Vec<MonsterAffix>,
+    pub loot: Vec<u32>,
+    pub spawner_id: Option<u32>,
+    pub faction: Faction,
+    pub lua_state: MonsterStateSlot,
 }
 
 pub type MonsterRc = Rc<RefCell<Monster>>;
-pub type MonsterArc = Arc<RwLock<Monster>>;
 
 static MONSTER_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 
@@ -56,17 +70,81 @@ impl Monster {
         Self {
             position: pos,
             hp: kind.max_hp,
-            kind,
             id,
             initialized: false,
-            accumulated_speed: 0,
+            name: kind.name.clone(),
+            max_hp: kind.max_hp,
+            speed: kind.speed,
+            melee_damage: kind.melee_damage,
+            material_colors: kind.material_colors,
+            rarity: MonsterRarity::Normal,
+            affixes: Vec::new(),
+            loot: Vec::new(),
+            spawner_id: None,
+            faction: kind.faction,
+            lua_state: MonsterStateSlot::default(),
+            kind,
+        }
+    }
+
+    /// Rolls a rarity for this monster and applies the resulting affixes,
+    /// stat overrides, palette and loot.
+    pub fn roll_rarity(&mut self, tier: u32, items: &Items, rng: &mut impl Rng) {
+        let rarity = MonsterRarity::roll(tier, rng);
+        if rarity == MonsterRarity::Normal {
+            return;
+        }
+
+        let affixes = rarity.roll_affixes(rng);
+        self.loot = rarity.roll_loot(tier, items, rng);
+        self.set_rarity(rarity, affixes);
+    }
+
+    pub fn set_rarity(&mut self, rarity: MonsterRarity, affixes: Vec<MonsterAffix>) {
+        self.rarity = rarity;
+        self.affixes = affixes;
+
+        self.max_hp = (self.kind.max_hp as f32 * rarity.hp_multiplier()).round() as u32;
+        self.speed = self.kind.speed;
+        self.melee_damage = self.kind.melee_damage;
+        self.material_colors = self.kind.material_colors;
+
+        if let Some(color) = rarity.palette_color() {
+            self.material_colors[1] = color;
+        }
+
+        if self.has_affix(MonsterAffix::Swift) {
+            self.speed = self.speed * 3 / 2;
+        }
+
+        self.hp = self.max_hp;
+
+        let mut name = String::new();
+        for affix in &self.affixes {
+            name.push_str(affix.name());
+            name.push(' ');
+        }
+        name.push_str(&self.kind.name);
+        self.name = name;
+    }
+
+    pub fn has_affix(&self, affix: MonsterAffix) -> bool {
+        self.affixes.contains(&affix)
+    }
+
+    /// Reduces incoming damage according to the monster's affixes.
+    pub fn mitigate_damage(&self, damage: i32) -> i32 {
+        if self.has_affix(MonsterAffix::Armored) && damage > 0 {
+            max(damage * 2 / 3, 1)
+        } else {
+            damage
         }
     }
 }
 
 impl Creature for Monster {
     fn name(&self) -> &str {
-        &self.kind.name
+        &self.name
     }
 
     fn pos(&self) -> Position {
@@ -78,11 +156,11 @@ impl Creature for Monster {
     }
 
     fn add_health(&mut self, amount: i32) {
-        self.hp = min(max((self.hp as i32) + amount, 0) as u32, self.kind.max_hp);
+        self.hp = min(max((self.hp as i32) + amount, 0) as u32, self.max_hp);
     }
 
     fn get_health(&self) -> (u32, u32) {
-        (self.hp, self.kind.max_hp)
+        (self.hp, self.max_hp)
     }
 
     fn draw(&self, material: &mut Material, offset: PointF) {
@@ -98,10 +176,10 @@ impl Creature for Monster {
             let sprite_size = Vec2::new(32.0, 32.0);
             graphics::graphics_manager::set_color_replacement_uniforms(
                 material,
-                self.kind.material_colors[0],
-                self.kind.material_colors[1],
-                self.kind.material_colors[2],
-                self.kind.material_colors[3],
+                self.material_colors[0],
+                self.material_colors[1],
+                self.material_colors[2],
+                self.material_colors[3],
             );
 
             let draw_params = DrawTextureParams {
@@ -167,5 +245,28 @@ impl UserData for Monster {
         );
 
         methods.add_method("get_id", |_, this, ()| Ok(this.id));
+
+        methods.add_method("get_state", |lua, this, ()| {
+            LuaInterface::get_monster_state(lua, &this.lua_state)
+        });
+
+        methods.add_method("get_faction", |_, this, ()| Ok(this.faction.name()));
+
+        methods.add_method_mut("set_faction", |_, this, name: String| {
+            let faction = Faction::from_name(&name)
+                .ok_or_else(|| mlua::Error::external(format!("Unknown faction `{}`", name)))?;
+            this.faction = faction;
+            Ok(())
+        });
+
+        methods.add_method("get_rarity", |_, this, ()| Ok(this.rarity.name()));
+
+        methods.add_method("get_affixes", |_, this, ()| {
+            Ok(this
+                .affixes
+                .iter()
+                .map(|affix| affix.name())
+                .collect::<Vec<_>>())
+        });
     }
 }
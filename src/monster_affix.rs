@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use macroquad::prelude::Color;
+use rand::{Rng, seq::SliceRandom};
+
+use crate::items::collection::Items;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonsterRarity {
+    Normal,
+    Elite,
+    Champion,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonsterAffix {
+    Swift,
+    Armored,
+    Vampiric,
+    Explosive,
+}
+
+const ALL_AFFIXES: [MonsterAffix; 4] = [
+    MonsterAffix::Swift,
+    MonsterAffix::Armored,
+    MonsterAffix::Vampiric,
+    MonsterAffix::Explosive,
+];
+
+impl MonsterRarity {
+    /// Rolls the rarity of a freshly spawned monster. Deeper tiers are more
+    /// likely to produce elites and champions.
+    pub fn roll(tier: u32, rng: &mut impl Rng) -> Self {
+        let champion_chance = (0.01 + 0.02 * tier as f64).min(0.15);
+        let elite_chance = (0.05 + 0.05 * tier as f64).min(0.4);
+
+        let roll = rng.gen_range(0.0..1.0);
+        if roll < champion_chance {
+            MonsterRarity::Champion
+        } else if roll < champion_chance + elite_chance {
+            MonsterRarity::Elite
+        } else {
+            MonsterRarity::Normal
+        }
+    }
+
+    pub fn affix_count(&self) -> usize {
+        match self {
+            MonsterRarity::Normal => 0,
+            MonsterRarity::Elite => 1,
+            MonsterRarity::Champion => 2,
+        }
+    }
+
+    pub fn hp_multiplier(&self) -> f32 {
+        match self {
+            MonsterRarity::Normal => 1.0,
+            MonsterRarity::Elite => 1.5,
+            MonsterRarity::Champion => 2.5,
+        }
+    }
+
+    /// Color used to replace the secondary palette slot of the sprite.
+    pub fn palette_color(&self) -> Option<Color> {
+        match self {
+            MonsterRarity::Normal => None,
+            MonsterRarity::Elite => Some(Color::from_rgba(255, 215, 0, 255)),
+            MonsterRarity::Champion => Some(Color::from_rgba(160, 32, 240, 255)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MonsterRarity::Normal => "Normal",
+            MonsterRarity::Elite => "Elite",
+            MonsterRarity::Champion => "Champion",
+        }
+    }
+
+    pub fn roll_affixes(&self, rng: &mut impl Rng) -> Vec<MonsterAffix> {
+        ALL_AFFIXES
+            .choose_multiple(rng, self.affix_count())
+            .copied()
+            .collect()
+    }
+
+    /// Picks item ids to drop when the monster dies. Champions roll from the
+    /// tier above the map when such items exist.
+    pub fn roll_loot(&self, tier: u32, items: &Items, rng: &mut impl Rng) -> Vec<u32> {
+        let (loot_tier, amount) = match self {
+            MonsterRarity::Normal => return Vec::new(),
+            MonsterRarity::Elite => (tier, 1),
+            MonsterRarity::Champion => (tier + 1, 2),
+        };
+
+        let candidates: Vec<u32> = items
+            .items_ids_by_tier
+            .get(loot_tier as usize)
+            .filter(|ids| !ids.is_empty())
+            .or_else(|| items.items_ids_by_tier.get(tier as usize))
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default();
+
+        candidates.choose_multiple(rng, amount).copied().collect()
+    }
+}
+
+impl MonsterAffix {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MonsterAffix::Swift => "Swift",
+            MonsterAffix::Armored => "Armored",
+            MonsterAffix::Vampiric => "Vampiric",
+            MonsterAffix::Explosive => "Explosive",
+        }
+    }
+}
@@ -30,6 +30,7 @@ use std::{
 };
 
 use crate::lua_interface::{LuaInterfaceRc, LuaScripted};
+use crate::scheduler::ACTION_COST_NORMAL;
 
 pub async fn load_monster_kinds(lua_interface_rc: &LuaInterfaceRc) -> MonsterKindsDataArc {
     let file: String = load_string("assets/monsters/monsters.json").await.unwrap();
@@ -98,6 +99,33 @@ impl MonsterKinds {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum Faction {
+    #[default]
+    Hostile,
+    Neutral,
+    Allied,
+}
+
+impl Faction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Faction::Hostile => "Hostile",
+            Faction::Neutral => "Neutral",
+            Faction::Allied => "Allied",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Hostile" => Some(Faction::Hostile),
+            "Neutral" => Some(Faction::Neutral),
+            "Allied" => Some(Faction::Allied),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(from = "MonsterKindHelper")]
 pub struct MonsterKind {
@@ -111,8 +139,21 @@ pub struct MonsterKind {
     pub max_hp: u32,
     pub speed: u32,
     pub melee_damage: i32,
+    // Time a melee attack takes, heavy hitters are slower
+    pub attack_cost: u32,
     #[serde(default)]
     pub flying: bool,
+    #[serde(default)]
+    pub opens_doors: bool,
+    // Glowing monsters light up this many tiles around them
+    #[serde(default)]
+    pub light_radius: u32,
+    #[serde(default)]
+    pub boss: bool,
+    #[serde(default)]
+    pub spawn_cap: Option<u32>,
+    #[serde(default)]
+    pub faction: Faction,
     pub script: Option<String>,
     #[serde(default)]
     pub scripted: bool,
@@ -134,8 +175,21 @@ struct MonsterKindHelper {
     pub max_hp: u32,
     pub speed: u32,
     pub melee_damage: i32,
+    #[serde(default = "default_attack_cost")]
+    pub attack_cost: u32,
     #[serde(default)]
     pub flying: bool,
+    #[serde(default)]
+    pub opens_doors: bool,
+    // Glowing monsters light up this many tiles around them
+    #[serde(default)]
+    pub light_radius: u32,
+    #[serde(default)]
+    pub boss: bool,
+    #[serde(default)]
+    pub spawn_cap: Option<u32>,
+    #[serde(default)]
+    pub faction: Faction,
     pub script: Option<String>,
     #[serde(default)]
     pub scripted: bool,
@@ -143,6 +197,10 @@ struct MonsterKindHelper {
     pub sprite_image: String,
 }
 
+fn default_attack_cost() -> u32 {
+    ACTION_COST_NORMAL
+}
+
 fn to_color(rgb: &[u8; 3]) -> Color {
     Color::new(
         rgb[0] as f32 / 255.0,
@@ -174,7 +232,13 @@ impl From<MonsterKindHelper> for MonsterKind {
             max_hp: helper.max_hp,
             speed: helper.speed,
             melee_damage: helper.melee_damage,
+            attack_cost: helper.attack_cost,
             flying: helper.flying,
+            opens_doors: helper.opens_doors,
+            light_radius: helper.light_radius,
+            boss: helper.boss,
+            spawn_cap: helper.spawn_cap,
+            faction: helper.faction,
             script: helper.script,
             scripted: helper.scripted,
             script_id: 0,
@@ -219,6 +283,7 @@ impl LuaScripted for MonsterKind {
             "on_spawn".to_string(),
             "on_update".to_string(),
             "on_death".to_string(),
+            "on_phase_change".to_string(),
         ]
     }
 }
@@ -25,18 +25,22 @@ use crate::graphics::graphics_manager::GraphicsManager;
 use crate::items::base_item::Item;
 use crate::items::holdable::*;
 use crate::maps::TILE_SIZE;
+use crate::maps::visibility::VisibilityGrid;
 use crate::player_spell::PlayerSpell;
 use crate::position::Position;
+use crate::scheduler::ACTION_COST_NORMAL;
 use crate::ui::point_f::PointF;
 use crate::{graphics, spell_type};
 use macroquad::prelude::*;
 use mlua::{Table, UserData, UserDataMethods};
 use std::cell::RefCell;
 use std::cmp::{max, min};
-use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
+/// Even empty-handed the player can make out what is right next to them.
+const PLAYER_BASE_LIGHT_RADIUS: u32 = 1;
+
 #[derive(Clone)]
 pub struct Equipment {
     pub weapon: Option<Weapon>,
@@ -58,14 +62,13 @@ pub struct Player {
     pub intelligence: u32,
 
     pub sp: u32,
-
-    pub accumulated_speed: u32,
+    pub keys: u32,
 
     pub position: Position,
     pub goal_position: Option<Position>,
     pub spells: Vec<PlayerSpell>,
     pub selected_spell: Option<u8>,
-    pub line_of_sight: HashSet<Position>,
+    pub line_of_sight: VisibilityGrid,
 
     pub equipment: Equipment,
 
@@ -93,12 +96,12 @@ impl Player {
             dexterity: 10,
             intelligence: 10,
             sp: 1,
-            accumulated_speed: 0,
+            keys: 0,
             position: pos,
             goal_position: None,
             spells: vec![],
             selected_spell: None,
-            line_of_sight: HashSet::new(),
+            line_of_sight: VisibilityGrid::default(),
             equipment: Equipment {
                 weapon: None,
                 shield: None,
@@ -144,6 +147,38 @@ impl Player {
         self.dexterity * 10
     }
 
+    /// Whether the wielded weapon can dig through destructible terrain.
+    pub fn can_dig(&self) -> bool {
+        self.equipment
+            .weapon
+            .as_ref()
+            .is_some_and(|weapon| weapon.digs)
+    }
+
+    pub fn attack_cost(&self) -> u32 {
+        self.equipment
+            .weapon
+            .as_ref()
+            .and_then(|weapon| weapon.attack_cost)
+            .unwrap_or(ACTION_COST_NORMAL)
+    }
+
+    /// How far the player lights up the dark, from the brightest thing they carry.
+    pub fn light_radius(&self) -> u32 {
+        let equipment = &self.equipment;
+        [
+            equipment.weapon.as_ref().map(|w| &w.base_holdable),
+            equipment.shield.as_ref().map(|s| &s.base_holdable),
+            equipment.helmet.as_ref().map(|h| &h.base_holdable),
+            equipment.armor.as_ref().map(|a| &a.base_holdable),
+            equipment.boots.as_ref().map(|b| &b.base_holdable),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|holdable| holdable.light_radius)
+        .fold(PLAYER_BASE_LIGHT_RADIUS, max)
+    }
+
     pub fn add_item(&mut self, item: Item) {
         match item {
             Item::Weapon(w) => self.equipment.weapon = Some(w),
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::rc::Rc;
+
+/// Time units a regular action (a step, a basic attack) takes at base speed.
+pub const ACTION_COST_NORMAL: u32 = 100;
+/// Speed at which an action takes exactly its cost in time units.
+pub const BASE_SPEED: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Actor {
+    Player,
+    Monster(u32),
+    /// A timed event scheduled from Lua, keyed by its event id.
+    Event(u32),
+    /// A status effect ticking on a creature, keyed by its id in `StatusEffects`.
+    Status(u32),
+}
+
+impl Actor {
+    /// Tie-break when several actors are due at the same time: the world
+    /// resolves first and the player always acts last.
+    fn priority(&self) -> u8 {
+        match self {
+            Actor::Event(_) | Actor::Status(_) => 0,
+            Actor::Monster(_) => 1,
+            Actor::Player => 2,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    time: u64,
+    seq: u64,
+    actor: Actor,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .cmp(&other.time)
+            .then(self.actor.priority().cmp(&other.actor.priority()))
+            .then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Energy based turn scheduler. Every actor is queued at the time its next
+/// action becomes available; acting pushes it back by `cost * BASE_SPEED / speed`.
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<Entry>>,
+    scheduled: HashSet<Actor>,
+    now: u64,
+    seq: u64,
+    next_event_id: u32,
+}
+
+pub type SchedulerRc = Rc<RefCell<Scheduler>>;
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            scheduled: HashSet::new(),
+            now: 0,
+            seq: 0,
+            next_event_id: 1,
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Time an action of `cost` takes for an actor moving at `speed`.
+    pub fn delay_for(cost: u32, speed: u32) -> u64 {
+        cost as u64 * BASE_SPEED as u64 / speed.max(1) as u64
+    }
+
+    pub fn is_scheduled(&self, actor: Actor) -> bool {
+        self.scheduled.contains(&actor)
+    }
+
+    /// Queues `actor` to act `delay` time units from now.
+    pub fn schedule(&mut self, actor: Actor, delay: u64) {
+        self.seq += 1;
+        self.scheduled.insert(actor);
+        self.queue.push(Reverse(Entry {
+            time: self.now + delay,
+            seq: self.seq,
+            actor,
+        }));
+    }
+
+    /// Reserves a new timed event id and queues it `delay` time units from now.
+    pub fn schedule_event(&mut self, delay: u64) -> u32 {
+        let id = self.next_event_id;
+        self.next_event_id += 1;
+        self.schedule(Actor::Event(id), delay);
+        id
+    }
+
+    /// Pops the next actor due and advances the clock to its time.
+    pub fn pop(&mut self) -> Option<Actor> {
+        let Reverse(entry) = self.queue.pop()?;
+        self.now = self.now.max(entry.time);
+        self.scheduled.remove(&entry.actor);
+        Some(entry.actor)
+    }
+}
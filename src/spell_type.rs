@@ -37,6 +37,13 @@ pub enum SpellKind {
     Summon,
 }
 
+/// Extra effects a spell has on top of its kind.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum SpellEffect {
+    /// Destroys the destructible terrain bordering the area.
+    Explosion,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 pub enum SpellStrategy {
     Aim,
@@ -56,12 +63,22 @@ pub struct SpellType {
     pub basepower: u32,     // Base Power of the spell
     pub cost: u32,          // Cost to buy
     #[serde(default)]
+    pub summon_kind: Option<u32>, // Monster kind brought in by Summon spells
+    #[serde(default)]
+    pub action_cost: Option<u32>, // Time it takes to cast, defaults to a regular action
+    #[serde(default)]
+    pub effects: Vec<SpellEffect>,
+    #[serde(default)]
     pub sprite_path: String,
     #[serde(skip)]
     pub sprite: Option<Arc<RwLock<Texture2D>>>,
 }
 
 impl SpellType {
+    pub fn has_effect(&self, effect: SpellEffect) -> bool {
+        self.effects.contains(&effect)
+    }
+
     fn draw(&self, offset: PointF) {
         if let Some(sprite_arc) = &self.sprite {}
     }
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+
+/// What a status effect does each time it ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    Poison { damage: i32 },
+}
+
+impl StatusKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusKind::Poison { .. } => "poison",
+        }
+    }
+}
+
+/// A lingering effect on a creature. It is queued in the scheduler as its own
+/// actor and ticks every `ACTION_COST_NORMAL` time units until it runs out.
+#[derive(Clone, Copy, Debug)]
+pub struct StatusEffect {
+    pub target: u32,
+    pub kind: StatusKind,
+    pub ticks_left: u32,
+}
+
+/// Status effects currently running, keyed by the id the scheduler knows them by.
+pub struct StatusEffects {
+    effects: HashMap<u32, StatusEffect>,
+    next_id: u32,
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        Self {
+            effects: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Stores a new effect and returns the id to schedule it under.
+    pub fn add(&mut self, effect: StatusEffect) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.effects.insert(id, effect);
+        id
+    }
+
+    /// Uses up one tick of the effect `id`, dropping it once none are left.
+    /// Returns the effect as it was before the tick.
+    pub fn tick(&mut self, id: u32) -> Option<StatusEffect> {
+        let effect = self.effects.get_mut(&id)?;
+        let before = *effect;
+        effect.ticks_left = effect.ticks_left.saturating_sub(1);
+        if effect.ticks_left == 0 {
+            self.effects.remove(&id);
+        }
+        Some(before)
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.effects.remove(&id);
+    }
+}
@@ -23,22 +23,41 @@
 use std::sync::{Arc, RwLock};
 
 use macroquad::prelude::*;
+use once_cell::sync::OnceCell;
 
 use crate::{
-    items::{base_item::ItemKind, orb::Orb, teleport::Teleport},
-    maps::{GRID_HEIGHT, GRID_WIDTH, TILE_SIZE},
+    items::base_item::ItemKind,
+    maps::TILE_SIZE,
     position::Position,
+    tile_def::{TileDef, get_tile_defs},
+    trap::Trap,
     ui::point_f::PointF,
 };
 
 pub const NO_CREATURE: u32 = 0;
 pub const PLAYER_CREATURE_ID: u32 = u32::MAX; // or any large unique value
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub enum TileKind {
-    Chasm,
-    Wall,
-    Floor,
+/// Index into the tile definitions loaded from `assets/tiles/tiles.json`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TileKind(pub u16);
+
+impl TileKind {
+    // Built-in kinds the generators rely on, the rest are only known by name
+    pub const CHASM: TileKind = TileKind(0);
+    pub const WALL: TileKind = TileKind(1);
+    pub const FLOOR: TileKind = TileKind(2);
+
+    pub fn def(self) -> &'static TileDef {
+        get_tile_defs().get(self)
+    }
+}
+
+/// State of a door tile. Closed and locked doors block movement and sight.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DoorState {
+    Open,
+    Closed,
+    Locked,
 }
 
 bitflags::bitflags! {
@@ -56,68 +75,55 @@ bitflags::bitflags! {
     }
 }
 
-pub struct TileFactory {
-    chasm: Tile,
-    floor: Tile,
-    wall: Tile,
-}
-
-impl TileFactory {
-    pub fn new() -> Self {
-        Self {
-            chasm: Tile::new(TileKind::Chasm),
-            floor: Tile::new(TileKind::Floor),
-            wall: Tile::new(TileKind::Wall),
+// One sprite per tile definition, indexed by kind
+static TILE_SPRITES: OnceCell<Vec<Option<Texture2D>>> = OnceCell::new();
+
+/// Loads the sprite of every tile definition that has one. Tiles only carry their
+/// kind, and look their sprite up here when drawn.
+pub async fn load_tile_sprites() {
+    let mut sprites = Vec::new();
+    for def in get_tile_defs().iter() {
+        let Some(path) = &def.sprite else {
+            sprites.push(None);
+            continue;
+        };
+        match load_texture(path).await {
+            Ok(texture) => {
+                texture.set_filter(FilterMode::Nearest);
+                sprites.push(Some(texture));
+            }
+            Err(e) => {
+                eprintln!("Failed to load {} texture: {}", def.name, e);
+                sprites.push(None);
+            }
         }
     }
-    pub async fn init(&mut self) {
-        {
-            let texture = load_texture("assets/sprites/scenario/chasm.png")
-                .await
-                .unwrap();
-            texture.set_filter(FilterMode::Nearest);
-            self.chasm.sprite = Some(Arc::new(RwLock::new(texture)));
-        }
-        {
-            let texture_result = load_texture("assets/sprites/scenario/floor.png").await;
-            let mut texture = match texture_result {
-                Ok(tex) => tex,
-                Err(e) => {
-                    eprintln!("Failed to load floor texture: {}", e);
-                    return;
-                }
-            };
+    TILE_SPRITES
+        .set(sprites)
+        .unwrap_or_else(|_| panic!("TILE_SPRITES already set!"));
+}
 
-            texture.set_filter(FilterMode::Nearest);
-            self.floor.sprite = Some(Arc::new(RwLock::new(texture)));
-        }
-    }
-    pub fn create_tile(&self, kind: TileKind) -> Tile {
-        match kind {
-            TileKind::Chasm => self.chasm.clone(),
-            TileKind::Wall => self.wall.clone(),
-            TileKind::Floor => self.floor.clone(),
-        }
-    }
+fn tile_sprite(kind: TileKind) -> Option<&'static Texture2D> {
+    TILE_SPRITES.get()?.get(kind.0 as usize)?.as_ref()
 }
 
-#[derive(Clone, Debug)]
+/// Terrain of a single map cell. Kept small and `Copy`, creatures and items live in
+/// the sparse layers of `TileMap` and sprites in a registry looked up when drawing.
+#[derive(Clone, Copy, Debug)]
 pub struct Tile {
     kind: TileKind,
     pub edge: EdgeKind,
-    pub creature: u32,
-    pub items: Vec<ItemKind>,
-    pub sprite: Option<Arc<RwLock<Texture2D>>>,
+    pub door: Option<DoorState>,
+    pub trap: Option<Trap>,
 }
 
 impl Tile {
-    pub(in crate::tile) fn new(kind: TileKind) -> Self {
+    pub fn new(kind: TileKind) -> Self {
         Self {
             kind,
-            creature: NO_CREATURE,
-            items: Vec::new(),
+            door: kind.def().door.then_some(DoorState::Closed),
+            trap: None,
             edge: EdgeKind::NONE,
-            sprite: None,
         }
     }
 
@@ -125,67 +131,44 @@ impl Tile {
         self.kind
     }
 
-    pub fn has_enemy(&self) -> bool {
-        self.creature != NO_CREATURE && self.creature != PLAYER_CREATURE_ID
-    }
-
-    pub fn is_walkable(&self) -> bool {
-        self.kind == TileKind::Floor
-            && (self.creature == NO_CREATURE || self.creature == PLAYER_CREATURE_ID)
-    }
-
-    pub fn is_blocking(&self) -> bool {
-        self.kind == TileKind::Wall
-            || (self.creature != NO_CREATURE && self.creature != PLAYER_CREATURE_ID)
+    pub fn def(&self) -> &'static TileDef {
+        self.kind.def()
     }
 
-    pub fn is_solid_blocking(&self) -> bool {
-        self.kind == TileKind::Wall
+    pub fn is_door_closed(&self) -> bool {
+        matches!(self.door, Some(DoorState::Closed | DoorState::Locked))
     }
 
-    pub fn has_container(&self) -> bool {
-        self.items
-            .iter()
-            .any(|item| matches!(item, ItemKind::Container(_)))
+    /// Whether the terrain can be walked on, leaving creatures aside.
+    pub fn is_passable(&self) -> bool {
+        self.def().walkable && !self.is_door_closed()
     }
 
-    pub fn get_top_item(&self) -> Option<&ItemKind> {
-        self.items.last()
+    /// Whether the terrain keeps flyers out, leaving creatures aside.
+    pub fn blocks_flyers(&self) -> bool {
+        !self.def().flyer_passable || self.is_door_closed()
     }
 
-    pub fn add_orb(&mut self) {
-        let orb = Orb {};
-        self.items.push(ItemKind::Orb(orb));
+    pub fn is_solid_blocking(&self) -> bool {
+        self.def().blocks_projectiles || self.is_door_closed()
     }
 
-    pub fn add_teleport(&mut self) {
-        let teleport = Teleport {};
-        self.items.push(ItemKind::Teleport(teleport));
+    pub fn blocks_sight(&self) -> bool {
+        self.def().blocks_sight || self.is_door_closed()
     }
 
     fn has_edge(&self, kind: EdgeKind) -> bool {
         self.edge.contains(kind)
     }
 
-    pub fn remove_item(&mut self, index: usize) -> Option<ItemKind> {
-        if index < self.items.len() {
-            Some(self.items.remove(index))
-        } else {
-            None
-        }
-    }
-
-    pub fn is_border(&self, pos: &Position) -> bool {
-        self.kind == TileKind::Floor
-            && (pos.x == 0 || pos.y == 0 || pos.x == GRID_WIDTH - 1 || pos.y == GRID_HEIGHT - 1)
-    }
-
     pub fn draw_edges(&self, pos: Position, offset: PointF) {
         const TILE_PX: f32 = 16.0;
         let mut px = 0.0 * TILE_PX;
         let mut py = 1.0 * TILE_PX;
         let dest = Vec2::new(32.0, 32.0);
-        let tex = &self.sprite.as_ref().unwrap().read().unwrap();
+        let Some(tex) = tile_sprite(self.kind) else {
+            return;
+        };
         let mut drawn = EdgeKind::NONE;
 
         if self.has_edge(EdgeKind::TOP) && self.has_edge(EdgeKind::RIGHT) {
@@ -420,97 +403,189 @@ impl Tile {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &self,
         pos: Position,
         offset: PointF,
+        is_border: bool,
         borders_locked: bool,
+        items: &[ItemKind],
+        occupied: bool,
         animating_effect: Option<&Arc<RwLock<Texture2D>>>,
         animate_for: f32,
     ) {
-        let color = match self.kind {
-            TileKind::Floor => Color {
-                r: 0.5,
-                g: 0.5,
-                b: 0.5,
-                a: 1.0,
-            },
-            TileKind::Wall => Color {
-                r: 0.3,
-                g: 0.3,
-                b: 0.3,
-                a: 1.0,
-            },
-            TileKind::Chasm => Color {
+        if !occupied && items.is_empty() {
+            self.draw_ground(pos, offset, is_border, borders_locked);
+        }
+
+        Self::draw_items(items, pos, offset, borders_locked);
+
+        if let Some(effect) = animating_effect {
+            let texture = effect.read().unwrap();
+            let frame = ((0.2 - animate_for) * 20.0).floor() as usize;
+            let draw_params = DrawTextureParams {
+                dest_size: Some(Vec2::new(32.0, 32.0)),
+                source: Some(Rect {
+                    x: 0.0,
+                    y: frame as f32 * 16.0,
+                    w: 16.0,
+                    h: 16.0,
+                }),
+                ..Default::default()
+            };
+
+            let x = offset.x + pos.x as f32 * TILE_SIZE;
+            let y = offset.y + pos.y as f32 * TILE_SIZE;
+
+            draw_texture_ex(&texture, x, y, WHITE, draw_params);
+        }
+    }
+
+    /// Draws a tile the player has seen before but can't see right now, with the
+    /// items it had back then and no creatures.
+    pub fn draw_remembered(
+        &self,
+        pos: Position,
+        offset: PointF,
+        is_border: bool,
+        borders_locked: bool,
+        items: &[ItemKind],
+    ) {
+        if items.is_empty() {
+            self.draw_ground(pos, offset, is_border, borders_locked);
+        }
+
+        Self::draw_items(items, pos, offset, borders_locked);
+
+        draw_rectangle(
+            offset.x + pos.x as f32 * TILE_SIZE,
+            offset.y + pos.y as f32 * TILE_SIZE,
+            TILE_SIZE,
+            TILE_SIZE,
+            Color {
                 r: 0.0,
                 g: 0.0,
                 b: 0.0,
-                a: 1.0,
+                a: 0.6,
             },
-        };
+        );
+    }
 
-        if self.creature == NO_CREATURE && self.items.is_empty() {
-            if self.sprite.is_some() {
-                if self.kind == TileKind::Chasm {
-                    self.draw_edges(pos, offset);
-                } else if self.kind == TileKind::Floor {
-                    if let Some(sprite_arc) = &self.sprite {
-                        let sprite = sprite_arc.read().unwrap();
-                        let draw_params = DrawTextureParams {
-                            dest_size: Some(Vec2::new(32.0, 32.0)),
-                            source: Some(Rect {
-                                x: 0.0,
-                                y: 0.0,
-                                w: 16.0,
-                                h: 16.0,
-                            }),
-                            ..Default::default()
-                        };
-
-                        let x = offset.x + pos.x as f32 * TILE_SIZE;
-                        let y = offset.y + pos.y as f32 * TILE_SIZE;
-
-                        draw_texture_ex(&sprite, x, y, WHITE, draw_params);
-                    }
-                }
+    fn draw_ground(&self, pos: Position, offset: PointF, is_border: bool, borders_locked: bool) {
+        let color = self.def().color();
+
+        if let Some(sprite) = tile_sprite(self.kind) {
+            if self.kind == TileKind::CHASM {
+                self.draw_edges(pos, offset);
             } else {
+                let draw_params = DrawTextureParams {
+                    dest_size: Some(Vec2::new(32.0, 32.0)),
+                    source: Some(Rect {
+                        x: 0.0,
+                        y: 0.0,
+                        w: 16.0,
+                        h: 16.0,
+                    }),
+                    ..Default::default()
+                };
+
+                let x = offset.x + pos.x as f32 * TILE_SIZE;
+                let y = offset.y + pos.y as f32 * TILE_SIZE;
+
+                draw_texture_ex(sprite, x, y, WHITE, draw_params);
+            }
+        } else {
+            draw_rectangle(
+                offset.x + pos.x as f32 * TILE_SIZE,
+                offset.y + pos.y as f32 * TILE_SIZE,
+                TILE_SIZE - 1.0,
+                TILE_SIZE - 1.0,
+                color,
+            );
+        }
+
+        match self.door {
+            Some(DoorState::Open) => {
+                // An open door only leaves its frame behind
                 draw_rectangle(
-                    offset.x + pos.x as f32 * TILE_SIZE,
+                    offset.x + pos.x as f32 * TILE_SIZE + TILE_SIZE / 4.0,
                     offset.y + pos.y as f32 * TILE_SIZE,
+                    TILE_SIZE / 2.0,
                     TILE_SIZE - 1.0,
-                    TILE_SIZE - 1.0,
-                    color,
+                    TileKind::FLOOR.def().color(),
                 );
             }
-
-            if self.is_border(&pos) {
-                // Draw border
-                let border_color = if borders_locked {
-                    Color {
-                        r: 0.8,
-                        g: 0.2,
-                        b: 0.2,
-                        a: 1.0,
-                    } // Red for locked borders
-                } else {
+            Some(DoorState::Locked) => {
+                draw_circle(
+                    offset.x + pos.x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+                    offset.y + pos.y as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+                    TILE_SIZE / 8.0,
                     Color {
-                        r: 0.2,
-                        g: 0.8,
-                        b: 0.2,
+                        r: 1.0,
+                        g: 0.85,
+                        b: 0.0,
                         a: 1.0,
-                    } // Green for unlocked borders
-                };
-                draw_rectangle(
-                    offset.x + pos.x as f32 * TILE_SIZE,
-                    offset.y + pos.y as f32 * TILE_SIZE,
-                    TILE_SIZE - 1.0,
-                    TILE_SIZE - 1.0,
-                    border_color,
+                    },
                 );
             }
+            Some(DoorState::Closed) | None => {}
+        }
+
+        if let Some(trap) = self.trap.filter(|trap| !trap.hidden) {
+            let (x, y) = (
+                offset.x + pos.x as f32 * TILE_SIZE,
+                offset.y + pos.y as f32 * TILE_SIZE,
+            );
+            let inset = TILE_SIZE / 4.0;
+            let color = trap.kind().color();
+            draw_line(
+                x + inset,
+                y + inset,
+                x + TILE_SIZE - inset,
+                y + TILE_SIZE - inset,
+                3.0,
+                color,
+            );
+            draw_line(
+                x + TILE_SIZE - inset,
+                y + inset,
+                x + inset,
+                y + TILE_SIZE - inset,
+                3.0,
+                color,
+            );
         }
 
-        for item in &self.items {
+        if is_border {
+            // Draw border
+            let border_color = if borders_locked {
+                Color {
+                    r: 0.8,
+                    g: 0.2,
+                    b: 0.2,
+                    a: 1.0,
+                } // Red for locked borders
+            } else {
+                Color {
+                    r: 0.2,
+                    g: 0.8,
+                    b: 0.2,
+                    a: 1.0,
+                } // Green for unlocked borders
+            };
+            draw_rectangle(
+                offset.x + pos.x as f32 * TILE_SIZE,
+                offset.y + pos.y as f32 * TILE_SIZE,
+                TILE_SIZE - 1.0,
+                TILE_SIZE - 1.0,
+                border_color,
+            );
+        }
+    }
+
+    fn draw_items(items: &[ItemKind], pos: Position, offset: PointF, borders_locked: bool) {
+        for item in items {
             match item {
                 ItemKind::Orb(_) => {
                     draw_circle(
@@ -525,8 +600,28 @@ impl Tile {
                         },
                     );
                 }
-                ItemKind::Teleport(_) => {
-                    let teleport_color = if borders_locked {
+                ItemKind::Key(_) => {
+                    draw_circle(
+                        offset.x + pos.x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+                        offset.y + pos.y as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+                        TILE_SIZE / 6.0,
+                        Color {
+                            r: 1.0,
+                            g: 0.85,
+                            b: 0.0,
+                            a: 1.0,
+                        },
+                    );
+                }
+                ItemKind::Teleport(teleport) => {
+                    let teleport_color = if teleport.up {
+                        Color {
+                            r: 1.0,
+                            g: 1.0,
+                            b: 1.0,
+                            a: 1.0,
+                        } // White for the way up
+                    } else if borders_locked {
                         Color {
                             r: 0.8,
                             g: 0.2,
@@ -574,25 +669,5 @@ impl Tile {
                 _ => {}
             }
         }
-
-        if let Some(effect) = animating_effect {
-            let texture = effect.read().unwrap();
-            let frame = ((0.2 - animate_for) * 20.0).floor() as usize;
-            let draw_params = DrawTextureParams {
-                dest_size: Some(Vec2::new(32.0, 32.0)),
-                source: Some(Rect {
-                    x: 0.0,
-                    y: frame as f32 * 16.0,
-                    w: 16.0,
-                    h: 16.0,
-                }),
-                ..Default::default()
-            };
-
-            let x = offset.x + pos.x as f32 * TILE_SIZE;
-            let y = offset.y + pos.y as f32 * TILE_SIZE;
-
-            draw_texture_ex(&texture, x, y, WHITE, draw_params);
-        }
     }
 }
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use macroquad::prelude::*;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::from_str;
+
+use crate::{scheduler::ACTION_COST_NORMAL, tile::TileKind};
+
+/// Added to the path cost of tiles that hurt, so monsters walk around them when they can.
+const HAZARD_PATH_PENALTY: u32 = 500;
+
+/// What happens to a creature entering the tile.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum OnStepEffect {
+    Damage { amount: i32 },
+}
+
+/// A terrain type from `assets/tiles/tiles.json`. The `id` is the `TileKind` stored in maps.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TileDef {
+    pub id: u16,
+    pub name: String,
+    // Used by prefabs and map dumps
+    pub glyph: char,
+    #[serde(default)]
+    pub sprite: Option<String>,
+    // Drawn when there is no sprite
+    pub color: [f32; 4],
+    pub walkable: bool,
+    pub blocks_sight: bool,
+    pub blocks_projectiles: bool,
+    // Flying monsters can cross it even when it is not walkable
+    pub flyer_passable: bool,
+    // Energy spent entering the tile, 100 being a normal step
+    #[serde(default = "default_movement_cost")]
+    pub movement_cost: u32,
+    #[serde(default)]
+    pub on_step: Option<OnStepEffect>,
+    // Tiles of this kind start out as closed doors
+    #[serde(default)]
+    pub door: bool,
+    // Glowing tiles light up this many tiles around them
+    #[serde(default)]
+    pub light_radius: u32,
+    // Name of the tile left behind once destroyed, indestructible when unset
+    #[serde(default)]
+    pub destroyed_into: Option<String>,
+}
+
+fn default_movement_cost() -> u32 {
+    ACTION_COST_NORMAL
+}
+
+impl TileDef {
+    pub fn color(&self) -> Color {
+        let [r, g, b, a] = self.color;
+        Color { r, g, b, a }
+    }
+
+    /// Cost used by the pathfinder, 100 being a normal step.
+    pub fn path_cost(&self) -> u32 {
+        match self.on_step {
+            Some(OnStepEffect::Damage { .. }) => self.movement_cost + HAZARD_PATH_PENALTY,
+            None => self.movement_cost,
+        }
+    }
+}
+
+pub struct TileDefs {
+    defs: Vec<TileDef>,
+}
+
+impl TileDefs {
+    /// Parses and checks the contents of a `tiles.json` file.
+    pub fn from_json(file: &str) -> TileDefs {
+        let mut defs: Vec<TileDef> = from_str(file).unwrap();
+        defs.sort_by_key(|def| def.id);
+
+        for (index, def) in defs.iter().enumerate() {
+            if def.id as usize != index {
+                panic!(
+                    "Tile ids must be contiguous, {} has id {}",
+                    def.name, def.id
+                );
+            }
+        }
+        for builtin in [TileKind::CHASM, TileKind::WALL, TileKind::FLOOR] {
+            if builtin.0 as usize >= defs.len() {
+                panic!("tiles.json is missing built-in tile {}", builtin.0);
+            }
+        }
+
+        let defs = TileDefs { defs };
+        for def in defs.iter() {
+            if let Some(name) = &def.destroyed_into
+                && defs.by_name(name).is_none()
+            {
+                panic!("{} is destroyed into unknown tile {}", def.name, name);
+            }
+        }
+
+        defs
+    }
+
+    pub fn get(&self, kind: TileKind) -> &TileDef {
+        &self.defs[kind.0 as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TileDef> {
+        self.defs.iter()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<TileKind> {
+        self.defs
+            .iter()
+            .find(|def| def.name.eq_ignore_ascii_case(name))
+            .map(|def| TileKind(def.id))
+    }
+
+    pub fn by_glyph(&self, glyph: char) -> Option<TileKind> {
+        self.defs
+            .iter()
+            .find(|def| def.glyph == glyph)
+            .map(|def| TileKind(def.id))
+    }
+
+    /// What a tile of `kind` turns into when destroyed, `None` if it can't be.
+    pub fn remains_of(&self, kind: TileKind) -> Option<TileKind> {
+        self.get(kind)
+            .destroyed_into
+            .as_deref()
+            .and_then(|name| self.by_name(name))
+    }
+}
+
+pub async fn load_tile_defs() -> TileDefs {
+    let file = load_string("assets/tiles/tiles.json").await.unwrap();
+    TileDefs::from_json(&file)
+}
+
+pub static TILE_DEFS: OnceCell<TileDefs> = OnceCell::new();
+
+pub fn set_global_tile_defs(defs: TileDefs) {
+    TILE_DEFS
+        .set(defs)
+        .unwrap_or_else(|_| panic!("GLOBAL_TILE_DEFS already set!"));
+}
+
+pub fn get_tile_defs() -> &'static TileDefs {
+    TILE_DEFS.get().expect("GLOBAL_TILE_DEFS not initialized")
+}
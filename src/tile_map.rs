@@ -20,22 +20,185 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::position::Position;
-use crate::tile::Tile;
+use std::collections::HashMap;
 use std::ops::{Index, IndexMut};
 
+use crate::items::base_item::ItemKind;
+use crate::items::{key::Key, orb::Orb, teleport::Teleport};
+use crate::position::Position;
+use crate::tile::{EdgeKind, NO_CREATURE, PLAYER_CREATURE_ID, Tile, TileKind};
+
+/// Terrain of a map as one flat row-major grid, with whoever stands on a tile and
+/// whatever lies on it kept in sparse layers, since most tiles have neither.
 #[derive(Clone, Debug)]
 pub struct TileMap {
-    tiles: Vec<Vec<Tile>>,
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+    creatures: HashMap<Position, u32>,
+    items: HashMap<Position, Vec<ItemKind>>,
+    // Bumped on every mutable access to the terrain, so sight can be cached
+    revision: u64,
 }
 
 impl TileMap {
+    /// Takes the column-major grid the generators carve, `tiles[x][y]`.
     pub fn new(tiles: Vec<Vec<Tile>>) -> Self {
-        Self { tiles }
+        let width = tiles.len();
+        let height = tiles[0].len();
+        let mut flat = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for column in &tiles {
+                flat.push(column[y]);
+            }
+        }
+        Self {
+            width,
+            height,
+            tiles: flat,
+            creatures: HashMap::new(),
+            items: HashMap::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes whenever a tile may have been modified. Creatures and items
+    /// coming and going leave it alone.
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
 
     pub fn in_bounds(&self, pos: Position) -> bool {
-        pos.x < self.tiles.len() && pos.y < self.tiles[0].len()
+        pos.x < self.width() && pos.y < self.height()
+    }
+
+    /// Walkable tiles on the outer ring lead to the neighboring map.
+    pub fn is_border(&self, pos: Position) -> bool {
+        self.in_bounds(pos)
+            && self[pos].def().walkable
+            && (pos.x == 0 || pos.y == 0 || pos.x == self.width() - 1 || pos.y == self.height() - 1)
+    }
+
+    /// Sides and corners of the chasm at `pos` that border walkable ground, which
+    /// is where its rim gets drawn. Anything but a chasm has no edges.
+    pub fn chasm_edges(&self, pos: Position) -> EdgeKind {
+        let mut edge = EdgeKind::NONE;
+        if self[pos].kind() != TileKind::CHASM {
+            return edge;
+        }
+        let neighbors = [
+            (0, -1, EdgeKind::TOP),
+            (1, 0, EdgeKind::RIGHT),
+            (0, 1, EdgeKind::BOTTOM),
+            (-1, 0, EdgeKind::LEFT),
+            (1, -1, EdgeKind::TOP_RIGHT),
+            (1, 1, EdgeKind::BOTTOM_RIGHT),
+            (-1, 1, EdgeKind::BOTTOM_LEFT),
+            (-1, -1, EdgeKind::TOP_LEFT),
+        ];
+        for (dx, dy, side) in neighbors {
+            let (x, y) = (pos.x as isize + dx, pos.y as isize + dy);
+            if x < 0 || y < 0 {
+                continue;
+            }
+            let neighbor = Position::new(x as usize, y as usize);
+            if self.in_bounds(neighbor) && self[neighbor].def().walkable {
+                edge |= side;
+            }
+        }
+        edge
+    }
+
+    /// Id of the creature on `pos`, `NO_CREATURE` when there is none.
+    pub fn creature(&self, pos: Position) -> u32 {
+        self.creatures.get(&pos).copied().unwrap_or(NO_CREATURE)
+    }
+
+    pub fn set_creature(&mut self, pos: Position, id: u32) {
+        if id == NO_CREATURE {
+            self.creatures.remove(&pos);
+        } else {
+            self.creatures.insert(pos, id);
+        }
+    }
+
+    pub fn has_monster(&self, pos: Position) -> bool {
+        let creature = self.creature(pos);
+        creature != NO_CREATURE && creature != PLAYER_CREATURE_ID
+    }
+
+    /// Whether the player or a monster could step on `pos` right now.
+    pub fn is_walkable(&self, pos: Position) -> bool {
+        self[pos].is_passable() && !self.has_monster(pos)
+    }
+
+    /// Whether `pos` is closed even to flyers right now.
+    pub fn is_blocking(&self, pos: Position) -> bool {
+        self[pos].blocks_flyers() || self.has_monster(pos)
+    }
+
+    pub fn items(&self, pos: Position) -> &[ItemKind] {
+        self.items.get(&pos).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn top_item(&self, pos: Position) -> Option<&ItemKind> {
+        self.items(pos).last()
+    }
+
+    pub fn has_container(&self, pos: Position) -> bool {
+        self.items(pos)
+            .iter()
+            .any(|item| matches!(item, ItemKind::Container(_)))
+    }
+
+    pub fn add_item(&mut self, pos: Position, item: ItemKind) {
+        self.items.entry(pos).or_default().push(item);
+    }
+
+    pub fn add_orb(&mut self, pos: Position) {
+        self.add_item(pos, ItemKind::Orb(Orb {}));
+    }
+
+    pub fn add_key(&mut self, pos: Position) {
+        self.add_item(pos, ItemKind::Key(Key {}));
+    }
+
+    pub fn add_teleport(&mut self, pos: Position) {
+        self.add_item(pos, ItemKind::Teleport(Teleport { up: false }));
+    }
+
+    pub fn add_upstairs(&mut self, pos: Position) {
+        self.add_item(pos, ItemKind::Teleport(Teleport { up: true }));
+    }
+
+    pub fn remove_item(&mut self, pos: Position, index: usize) -> Option<ItemKind> {
+        let items = self.items.get_mut(&pos)?;
+        if index >= items.len() {
+            return None;
+        }
+        let item = items.remove(index);
+        if items.is_empty() {
+            self.items.remove(&pos);
+        }
+        Some(item)
+    }
+
+    /// Keeps only the items on `pos` for which `keep` returns true.
+    pub fn retain_items(&mut self, pos: Position, keep: impl FnMut(&ItemKind) -> bool) {
+        if let Some(items) = self.items.get_mut(&pos) {
+            items.retain(keep);
+            if items.is_empty() {
+                self.items.remove(&pos);
+            }
+        }
     }
 }
 
@@ -43,12 +206,13 @@ impl Index<Position> for TileMap {
     type Output = Tile;
 
     fn index(&self, pos: Position) -> &Self::Output {
-        &self.tiles[pos.x][pos.y]
+        &self.tiles[pos.y * self.width + pos.x]
     }
 }
 
 impl IndexMut<Position> for TileMap {
     fn index_mut(&mut self, pos: Position) -> &mut Self::Output {
-        &mut self.tiles[pos.x][pos.y]
+        self.revision += 1;
+        &mut self.tiles[pos.y * self.width + pos.x]
     }
 }
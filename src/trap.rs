@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use macroquad::prelude::*;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::from_str;
+
+use crate::lua_interface::{LuaInterfaceRc, LuaScripted};
+
+/// Extra path cost for stepping on a trap, so paths go around them when they can.
+pub const TRAP_PATH_PENALTY: u32 = 400;
+
+/// What a trap does to whoever sets it off.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum TrapEffect {
+    Spike {
+        damage: i32,
+    },
+    // Hurts every creature within `radius`, the one stepping in included, and
+    // leaves them poisoned for half as much over `lingering` more ticks
+    PoisonGas {
+        damage: i32,
+        radius: usize,
+        #[serde(default)]
+        lingering: u32,
+    },
+    // Neutral creatures within `radius` turn hostile
+    Alarm {
+        radius: usize,
+    },
+    Teleport,
+    // Drops the player to the floor below, only placed on maps that lead down
+    Pit,
+    // Handled by the `on_step` function of the trap's script
+    Scripted,
+}
+
+/// A trap type from `assets/traps/traps.json`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TrapKind {
+    pub id: u32,
+    pub name: String,
+    pub effect: TrapEffect,
+    #[serde(default)]
+    pub min_tier: u32,
+    // Relative odds of being picked among the traps allowed on a tier
+    pub weight: u32,
+    // Chance for each search next to the trap to reveal it
+    pub detect_chance: f64,
+    pub color: [f32; 4],
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(skip)]
+    pub script_id: u32,
+}
+
+impl TrapKind {
+    pub fn color(&self) -> Color {
+        let [r, g, b, a] = self.color;
+        Color { r, g, b, a }
+    }
+}
+
+impl LuaScripted for TrapKind {
+    fn set_script_id(&mut self, id: u32) {
+        self.script_id = id;
+    }
+    fn get_script_id(&self) -> u32 {
+        self.script_id
+    }
+
+    fn script_path(&self) -> Option<String> {
+        self.script
+            .as_ref()
+            .map(|script| format!("assets/traps/{}", script))
+    }
+
+    fn is_scripted(&self) -> bool {
+        self.script.is_some()
+    }
+
+    fn functions(&self) -> Vec<String> {
+        vec!["on_step".to_string()]
+    }
+}
+
+/// A trap armed on a tile. Hidden traps are only drawn once found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trap {
+    pub kind: u32,
+    pub hidden: bool,
+}
+
+impl Trap {
+    pub fn kind(&self) -> &'static TrapKind {
+        &get_trap_kinds()[self.kind as usize]
+    }
+}
+
+pub async fn load_trap_kinds(lua_interface: &LuaInterfaceRc) -> Vec<TrapKind> {
+    let file = load_string("assets/traps/traps.json").await.unwrap();
+    let mut kinds: Vec<TrapKind> = from_str(&file).unwrap();
+    kinds.sort_by_key(|kind| kind.id);
+
+    for (index, kind) in kinds.iter_mut().enumerate() {
+        if kind.id as usize != index {
+            panic!(
+                "Trap ids must be contiguous, {} has id {}",
+                kind.name, kind.id
+            );
+        }
+        if kind.script.is_some()
+            && let Err(e) = lua_interface.borrow_mut().load_script(kind)
+        {
+            eprintln!("Error loading trap script: {}", e);
+        }
+    }
+
+    kinds
+}
+
+pub static TRAP_KINDS: OnceCell<Vec<TrapKind>> = OnceCell::new();
+
+pub fn set_global_trap_kinds(kinds: Vec<TrapKind>) {
+    TRAP_KINDS
+        .set(kinds)
+        .unwrap_or_else(|_| panic!("GLOBAL_TRAP_KINDS already set!"));
+}
+
+pub fn get_trap_kinds() -> &'static Vec<TrapKind> {
+    TRAP_KINDS.get().expect("GLOBAL_TRAP_KINDS not initialized")
+}
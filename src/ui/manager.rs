@@ -40,6 +40,7 @@ use crate::{
         widget::{AnchorKind, Widget},
         widget_bar::WidgetBar,
         widget_button::WidgetButton,
+        widget_minimap::{FloorView, WidgetMinimap},
         widget_panel::WidgetPanel,
         widget_text::WidgetText,
     },
@@ -82,8 +83,14 @@ pub struct Ui {
     left_panel_id: u32,
     right_panel_id: u32,
     right_panel_tile_description_id: u32,
+    boss_name_id: u32,
+    boss_bar_id: u32,
     character_sheet_id: u32,
     chest_view_id: u32,
+    minimap_id: u32,
+    floor_map_id: u32,
+    floor_map_title_id: u32,
+    floor_map_view_id: u32,
     hp_bar_id: u32,
     mp_bar_id: u32,
     sp_value_id: u32,
@@ -129,8 +136,14 @@ impl Ui {
             left_panel_id: u32::MAX,
             right_panel_id: u32::MAX,
             right_panel_tile_description_id: u32::MAX,
+            boss_name_id: u32::MAX,
+            boss_bar_id: u32::MAX,
             character_sheet_id: u32::MAX,
             chest_view_id: u32::MAX,
+            minimap_id: u32::MAX,
+            floor_map_id: u32::MAX,
+            floor_map_title_id: u32::MAX,
+            floor_map_view_id: u32::MAX,
             hp_bar_id: u32::MAX,
             mp_bar_id: u32::MAX,
             sp_value_id: u32::MAX,
@@ -159,6 +172,7 @@ impl Ui {
         ui.create_right_panel();
         ui.create_character_sheet(spell_types);
         ui.create_chest_view();
+        ui.create_floor_map();
         ui
     }
 
@@ -195,6 +209,30 @@ impl Ui {
         }
     }
 
+    /// Shows `view` on both the minimap and the full floor map.
+    pub fn set_floor_view(&mut self, view: &FloorView) {
+        for id in [self.minimap_id, self.floor_map_view_id] {
+            if let Some(w) = self.widgets.get(id as usize) {
+                let mut minimap_ref = w.borrow_mut();
+                if let Some(minimap) = minimap_ref.as_any_mut().downcast_mut::<WidgetMinimap>()
+                    && minimap.view != *view
+                {
+                    minimap.set_view(view.clone());
+                }
+            }
+        }
+
+        if let Some(w) = self.widgets.get(self.floor_map_title_id as usize) {
+            let mut text_ref = w.borrow_mut();
+            if let Some(text) = text_ref.as_any_mut().downcast_mut::<WidgetText>() {
+                let title = format!("Floor {}", view.floor + 1);
+                if text.text != title {
+                    text.set_text(&title);
+                }
+            }
+        }
+    }
+
     pub fn handle_click(&mut self, mouse_position: PointF) {
         let widgets: Vec<_> = self.widgets.iter().cloned().collect();
         for widget in widgets {
@@ -219,6 +257,29 @@ impl Ui {
         }
     }
 
+    pub fn set_boss_health(&mut self, boss: Option<(&str, u32, u32)>) {
+        if let Some(name_widget) = self.widgets.get(self.boss_name_id as usize) {
+            let mut text_ref = name_widget.borrow_mut();
+            text_ref.set_visible(boss.is_some());
+            if let (Some(text), Some((name, _, _))) =
+                (text_ref.as_any_mut().downcast_mut::<WidgetText>(), boss)
+            {
+                text.set_text(&name.to_string());
+            }
+        }
+
+        if let Some(boss_bar) = self.widgets.get(self.boss_bar_id as usize) {
+            let mut bar_ref = boss_bar.borrow_mut();
+            bar_ref.set_visible(boss.is_some());
+            if let (Some(bar), Some((_, hp, max_hp))) =
+                (bar_ref.as_any_mut().downcast_mut::<WidgetBar>(), boss)
+            {
+                bar.set_text(&format!("{}/{}", hp, max_hp));
+                bar.set_bar_percentage(hp as f32 / max_hp.max(1) as f32);
+            }
+        }
+    }
+
     pub fn set_player_mp(&mut self, mp: u32, max_mp: u32) {
         self.player_mp = mp;
         self.player_max_mp = max_mp;
@@ -436,6 +497,15 @@ impl Ui {
         self.is_focused = !is_visible;
     }
 
+    pub fn toggle_floor_map(&mut self) {
+        let is_visible = self.widgets[self.floor_map_id as usize]
+            .borrow()
+            .is_visible();
+        let mut floor_map = self.widgets[self.floor_map_id as usize].borrow_mut();
+        floor_map.set_visible(!is_visible);
+        self.is_focused = !is_visible;
+    }
+
     pub fn show_chest_view(&mut self, items: &Vec<(u32, String)>) {
         self.set_chest_items(items);
         self.widgets[self.chest_view_id as usize]
@@ -451,6 +521,9 @@ impl Ui {
         self.widgets[self.chest_view_id as usize]
             .borrow_mut()
             .set_visible(false);
+        self.widgets[self.floor_map_id as usize]
+            .borrow_mut()
+            .set_visible(false);
         self.is_focused = false;
     }
 
@@ -852,6 +925,45 @@ impl Ui {
             text.add_anchor_to_parent(AnchorKind::Top, AnchorKind::Top);
             text.add_anchor_to_parent(AnchorKind::Left, AnchorKind::Left);
         }
+
+        self.boss_name_id = self.id_counter + 1;
+        let boss_name = self.create_widget::<WidgetText>(Some(Rc::downgrade(&parent_dyn)));
+        {
+            let mut lbl = boss_name.borrow_mut();
+            lbl.set_color(ORANGE);
+            lbl.set_margin(QuadF::new(10.0, 30.0, 0.0, 0.0));
+            lbl.add_anchor(
+                AnchorKind::Top,
+                self.right_panel_tile_description_id,
+                AnchorKind::Bottom,
+            );
+            lbl.add_anchor_to_parent(AnchorKind::Left, AnchorKind::Left);
+            lbl.set_visible(false);
+        }
+
+        self.boss_bar_id = self.id_counter + 1;
+        let boss_bar = self.create_widget::<WidgetBar>(Some(Rc::downgrade(&parent_dyn)));
+        {
+            let mut bar = boss_bar.borrow_mut();
+            bar.set_size(SizeF::new(340.0, 20.0));
+            bar.set_margin_top(10.0);
+            bar.add_anchor(AnchorKind::Top, self.boss_name_id, AnchorKind::Bottom);
+            bar.add_anchor(AnchorKind::Left, self.boss_name_id, AnchorKind::Left);
+            bar.set_background_color(BLACK);
+            bar.set_bar_color(ORANGE);
+            bar.set_visible(false);
+        }
+
+        self.minimap_id = self.id_counter + 1;
+        let minimap = self.create_widget::<WidgetMinimap>(Some(Rc::downgrade(&parent_dyn)));
+        {
+            let mut minimap = minimap.borrow_mut();
+            // The bottom margin is taken off the height, leaving a 360x360 square
+            minimap.set_size(SizeF::new(360.0, 380.0));
+            minimap.set_margin(QuadF::new(20.0, 0.0, 0.0, 20.0));
+            minimap.add_anchor_to_parent(AnchorKind::Bottom, AnchorKind::Bottom);
+            minimap.add_anchor_to_parent(AnchorKind::Left, AnchorKind::Left);
+        }
     }
 
     fn create_character_sheet(&mut self, spell_types: &Vec<Option<Arc<SpellType>>>) {
@@ -1198,6 +1310,43 @@ impl Ui {
         }
     }
 
+    fn create_floor_map(&mut self) {
+        self.floor_map_id = self.id_counter + 1;
+        let floor_map_rc =
+            self.create_widget::<WidgetPanel>(Some(Rc::downgrade(&self.widgets[ROOT_ID as usize])));
+        {
+            let mut floor_map = floor_map_rc.borrow_mut();
+            floor_map.set_border(WHITE, 2.0);
+            floor_map.add_anchor_to_parent(AnchorKind::Top, AnchorKind::Top);
+            floor_map.add_anchor_to_parent(AnchorKind::Bottom, AnchorKind::Bottom);
+            floor_map.add_anchor(AnchorKind::Left, self.left_panel_id, AnchorKind::Right);
+            floor_map.add_anchor(AnchorKind::Right, self.right_panel_id, AnchorKind::Left);
+            floor_map.set_color(BLACK);
+            floor_map.set_visible(false);
+        }
+
+        let parent_dyn = Rc::clone(&self.widgets[self.floor_map_id as usize]);
+
+        self.floor_map_title_id = self.id_counter + 1;
+        let title = self.create_widget::<WidgetText>(Some(Rc::downgrade(&parent_dyn)));
+        {
+            let mut lbl = title.borrow_mut();
+            lbl.set_text(&"Floor 1".to_string());
+            lbl.set_margin(QuadF::new(10.0, 30.0, 0.0, 0.0));
+            lbl.add_anchor_to_parent(AnchorKind::Top, AnchorKind::Top);
+            lbl.add_anchor_to_parent(AnchorKind::Left, AnchorKind::Left);
+        }
+
+        self.floor_map_view_id = self.id_counter + 1;
+        let view = self.create_widget::<WidgetMinimap>(Some(Rc::downgrade(&parent_dyn)));
+        {
+            let mut view = view.borrow_mut();
+            // Margins keep 20px clear on the sides and bottom, and room for the title
+            view.set_margin(QuadF::new(20.0, 80.0, 40.0, 100.0));
+            view.fill_parent();
+        }
+    }
+
     pub fn add_player_skills(&mut self, skill: &PlayerSpell) {
         let area = &self.widgets[self.left_panel_spells_area_id as usize];
         let children_len = area.borrow().get_children().len();
@@ -30,5 +30,6 @@ mod widget;
 pub mod manager;
 pub mod widget_bar;
 pub mod widget_button;
+pub mod widget_minimap;
 pub mod widget_panel;
 pub mod widget_text;
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MIT
+//
+// Copyright (c) 2025 Alexandre Severino
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::any::Any;
+use std::fmt;
+
+use macroquad::prelude::*;
+
+use std::{cell::RefCell, rc::Weak};
+
+use crate::maps::overworld::VisitedState;
+use crate::ui::{
+    manager::Ui,
+    widget::{Widget, WidgetBase, WidgetBasicConstructor},
+};
+
+/// One map slot of a floor as far as the player knows it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorRoom {
+    pub x: usize,
+    pub y: usize,
+    pub state: VisitedState,
+    // Known ways out, in `Border` order: top, right, bottom, left
+    pub exits: [bool; 4],
+    pub downstairs: bool,
+    pub upstairs: bool,
+}
+
+/// Everything the minimap needs to draw one floor of the overworld.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FloorView {
+    pub floor: usize,
+    pub width: usize,
+    pub height: usize,
+    // Slots missing here have never been seen
+    pub rooms: Vec<FloorRoom>,
+    pub player: Option<(usize, usize)>,
+}
+
+pub struct WidgetMinimap {
+    pub base: WidgetBase,
+    pub view: FloorView,
+}
+
+impl WidgetMinimap {
+    pub fn draw(&self, _ui: &Ui) {
+        if !self.is_visible() || self.view.width == 0 || self.view.height == 0 {
+            return;
+        }
+
+        let Some(quad) = self.base.computed_quad else {
+            return;
+        };
+
+        let cell = (quad.w / self.view.width as f32).min(quad.h / self.view.height as f32);
+        let left = quad.x + (quad.w - cell * self.view.width as f32) / 2.0;
+        let top = quad.y + (quad.h - cell * self.view.height as f32) / 2.0;
+        let room_size = cell * 0.7;
+        let inset = (cell - room_size) / 2.0;
+
+        for x in 0..self.view.width {
+            for y in 0..self.view.height {
+                draw_rectangle_lines(
+                    left + x as f32 * cell + inset,
+                    top + y as f32 * cell + inset,
+                    room_size,
+                    room_size,
+                    1.0,
+                    DARKGRAY,
+                );
+            }
+        }
+
+        for room in &self.view.rooms {
+            let room_x = left + room.x as f32 * cell + inset;
+            let room_y = top + room.y as f32 * cell + inset;
+            let fill = match room.state {
+                VisitedState::Visited => GRAY,
+                VisitedState::Peeked => Color::new(0.25, 0.25, 0.3, 1.0),
+                VisitedState::Unvisited => continue,
+            };
+            draw_rectangle(room_x, room_y, room_size, room_size, fill);
+
+            // Corridors reaching out towards the neighboring slots
+            let center_x = room_x + room_size / 2.0;
+            let center_y = room_y + room_size / 2.0;
+            let corridor = inset.max(2.0);
+            let thickness = (cell / 8.0).max(2.0);
+            let exits = [
+                (center_x, room_y - corridor / 2.0, thickness, corridor),
+                (
+                    room_x + room_size + corridor / 2.0,
+                    center_y,
+                    corridor,
+                    thickness,
+                ),
+                (
+                    center_x,
+                    room_y + room_size + corridor / 2.0,
+                    thickness,
+                    corridor,
+                ),
+                (room_x - corridor / 2.0, center_y, corridor, thickness),
+            ];
+            for (exit, (x, y, w, h)) in room.exits.iter().zip(exits) {
+                if *exit {
+                    draw_rectangle(x - w / 2.0, y - h / 2.0, w, h, LIGHTGRAY);
+                }
+            }
+
+            if room.downstairs {
+                draw_circle(center_x, center_y, room_size / 5.0, BLACK);
+            }
+            if room.upstairs {
+                draw_circle_lines(center_x, center_y, room_size / 4.0, 2.0, WHITE);
+            }
+        }
+
+        if let Some((x, y)) = self.view.player {
+            draw_rectangle_lines(
+                left + x as f32 * cell + inset,
+                top + y as f32 * cell + inset,
+                room_size,
+                room_size,
+                3.0,
+                YELLOW,
+            );
+        }
+    }
+
+    pub fn set_view(&mut self, view: FloorView) {
+        self.view = view;
+    }
+}
+
+impl fmt::Debug for WidgetMinimap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WidgetMinimap").finish()
+    }
+}
+
+impl WidgetBasicConstructor for WidgetMinimap {
+    fn basic_constructor(id: u32, parent: Option<Weak<RefCell<dyn Widget>>>) -> Self {
+        WidgetMinimap {
+            base: WidgetBase::new(id, parent),
+            view: FloorView::default(),
+        }
+    }
+}
+
+impl_widget!(WidgetMinimap, base);